@@ -0,0 +1,202 @@
+//! Search index benchmarks at a realistic scale.
+//!
+//! Builds an encrypted index of synthetic messages (1M by default; set
+//! `NODES_SEARCH_BENCH_DOCS` to change it) and times queries and incremental
+//! updates against it. Run with `cargo bench --bench search`.
+
+#[allow(dead_code)]
+#[path = "../src/search/mod.rs"]
+mod search;
+
+use std::path::PathBuf;
+use std::time::Instant;
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use rusqlite::Connection;
+use search::{ContentFilter, DocumentKind, SearchDocument, SearchFilters};
+
+const DEFAULT_DOCS: usize = 1_000_000;
+const VOCABULARY: usize = 20_000;
+const AUTHORS: usize = 500;
+const CHANNELS: usize = 200;
+const BATCH: usize = 10_000;
+const START: i64 = 1_700_000_000_000;
+
+struct Corpus {
+    words: Vec<String>,
+    rng: StdRng,
+}
+
+impl Corpus {
+    fn new() -> Self {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut words: Vec<String> = [
+            "meeting",
+            "deploy",
+            "deployment",
+            "release",
+            "review",
+            "tomorrow",
+            "server",
+            "channel",
+            "message",
+            "picture",
+            "the",
+            "and",
+            "for",
+            "with",
+            "this",
+            "that",
+        ]
+        .iter()
+        .map(|w| w.to_string())
+        .collect();
+        while words.len() < VOCABULARY {
+            let len = rng.gen_range(3..11);
+            words.push(
+                (0..len)
+                    .map(|_| rng.gen_range(b'a'..=b'z') as char)
+                    .collect(),
+            );
+        }
+        Self { words, rng }
+    }
+
+    /// Zipf-like: low indexes are far more common.
+    fn word(&mut self) -> &str {
+        let x: f64 = self.rng.gen();
+        &self.words[((x * x * x) * self.words.len() as f64) as usize]
+    }
+
+    fn document(&mut self, n: usize) -> SearchDocument {
+        let len = self.rng.gen_range(4..30);
+        let mut content = (0..len)
+            .map(|_| self.word().to_owned())
+            .collect::<Vec<_>>()
+            .join(" ");
+        if self.rng.gen_ratio(1, 20) {
+            content.push_str(" https://example.com/page");
+        }
+        let attachments = self.rng.gen_ratio(1, 25).then(|| {
+            let mime = if self.rng.gen_bool(0.5) {
+                "image/png"
+            } else {
+                "application/pdf"
+            };
+            format!(r#"[{{"name":"file","mimeType":"{mime}"}}]"#)
+        });
+        SearchDocument {
+            id: format!("m{n:08}"),
+            kind: DocumentKind::Message,
+            content,
+            author_key: format!("author{}", self.rng.gen_range(0..AUTHORS)),
+            timestamp: START + n as i64 * 1000,
+            channel_id: Some(format!("channel{}", self.rng.gen_range(0..CHANNELS))),
+            node_id: Some("node".into()),
+            conversation_id: None,
+            attachments,
+        }
+    }
+}
+
+fn open(path: &PathBuf) -> Connection {
+    let conn = Connection::open(path).unwrap();
+    // Same page encryption and journaling as the app's database.
+    conn.execute_batch(&format!("PRAGMA key = \"x'{}'\";", "ab".repeat(32)))
+        .unwrap();
+    conn.pragma_update(None, "journal_mode", "WAL").unwrap();
+    conn.pragma_update(None, "synchronous", "NORMAL").unwrap();
+    conn.pragma_update(None, "cache_size", -32 * 1024).unwrap();
+    conn
+}
+
+fn build(docs: usize) -> (Connection, Corpus, PathBuf) {
+    let path = std::env::temp_dir().join(format!("nodes-search-bench-{}.db", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+    let mut conn = open(&path);
+    conn.execute_batch(search::SCHEMA).unwrap();
+
+    let mut corpus = Corpus::new();
+    let started = Instant::now();
+    for start in (0..docs).step_by(BATCH) {
+        let batch: Vec<_> = (start..(start + BATCH).min(docs))
+            .map(|n| corpus.document(n))
+            .collect();
+        let tx = conn.transaction().unwrap();
+        search::index(&tx, &batch).unwrap();
+        tx.commit().unwrap();
+    }
+    eprintln!("indexed {docs} documents in {:.1?}", started.elapsed());
+    (conn, corpus, path)
+}
+
+fn terms(query: &str) -> Vec<String> {
+    query.split_whitespace().map(String::from).collect()
+}
+
+fn bench(c: &mut Criterion) {
+    let docs = std::env::var("NODES_SEARCH_BENCH_DOCS")
+        .ok()
+        .and_then(|n| n.parse().ok())
+        .unwrap_or(DEFAULT_DOCS);
+    let (conn, mut corpus, path) = build(docs);
+    let none = SearchFilters::default();
+
+    let mut group = c.benchmark_group(format!("search/{docs}"));
+    group.sample_size(20);
+    let cases: [(&str, &str, SearchFilters); 7] = [
+        ("rare term", "deployment", none.clone()),
+        ("common term", "the", none.clone()),
+        ("prefix", "depl", none.clone()),
+        ("fuzzy", "meetng", none.clone()),
+        ("several terms", "release review server", none.clone()),
+        (
+            "term in channel",
+            "meeting",
+            SearchFilters {
+                channel: Some("channel7".into()),
+                ..Default::default()
+            },
+        ),
+        (
+            "filters only",
+            "",
+            SearchFilters {
+                from: Some("author3".into()),
+                has: Some(ContentFilter::Image),
+                ..Default::default()
+            },
+        ),
+    ];
+    for (name, query, filters) in &cases {
+        let terms = terms(query);
+        group.bench_function(*name, |b| {
+            b.iter(|| search::search(&conn, &terms, filters, None).unwrap())
+        });
+    }
+
+    let mut next = docs;
+    group.bench_function("index one new message", |b| {
+        b.iter_batched(
+            || {
+                next += 1;
+                corpus.document(next)
+            },
+            |document| search::index(&conn, &[document]).unwrap(),
+            BatchSize::SmallInput,
+        )
+    });
+    group.finish();
+
+    drop(conn);
+    for suffix in ["", "-wal", "-shm"] {
+        let mut file = path.clone().into_os_string();
+        file.push(suffix);
+        let _ = std::fs::remove_file(file);
+    }
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
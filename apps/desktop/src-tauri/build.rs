@@ -1,3 +1,80 @@
+/// Every command registered in `main.rs`. Each gets an `allow-<command>`
+/// permission, and windows can only call the ones their capability lists.
+const COMMANDS: &[&str] = &[
+    "deep_link_ready",
+    "hotkeys_conflicts",
+    "keystore_save",
+    "keystore_load",
+    "keystore_list",
+    "keystore_delete",
+    "keystore_migrate_legacy",
+    "keystore_create",
+    "keystore_unlock",
+    "keystore_change_passphrase",
+    "keystore_lock",
+    "vault_enable",
+    "vault_disable",
+    "vault_unlock",
+    "identity_export_mnemonic",
+    "identity_import_mnemonic",
+    "identity_split_backup",
+    "identity_export_share_file",
+    "identity_read_share_file",
+    "identity_recover_backup",
+    "inbox_add",
+    "inbox_page",
+    "inbox_mark_read",
+    "inbox_mark_all_read",
+    "inbox_remove",
+    "inbox_clear",
+    "inbox_unread",
+    "sea_sign",
+    "sea_verify",
+    "sea_secret",
+    "sea_encrypt",
+    "sea_decrypt",
+    "file_encrypt",
+    "file_decrypt",
+    "dm_session_encrypt",
+    "dm_session_decrypt",
+    "dm_session_reset",
+    "group_rotate",
+    "group_distribute",
+    "group_accept",
+    "group_encrypt",
+    "group_decrypt",
+    "group_forget",
+    "message_cache_put",
+    "message_cache_history",
+    "message_cache_set_reactions",
+    "notification_show",
+    "notification_clear",
+    "popout_open",
+    "popout_focus",
+    "popout_close",
+    "popout_list",
+    "popout_target",
+    "popout_publish",
+    "quiet_hours_status",
+    "relay_status",
+    "search_index",
+    "search_remove",
+    "search_query",
+    "search_stats",
+    "search_clear",
+    "settings_get",
+    "settings_set",
+    "app_quit",
+    "shutdown_register",
+    "shutdown_unregister",
+    "shutdown_ack",
+    "tray_update",
+];
+
 fn main() {
-    tauri_build::build()
+    tauri_build::try_build(
+        tauri_build::Attributes::new()
+            .app_manifest(tauri_build::AppManifest::new().commands(COMMANDS)),
+    )
+    .expect("failed to run tauri-build");
 }
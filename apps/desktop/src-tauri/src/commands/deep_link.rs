@@ -0,0 +1,13 @@
+//! `nodes://` link delivery. See [`crate::deep_link`].
+
+use tauri::State;
+
+use crate::deep_link::{DeepLinks, PendingLinks};
+
+/// Called once the frontend is listening for `deep-link:open` and
+/// `deep-link:error`. Returns links that arrived before then, such as the
+/// one the app was launched with.
+#[tauri::command]
+pub fn deep_link_ready(deep_links: State<'_, DeepLinks>) -> PendingLinks {
+    deep_links.ready()
+}
@@ -0,0 +1,69 @@
+//! Streaming DM attachment encryption from disk paths.
+//!
+//! Progress is reported as `file-crypto:progress` events tagged with the
+//! caller-supplied `transferId`, at most once per percent.
+
+use std::path::PathBuf;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+
+use super::run_blocking;
+use crate::crypto::file::{self, DEFAULT_CHUNK_SIZE};
+
+#[derive(Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Progress<'a> {
+    transfer_id: &'a str,
+    processed: u64,
+    total: u64,
+}
+
+#[tauri::command]
+pub async fn file_encrypt(
+    input: PathBuf,
+    output: PathBuf,
+    secret: String,
+    transfer_id: String,
+    app: AppHandle,
+) -> Result<(), String> {
+    run_blocking(move || {
+        let mut report = progress_reporter(&app, &transfer_id);
+        file::encrypt_file(&input, &output, &secret, DEFAULT_CHUNK_SIZE, &mut report)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn file_decrypt(
+    input: PathBuf,
+    output: PathBuf,
+    secret: String,
+    transfer_id: String,
+    app: AppHandle,
+) -> Result<(), String> {
+    run_blocking(move || {
+        let mut report = progress_reporter(&app, &transfer_id);
+        file::decrypt_file(&input, &output, &secret, &mut report)
+    })
+    .await
+}
+
+fn progress_reporter<'a>(app: &'a AppHandle, transfer_id: &'a str) -> impl FnMut(u64, u64) + 'a {
+    let mut last_percent = None;
+    move |processed, total| {
+        let percent = (processed * 100).checked_div(total).unwrap_or(100);
+        if last_percent == Some(percent) && processed < total {
+            return;
+        }
+        last_percent = Some(percent);
+        let _ = app.emit(
+            "file-crypto:progress",
+            Progress {
+                transfer_id,
+                processed,
+                total,
+            },
+        );
+    }
+}
@@ -0,0 +1,101 @@
+//! Sender-key encryption for private channels.
+//!
+//! `message-transport.ts` stores the `NDG1…` output of `group_encrypt` as
+//! the message content. Distributions are published in the graph for the
+//! members to `group_accept`; `moderation-manager.ts` calls `group_rotate`
+//! with the remaining members after a kick or ban.
+
+use tauri::{AppHandle, Manager};
+
+use super::run_blocking;
+use crate::crypto::group::{Distribution, Member};
+use crate::crypto::KeyPair;
+use crate::keystore::groups::GroupKeyStore;
+
+#[tauri::command]
+pub async fn group_rotate(
+    pair: KeyPair,
+    channel_id: String,
+    members: Vec<Member>,
+    app: AppHandle,
+) -> Result<Distribution, String> {
+    run_blocking(move || {
+        app.state::<GroupKeyStore>()
+            .with_channel(&pair, &channel_id, |state| state.rotate(&pair, &members))
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn group_distribute(
+    pair: KeyPair,
+    channel_id: String,
+    members: Vec<Member>,
+    app: AppHandle,
+) -> Result<Distribution, String> {
+    run_blocking(move || {
+        app.state::<GroupKeyStore>()
+            .with_channel(&pair, &channel_id, |state| {
+                state.distribute(&pair, &members)
+            })
+    })
+    .await
+}
+
+/// `rotated` is set by `moderation-manager.ts` for the distribution that
+/// follows a kick or ban; see [`GroupState::accept`].
+///
+/// [`GroupState::accept`]: crate::crypto::group::GroupState::accept
+#[tauri::command]
+pub async fn group_accept(
+    pair: KeyPair,
+    sender_epub: String,
+    distribution: Distribution,
+    rotated: Option<bool>,
+    app: AppHandle,
+) -> Result<(), String> {
+    run_blocking(move || {
+        app.state::<GroupKeyStore>()
+            .with_channel(&pair, &distribution.channel_id, |state| {
+                state.accept(&pair, &sender_epub, &distribution, rotated.unwrap_or(false))
+            })
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn group_encrypt(
+    pair: KeyPair,
+    channel_id: String,
+    plaintext: String,
+    app: AppHandle,
+) -> Result<String, String> {
+    run_blocking(move || {
+        app.state::<GroupKeyStore>()
+            .with_channel(&pair, &channel_id, |state| state.encrypt(&pair, &plaintext))
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn group_decrypt(
+    pair: KeyPair,
+    channel_id: String,
+    envelope: String,
+    app: AppHandle,
+) -> Result<String, String> {
+    run_blocking(move || {
+        app.state::<GroupKeyStore>()
+            .with_channel(&pair, &channel_id, |state| state.decrypt(&envelope))
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn group_forget(
+    my_pub: String,
+    channel_id: String,
+    app: AppHandle,
+) -> Result<bool, String> {
+    run_blocking(move || app.state::<GroupKeyStore>().forget(&my_pub, &channel_id)).await
+}
@@ -0,0 +1,11 @@
+//! Global hotkeys. See [`crate::hotkeys`].
+
+use tauri::State;
+
+use crate::hotkeys::{Conflict, Hotkeys};
+
+/// Bindings from the settings that aren't registered, and why.
+#[tauri::command]
+pub fn hotkeys_conflicts(hotkeys: State<'_, Hotkeys>) -> Vec<Conflict> {
+    hotkeys.conflicts()
+}
@@ -0,0 +1,78 @@
+//! Identity backup and recovery commands.
+
+use std::fs;
+use std::path::PathBuf;
+
+use crate::crypto::shamir::{self, Share, ShareFile};
+use crate::crypto::{mnemonic, KeyBackup, KeyPair};
+
+/// Export the keypair as a 48-word recovery phrase.
+#[tauri::command]
+pub fn identity_export_mnemonic(keypair: KeyPair) -> Result<String, String> {
+    mnemonic::export(&keypair)
+        .map(|phrase| phrase.to_string())
+        .map_err(|e| e.to_string())
+}
+
+/// Restore a keypair from a recovery phrase.
+#[tauri::command]
+pub fn identity_import_mnemonic(phrase: String) -> Result<KeyPair, String> {
+    mnemonic::import(&phrase).map_err(|e| e.to_string())
+}
+
+/// Split a backup into `total` recovery shares, `threshold` of which restore
+/// it. Returns the text form of each share, in index order.
+#[tauri::command]
+pub fn identity_split_backup(
+    backup: KeyBackup,
+    threshold: u8,
+    total: u8,
+) -> Result<Vec<String>, String> {
+    let secret = serde_json::to_vec(&backup).map_err(|e| e.to_string())?;
+    let shares = shamir::split(&secret, threshold, total).map_err(|e| e.to_string())?;
+    Ok(shares.iter().map(Share::to_text).collect())
+}
+
+/// Save one share as a JSON file, labelled with the identity it belongs to.
+#[tauri::command]
+pub fn identity_export_share_file(
+    share: String,
+    pub_key: String,
+    label: String,
+    path: PathBuf,
+) -> Result<(), String> {
+    let parsed = Share::from_text(&share).map_err(|e| e.to_string())?;
+    let file = ShareFile {
+        version: 1,
+        pub_key,
+        label,
+        threshold: parsed.threshold,
+        total: parsed.total,
+        index: parsed.index,
+        share: parsed.to_text(),
+    };
+    let json = serde_json::to_vec_pretty(&file).map_err(|e| e.to_string())?;
+    fs::write(path, json).map_err(|e| e.to_string())
+}
+
+/// Read a share file back into its text form.
+#[tauri::command]
+pub fn identity_read_share_file(path: PathBuf) -> Result<String, String> {
+    let bytes = fs::read(path).map_err(|e| e.to_string())?;
+    let file: ShareFile = serde_json::from_slice(&bytes).map_err(|e| e.to_string())?;
+    // Validate before handing it back so a damaged file is caught early.
+    Share::from_text(&file.share).map_err(|e| e.to_string())?;
+    Ok(file.share)
+}
+
+/// Recombine text shares into the original backup.
+#[tauri::command]
+pub fn identity_recover_backup(shares: Vec<String>) -> Result<KeyBackup, String> {
+    let parsed = shares
+        .iter()
+        .map(|text| Share::from_text(text))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    let secret = shamir::combine(&parsed).map_err(|e| e.to_string())?;
+    serde_json::from_slice(&secret).map_err(|_| "Recovered data is not a valid backup".to_string())
+}
@@ -0,0 +1,110 @@
+//! The notification inbox. See [`crate::db::inbox`].
+//!
+//! Every change is followed by `inbox:unread` with the new unread counts,
+//! which also go to the tray.
+
+use tauri::{AppHandle, Emitter, Manager};
+
+use super::run_blocking;
+use crate::db::inbox::{
+    InboxNotification, InboxPage, InboxQuery, InboxScope, NewNotification, UnreadCounts,
+};
+use crate::db::{Database, DbError};
+use crate::settings::Settings;
+use crate::tray;
+
+/// Add notifications, skipping messages already in the inbox, and return
+/// the ones added.
+#[tauri::command]
+pub async fn inbox_add(
+    notifications: Vec<NewNotification>,
+    app: AppHandle,
+) -> Result<Vec<InboxNotification>, String> {
+    run_blocking(move || {
+        let retention = app.state::<Settings>().get().inbox_retention;
+        let added = app
+            .state::<Database>()
+            .inbox_add(&notifications, &retention)?;
+        publish_unread(&app);
+        Ok::<_, DbError>(added)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn inbox_page(query: Option<InboxQuery>, app: AppHandle) -> Result<InboxPage, String> {
+    run_blocking(move || {
+        app.state::<Database>()
+            .inbox_page(&query.unwrap_or_default())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn inbox_mark_read(ids: Vec<String>, app: AppHandle) -> Result<usize, String> {
+    run_blocking(move || {
+        let changed = app.state::<Database>().inbox_mark_read(&ids)?;
+        publish_unread(&app);
+        Ok::<_, DbError>(changed)
+    })
+    .await
+}
+
+/// Mark everything in a node or channel read, or everything at all without
+/// a scope.
+#[tauri::command]
+pub async fn inbox_mark_all_read(
+    scope: Option<InboxScope>,
+    app: AppHandle,
+) -> Result<usize, String> {
+    run_blocking(move || {
+        let changed = app
+            .state::<Database>()
+            .inbox_mark_all_read(&scope.unwrap_or_default())?;
+        publish_unread(&app);
+        Ok::<_, DbError>(changed)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn inbox_remove(ids: Vec<String>, app: AppHandle) -> Result<usize, String> {
+    run_blocking(move || {
+        let removed = app.state::<Database>().inbox_remove(&ids)?;
+        publish_unread(&app);
+        Ok::<_, DbError>(removed)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn inbox_clear(scope: Option<InboxScope>, app: AppHandle) -> Result<usize, String> {
+    run_blocking(move || {
+        let removed = app
+            .state::<Database>()
+            .inbox_clear(&scope.unwrap_or_default())?;
+        publish_unread(&app);
+        Ok::<_, DbError>(removed)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn inbox_unread(app: AppHandle) -> Result<UnreadCounts, String> {
+    run_blocking(move || app.state::<Database>().inbox_unread()).await
+}
+
+/// Send the current unread counts to the frontend and the tray. A locked
+/// database counts as nothing unread.
+pub(super) fn publish_unread(app: &AppHandle) {
+    let counts = match app.state::<Database>().inbox_unread() {
+        Ok(counts) => counts,
+        Err(DbError::Locked) => UnreadCounts::default(),
+        Err(e) => {
+            eprintln!("[inbox] Failed to count unread notifications: {e}");
+            return;
+        }
+    };
+    tray::set_unread(app, counts.total);
+    let _ = app.emit("inbox:unread", &counts);
+}
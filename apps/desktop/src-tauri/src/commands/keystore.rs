@@ -0,0 +1,156 @@
+//! Keystore commands — replaces the `nodes:keystore` localStorage entry.
+
+use serde::Serialize;
+use tauri::{AppHandle, Manager, State};
+
+use super::run_blocking;
+use crate::crypto::KeyPair;
+use crate::db::Database;
+use crate::keystore::wrap::KdfParams;
+use crate::keystore::{
+    EncryptedKeystore, Keystore, KeystoreError, KeystoreSummary, MigrationOutcome,
+};
+use crate::vault::Vault;
+
+#[tauri::command]
+pub fn keystore_save(
+    keystore: EncryptedKeystore,
+    store: State<'_, Keystore>,
+) -> Result<KeystoreSummary, String> {
+    store.save(&keystore).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn keystore_load(
+    pub_key: String,
+    store: State<'_, Keystore>,
+) -> Result<Option<EncryptedKeystore>, String> {
+    match store.load(&pub_key) {
+        Ok(keystore) => Ok(Some(keystore)),
+        Err(KeystoreError::NotFound) => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[tauri::command]
+pub fn keystore_list(store: State<'_, Keystore>) -> Result<Vec<KeystoreSummary>, String> {
+    store.list().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn keystore_delete(
+    pub_key: String,
+    store: State<'_, Keystore>,
+    vault: State<'_, Vault>,
+) -> Result<bool, String> {
+    let removed = store.delete(&pub_key).map_err(|e| e.to_string())?;
+    vault.forget(&pub_key).map_err(|e| e.to_string())?;
+    Ok(removed)
+}
+
+/// Called once at startup with whatever is in localStorage (or `null`).
+/// The frontend may remove its copy once this resolves.
+#[tauri::command]
+pub fn keystore_migrate_legacy(
+    keystore: Option<EncryptedKeystore>,
+    store: State<'_, Keystore>,
+) -> Result<MigrationOutcome, String> {
+    store
+        .migrate_legacy(keystore.as_ref())
+        .map_err(|e| e.to_string())
+}
+
+/// Result of `keystore_change_passphrase`. The new passphrase is in effect
+/// even when the local database couldn't be rekeyed.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PassphraseChanged {
+    #[serde(flatten)]
+    summary: KeystoreSummary,
+    /// Why the database is still under its old page key, if it is.
+    rekey_error: Option<String>,
+}
+
+// Argon2 is deliberately slow, so the commands below run on the blocking pool
+// instead of the main thread.
+
+#[tauri::command]
+pub async fn keystore_create(
+    keypair: KeyPair,
+    passphrase: String,
+    kdf: Option<KdfParams>,
+    app: AppHandle,
+) -> Result<KeystoreSummary, String> {
+    run_blocking(move || {
+        let summary =
+            app.state::<Keystore>()
+                .create(&keypair, &passphrase, kdf.unwrap_or_default())?;
+        unlock_database(&app, &keypair);
+        Ok::<_, KeystoreError>(summary)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn keystore_unlock(
+    pub_key: String,
+    passphrase: String,
+    app: AppHandle,
+) -> Result<KeyPair, String> {
+    run_blocking(move || {
+        let keypair = app.state::<Keystore>().unlock(&pub_key, &passphrase)?;
+        unlock_database(&app, &keypair);
+        Ok::<_, KeystoreError>(keypair)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn keystore_change_passphrase(
+    pub_key: String,
+    current_passphrase: String,
+    new_passphrase: String,
+    kdf: Option<KdfParams>,
+    app: AppHandle,
+) -> Result<PassphraseChanged, String> {
+    run_blocking(move || {
+        let summary = app.state::<Keystore>().change_passphrase(
+            &pub_key,
+            &current_passphrase,
+            &new_passphrase,
+            kdf.unwrap_or_default(),
+        )?;
+        // The cached wrapping key belongs to the old passphrase.
+        if let Err(e) = app.state::<Vault>().forget(&pub_key) {
+            eprintln!("[vault] failed to clear stale credential: {e}");
+        }
+        // Rotate the local database key along with it.
+        let rekey_error = app
+            .state::<Database>()
+            .rekey(&pub_key)
+            .err()
+            .map(|e| e.to_string());
+        Ok::<_, KeystoreError>(PassphraseChanged {
+            summary,
+            rekey_error,
+        })
+    })
+    .await
+}
+
+/// Close the local database on logout. Unlocking opens it again.
+#[tauri::command]
+pub fn keystore_lock(app: AppHandle, database: State<'_, Database>) {
+    database.lock();
+    super::inbox::publish_unread(&app);
+}
+
+/// Open the identity's local database and show its unread notifications.
+/// The cache is not essential, so a failure is logged rather than failing
+/// the unlock.
+pub(super) fn unlock_database(app: &AppHandle, keypair: &KeyPair) {
+    if let Err(e) = app.state::<Database>().unlock(keypair) {
+        eprintln!("[db] failed to open local database: {e}");
+    }
+    super::inbox::publish_unread(app);
+}
@@ -0,0 +1,47 @@
+//! Local message cache, so history is available offline and on cold start.
+//!
+//! The transport writes every message it sees through `message_cache_put`
+//! and reads `message_cache_history` before (or instead of) asking relays.
+
+use std::collections::BTreeMap;
+
+use tauri::{AppHandle, Manager};
+
+use super::run_blocking;
+use crate::db::messages::{CachedMessage, HistoryOpts, Reaction};
+use crate::db::Database;
+
+#[tauri::command]
+pub async fn message_cache_put(
+    messages: Vec<CachedMessage>,
+    app: AppHandle,
+) -> Result<usize, String> {
+    run_blocking(move || app.state::<Database>().put_messages(&messages)).await
+}
+
+#[tauri::command]
+pub async fn message_cache_history(
+    channel_id: String,
+    opts: Option<HistoryOpts>,
+    app: AppHandle,
+) -> Result<Vec<CachedMessage>, String> {
+    run_blocking(move || {
+        app.state::<Database>()
+            .history(&channel_id, &opts.unwrap_or_default())
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn message_cache_set_reactions(
+    channel_id: String,
+    message_id: String,
+    reactions: BTreeMap<String, Vec<Reaction>>,
+    app: AppHandle,
+) -> Result<(), String> {
+    run_blocking(move || {
+        app.state::<Database>()
+            .set_reactions(&channel_id, &message_id, &reactions)
+    })
+    .await
+}
@@ -1,6 +1,37 @@
-// Tauri IPC commands will be registered here in future milestones.
-// Example pattern:
-// #[tauri::command]
-// pub fn greet(name: &str) -> String {
-//     format!("Hello, {}! Welcome to Nodes.", name)
-// }
+//! Tauri IPC commands exposed to the frontend.
+//!
+//! Each submodule wraps one native subsystem. Errors are returned as strings
+//! so the frontend receives them as rejected promises with a readable message.
+
+pub mod deep_link;
+pub mod files;
+pub mod groups;
+pub mod hotkeys;
+pub mod identity;
+pub mod inbox;
+pub mod keystore;
+pub mod messages;
+pub mod notifications;
+pub mod popout;
+pub mod quiet_hours;
+pub mod relay;
+pub mod sea;
+pub mod search;
+pub mod sessions;
+pub mod settings;
+pub mod shutdown;
+pub mod tray;
+pub mod vault;
+
+/// Run a slow or blocking operation off the main thread.
+async fn run_blocking<T, E, F>(f: F) -> Result<T, String>
+where
+    T: Send + 'static,
+    E: std::fmt::Display + Send + 'static,
+    F: FnOnce() -> Result<T, E> + Send + 'static,
+{
+    tauri::async_runtime::spawn_blocking(f)
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())
+}
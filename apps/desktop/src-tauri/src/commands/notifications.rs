@@ -0,0 +1,26 @@
+//! Native notifications. See [`crate::notifications`].
+
+use tauri::{AppHandle, Manager};
+
+use crate::notifications::{Conversation, MessageNotification, Notifier};
+
+/// Show `notification`, grouped with others from the same conversation.
+#[tauri::command]
+pub async fn notification_show(
+    notification: MessageNotification,
+    app: AppHandle,
+) -> Result<(), String> {
+    app.state::<Notifier>()
+        .show(&app, notification)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// Remove the conversation's notification, e.g. once the user has read it.
+#[tauri::command]
+pub async fn notification_clear(conversation: Conversation, app: AppHandle) -> Result<(), String> {
+    app.state::<Notifier>()
+        .clear(&conversation)
+        .await
+        .map_err(|e| e.to_string())
+}
@@ -0,0 +1,47 @@
+//! Pop-out windows. See [`crate::popout`].
+
+use tauri::{AppHandle, State, WebviewWindow};
+
+use crate::popout::{self, OpenPopout, Popout, Popouts, StateUpdate};
+
+/// Open `target` in its own window, or focus it if it's already open, and
+/// return the window's label. Async so the window isn't created on the main
+/// thread while it's handling the call.
+#[tauri::command]
+pub async fn popout_open(
+    target: Popout,
+    title: Option<String>,
+    app: AppHandle,
+) -> Result<String, String> {
+    popout::open(&app, target, title)
+        .map(|window| window.label().to_string())
+        .map_err(|e| e.to_string())
+}
+
+/// Focus the window showing `target`. Returns false if it isn't popped out.
+#[tauri::command]
+pub fn popout_focus(target: Popout, app: AppHandle) -> bool {
+    popout::focus(&app, &target).is_some()
+}
+
+#[tauri::command]
+pub fn popout_close(target: Popout, app: AppHandle) -> Result<(), String> {
+    popout::close(&app, &target).map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub fn popout_list(popouts: State<'_, Popouts>) -> Vec<OpenPopout> {
+    popouts.list()
+}
+
+/// What the calling window should show; `None` for the main window.
+#[tauri::command]
+pub fn popout_target(window: WebviewWindow, popouts: State<'_, Popouts>) -> Option<Popout> {
+    popouts.target(window.label())
+}
+
+/// Pass `update` on to the other windows as `popout:state`.
+#[tauri::command]
+pub fn popout_publish(update: StateUpdate, window: WebviewWindow, app: AppHandle) {
+    popout::publish(&app, window.label(), &update);
+}
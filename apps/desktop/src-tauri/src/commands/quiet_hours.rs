@@ -0,0 +1,11 @@
+//! Quiet hours. See [`crate::quiet_hours`].
+
+use tauri::State;
+
+use crate::quiet_hours::{QuietHours, QuietStatus};
+
+/// Whether quiet hours are on, until when, and how much they've held back.
+#[tauri::command]
+pub fn quiet_hours_status(quiet_hours: State<'_, QuietHours>) -> QuietStatus {
+    quiet_hours.status()
+}
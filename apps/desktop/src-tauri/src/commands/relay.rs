@@ -0,0 +1,12 @@
+//! The built-in Gun relay. See [`crate::relay`].
+
+use tauri::State;
+
+use crate::relay::{Relay, RelayStatus};
+
+/// Whether the relay is running, the URLs peers reach it at, and how many
+/// are connected.
+#[tauri::command]
+pub fn relay_status(relay: State<'_, Relay>) -> RelayStatus {
+    relay.status()
+}
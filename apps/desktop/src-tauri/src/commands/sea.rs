@@ -0,0 +1,71 @@
+//! Native SEA operations, batched so history loads cost one IPC round trip.
+//!
+//! Per-item failures in `sea_verify` and `sea_decrypt` resolve to `null`
+//! (matching SEA's own `undefined`) rather than failing the whole batch.
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::run_blocking;
+use crate::crypto::{sea, KeyPair};
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignedItem {
+    pub signed: String,
+    #[serde(rename = "pub")]
+    pub pub_key: String,
+}
+
+#[tauri::command]
+pub async fn sea_sign(messages: Vec<Value>, pair: KeyPair) -> Result<Vec<String>, String> {
+    run_blocking(move || {
+        messages
+            .iter()
+            .map(|m| sea::sign(m, &pair.priv_key))
+            .collect::<Result<Vec<_>, _>>()
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn sea_verify(items: Vec<SignedItem>) -> Result<Vec<Option<Value>>, String> {
+    run_blocking(move || {
+        Ok::<_, String>(
+            items
+                .iter()
+                .map(|item| sea::verify(&item.signed, &item.pub_key).ok().flatten())
+                .collect(),
+        )
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn sea_secret(epub: String, pair: KeyPair) -> Result<String, String> {
+    run_blocking(move || sea::secret(&epub, &pair.epriv)).await
+}
+
+#[tauri::command]
+pub async fn sea_encrypt(messages: Vec<Value>, key: String) -> Result<Vec<String>, String> {
+    run_blocking(move || {
+        messages
+            .iter()
+            .map(|m| sea::encrypt(m, &key))
+            .collect::<Result<Vec<_>, _>>()
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn sea_decrypt(items: Vec<String>, key: String) -> Result<Vec<Option<Value>>, String> {
+    run_blocking(move || {
+        Ok::<_, String>(
+            items
+                .iter()
+                .map(|item| sea::decrypt_value(item, &key).ok())
+                .collect(),
+        )
+    })
+    .await
+}
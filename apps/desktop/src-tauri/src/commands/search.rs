@@ -0,0 +1,45 @@
+//! Full-text search, replacing the MiniSearch index kept in IndexedDB.
+//!
+//! The frontend feeds messages and decrypted DMs through `search_index` as
+//! they arrive and removes deleted ones with `search_remove`. Queries are
+//! parsed by `search-store.ts` and passed in as terms plus filters.
+
+use tauri::{AppHandle, Manager};
+
+use super::run_blocking;
+use crate::db::Database;
+use crate::search::{SearchDocument, SearchFilters, SearchPage, SearchStats};
+
+#[tauri::command]
+pub async fn search_index(documents: Vec<SearchDocument>, app: AppHandle) -> Result<usize, String> {
+    run_blocking(move || app.state::<Database>().search_index(&documents)).await
+}
+
+#[tauri::command]
+pub async fn search_remove(ids: Vec<String>, app: AppHandle) -> Result<usize, String> {
+    run_blocking(move || app.state::<Database>().search_remove(&ids)).await
+}
+
+#[tauri::command]
+pub async fn search_query(
+    terms: Vec<String>,
+    filters: Option<SearchFilters>,
+    limit: Option<u32>,
+    app: AppHandle,
+) -> Result<SearchPage, String> {
+    run_blocking(move || {
+        app.state::<Database>()
+            .search(&terms, &filters.unwrap_or_default(), limit)
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn search_stats(app: AppHandle) -> Result<SearchStats, String> {
+    run_blocking(move || app.state::<Database>().search_stats()).await
+}
+
+#[tauri::command]
+pub async fn search_clear(app: AppHandle) -> Result<(), String> {
+    run_blocking(move || app.state::<Database>().search_clear()).await
+}
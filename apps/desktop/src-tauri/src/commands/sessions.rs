@@ -0,0 +1,55 @@
+//! Forward-secret DM sessions for `dm-manager.ts`.
+//!
+//! Output of `dm_session_encrypt` starts with `NDR1` and goes in a DM's
+//! `encrypted` field; anything starting with `SEA{` is a legacy message and
+//! should still go through `SEA.decrypt`.
+
+use tauri::{AppHandle, Manager};
+
+use super::run_blocking;
+use crate::crypto::KeyPair;
+use crate::keystore::sessions::SessionStore;
+
+#[tauri::command]
+pub async fn dm_session_encrypt(
+    pair: KeyPair,
+    their_pub: String,
+    their_epub: String,
+    plaintext: String,
+    app: AppHandle,
+) -> Result<String, String> {
+    run_blocking(move || {
+        app.state::<SessionStore>()
+            .with_session(&pair, &their_pub, &their_epub, |session| {
+                session.encrypt(&pair, &plaintext)
+            })
+    })
+    .await
+}
+
+#[tauri::command]
+pub async fn dm_session_decrypt(
+    pair: KeyPair,
+    their_pub: String,
+    their_epub: String,
+    envelope: String,
+    app: AppHandle,
+) -> Result<String, String> {
+    run_blocking(move || {
+        app.state::<SessionStore>()
+            .with_session(&pair, &their_pub, &their_epub, |session| {
+                session.decrypt(&pair, &envelope)
+            })
+    })
+    .await
+}
+
+/// Forget the session with a peer; the next message starts a fresh one.
+#[tauri::command]
+pub async fn dm_session_reset(
+    my_pub: String,
+    their_pub: String,
+    app: AppHandle,
+) -> Result<bool, String> {
+    run_blocking(move || app.state::<SessionStore>().reset(&my_pub, &their_pub)).await
+}
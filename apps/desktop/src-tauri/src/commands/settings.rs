@@ -0,0 +1,35 @@
+//! App behavior settings. See [`crate::settings`].
+
+use tauri::{AppHandle, Emitter, Manager, State};
+
+use crate::hotkeys;
+use crate::quiet_hours;
+use crate::relay;
+use crate::settings::{AppSettings, Settings, SettingsPatch};
+
+#[tauri::command]
+pub fn settings_get(settings: State<'_, Settings>) -> AppSettings {
+    settings.get()
+}
+
+/// Apply a partial update and broadcast the result as `settings:changed`.
+/// Hotkeys are registered again if the bindings changed, quiet hours are
+/// rechecked if the schedule did, and the relay restarts if its settings
+/// did.
+#[tauri::command]
+pub fn settings_set(patch: SettingsPatch, app: AppHandle) -> Result<AppSettings, String> {
+    let state = app.state::<Settings>();
+    let previous = state.get();
+    let settings = state.update(&app, &patch).map_err(|e| e.to_string())?;
+    if settings.hotkeys != previous.hotkeys {
+        hotkeys::apply(&app, &settings.hotkeys);
+    }
+    if settings.quiet_hours != previous.quiet_hours {
+        quiet_hours::refresh(&app);
+    }
+    if settings.relay != previous.relay {
+        relay::apply(&app, &settings.relay);
+    }
+    let _ = app.emit("settings:changed", &settings);
+    Ok(settings)
+}
@@ -0,0 +1,29 @@
+//! Quitting from the frontend and the shutdown handshake. See
+//! [`crate::shutdown`].
+
+use tauri::{AppHandle, State};
+
+use crate::shutdown::{QuitReason, Shutdown};
+
+/// Quit without asking, e.g. from a menu in the frontend.
+#[tauri::command]
+pub fn app_quit(app: AppHandle) {
+    crate::shutdown::quit(&app, QuitReason::User);
+}
+
+/// Make quitting wait for `subscriber` to call `shutdown_ack`.
+#[tauri::command]
+pub fn shutdown_register(subscriber: String, shutdown: State<'_, Shutdown>) {
+    shutdown.register(subscriber);
+}
+
+#[tauri::command]
+pub fn shutdown_unregister(subscriber: String, shutdown: State<'_, Shutdown>) {
+    shutdown.unregister(&subscriber);
+}
+
+/// `subscriber` finished handling `app:before-quit`.
+#[tauri::command]
+pub fn shutdown_ack(subscriber: String, shutdown: State<'_, Shutdown>) {
+    shutdown.acknowledge(&subscriber);
+}
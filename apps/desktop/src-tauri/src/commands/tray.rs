@@ -0,0 +1,12 @@
+//! Tray state pushed from the frontend. See [`crate::tray`].
+
+use tauri::{AppHandle, State};
+
+use crate::tray::{Tray, TrayState};
+
+/// Replace the state the tray shows. Runs on the main thread, where menu
+/// changes are applied.
+#[tauri::command]
+pub fn tray_update(state: TrayState, app: AppHandle, tray: State<'_, Tray>) -> Result<(), String> {
+    tray.update(&app, state).map_err(|e| e.to_string())
+}
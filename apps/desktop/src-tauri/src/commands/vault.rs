@@ -0,0 +1,61 @@
+//! "Remember me" commands backed by the OS credential store.
+
+use tauri::{AppHandle, Manager};
+
+use super::keystore::unlock_database;
+use super::run_blocking;
+use crate::crypto::KeyPair;
+use crate::keystore::wrap::WrapError;
+use crate::keystore::{Keystore, KeystoreError};
+use crate::vault::{Vault, VaultBackend};
+
+/// Opt in: verify the passphrase and cache the keystore's wrapping key.
+#[tauri::command]
+pub async fn vault_enable(
+    pub_key: String,
+    passphrase: String,
+    app: AppHandle,
+) -> Result<VaultBackend, String> {
+    run_blocking(move || {
+        let (_, key) = app
+            .state::<Keystore>()
+            .unlock_with_wrapping_key(&pub_key, &passphrase)
+            .map_err(|e| e.to_string())?;
+        app.state::<Vault>()
+            .store(&pub_key, key.as_slice())
+            .map_err(|e| e.to_string())
+    })
+    .await
+}
+
+/// Opt out: remove the cached key from every backend.
+#[tauri::command]
+pub async fn vault_disable(pub_key: String, app: AppHandle) -> Result<(), String> {
+    run_blocking(move || app.state::<Vault>().forget(&pub_key)).await
+}
+
+/// Unlock without a passphrase. Resolves to `null` when the identity isn't
+/// enrolled or the cached key no longer matches (e.g. after a passphrase
+/// change on another device), in which case the frontend should prompt.
+#[tauri::command]
+pub async fn vault_unlock(pub_key: String, app: AppHandle) -> Result<Option<KeyPair>, String> {
+    run_blocking(move || {
+        let vault = app.state::<Vault>();
+        let Some(key) = vault.fetch(&pub_key).map_err(|e| e.to_string())? else {
+            return Ok(None);
+        };
+        match app.state::<Keystore>().unlock_with_key(&pub_key, &key) {
+            Ok(keypair) => {
+                unlock_database(&app, &keypair);
+                Ok(Some(keypair))
+            }
+            Err(KeystoreError::Wrap(WrapError::WrongPassphrase))
+            | Err(KeystoreError::Wrap(WrapError::UnsupportedVersion(_))) => {
+                let _ = vault.forget(&pub_key);
+                Ok(None)
+            }
+            Err(e) => Err(e.to_string()),
+        }
+    })
+    .await
+}
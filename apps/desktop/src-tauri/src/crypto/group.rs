@@ -0,0 +1,685 @@
+//! Sender-key encryption for private channels.
+//!
+//! Every member holds their own sender key per channel: a symmetric chain
+//! (as in [`super::ratchet`]) plus a P-256 signing key. It is handed to the
+//! other members in a [`Distribution`], wrapped separately for each member's
+//! `epub`. A message is then encrypted once for the whole channel.
+//!
+//! Keys belong to an epoch. Kicking or banning someone starts a new epoch:
+//! the moderator rotates and distributes to the remaining members only, and
+//! everyone else must distribute a fresh key for the new epoch before
+//! sending again. The removed member keeps what they could already read, but
+//! never receives a key for anything written afterwards.
+//!
+//! Any member can sign a distribution, so only the moderation flow moves a
+//! member to a new epoch, one rotation at a time. Keys for the next epoch
+//! are kept as they arrive, and keys for epochs further ahead are refused.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::engine::general_purpose::{STANDARD as BASE64, URL_SAFE_NO_PAD as BASE64_URL};
+use base64::Engine;
+use hkdf::Hkdf;
+use p256::ecdsa::signature::{Signer, Verifier};
+use p256::ecdsa::{Signature, SigningKey, VerifyingKey};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use zeroize::Zeroizing;
+
+use super::ratchet::{message_cipher, step_chain, Chain, Key};
+use super::{sea, KeyPair};
+
+/// Prefix of an encrypted channel message.
+pub const ENVELOPE_PREFIX: &str = "NDG1";
+
+/// Most message keys skipped in one sender chain before a message is rejected.
+pub const MAX_SKIP: u32 = 2000;
+/// Most skipped keys retained per sender key; the oldest are dropped first.
+pub const MAX_STORED_SKIPPED: usize = 4000;
+/// Sender keys from this many epochs back are kept for reading history.
+pub const KEPT_EPOCHS: u64 = 32;
+
+const NONCE_LENGTH: usize = 12;
+
+#[derive(Debug, thiserror::Error)]
+pub enum GroupError {
+    #[error("No sender key for the current epoch. Distribute one first")]
+    NoSenderKey,
+    #[error("Sender key was not wrapped for this identity")]
+    NotARecipient,
+    #[error("Unknown sender key. It was never received or has expired")]
+    UnknownSenderKey,
+    #[error("Malformed group message: {0}")]
+    Malformed(String),
+    #[error("Failed to decrypt group message")]
+    Decrypt,
+    #[error("Group message signature is invalid")]
+    BadSignature,
+    #[error("Message is too far ahead of this sender key")]
+    TooManySkipped,
+    #[error("Message was already decrypted or its key has expired")]
+    Replayed,
+    #[error("Sender key is for an epoch this channel hasn't reached")]
+    EpochAhead,
+    #[error("Channel has run out of epochs")]
+    EpochOverflow,
+    #[error(transparent)]
+    Key(#[from] sea::SeaError),
+}
+
+/// A channel member to wrap sender keys for.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Member {
+    #[serde(rename = "pub")]
+    pub pub_key: String,
+    pub epub: String,
+}
+
+/// One member's sender key, wrapped for each recipient `pub`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Distribution {
+    pub channel_id: String,
+    pub epoch: u64,
+    pub sender_pub: String,
+    pub key_id: String,
+    pub wrapped: BTreeMap<String, String>,
+}
+
+/// What a recipient unwraps: enough to follow the sender's chain.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SenderKeyMaterial {
+    chain: Chain,
+    signing_pub: String,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct OwnKey {
+    key_id: String,
+    epoch: u64,
+    chain: Chain,
+    signing: Key,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PeerKey {
+    sender_pub: String,
+    key_id: String,
+    epoch: u64,
+    chain: Chain,
+    signing_pub: String,
+    skipped: VecDeque<(u32, Key)>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    e: u64,
+    s: String,
+    k: String,
+    n: u32,
+    ct: String,
+    sig: String,
+}
+
+/// One identity's sender-key state for one channel.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupState {
+    channel_id: String,
+    epoch: u64,
+    own: Option<OwnKey>,
+    peers: Vec<PeerKey>,
+}
+
+impl GroupState {
+    pub fn new(channel_id: &str) -> Self {
+        Self {
+            channel_id: channel_id.to_string(),
+            epoch: 0,
+            own: None,
+            peers: Vec::new(),
+        }
+    }
+
+    /// Start a new epoch with a fresh sender key for the given members.
+    /// Call after a kick or ban, with the removed member left out.
+    pub fn rotate(&mut self, me: &KeyPair, members: &[Member]) -> Result<Distribution, GroupError> {
+        self.epoch = self.epoch.checked_add(1).ok_or(GroupError::EpochOverflow)?;
+        self.own = None;
+        self.prune();
+        self.distribute(me, members)
+    }
+
+    /// Wrap our current sender key for `members`, creating one first if we
+    /// have none for this epoch. New members get the chain from its current
+    /// position, so they can't read what was sent before they joined.
+    pub fn distribute(
+        &mut self,
+        me: &KeyPair,
+        members: &[Member],
+    ) -> Result<Distribution, GroupError> {
+        let own = match &self.own {
+            Some(own) if own.epoch == self.epoch => own.clone(),
+            _ => self.create_own_key(me),
+        };
+        let signing = SigningKey::from_slice(&own.signing.0)
+            .map_err(|_| GroupError::Malformed("corrupt signing key".into()))?;
+        let material = SenderKeyMaterial {
+            chain: own.chain.clone(),
+            signing_pub: sea::public_key_string(&signing.verifying_key().into()),
+        };
+        let plain = Zeroizing::new(
+            serde_json::to_vec(&material).map_err(|e| GroupError::Malformed(e.to_string()))?,
+        );
+
+        let mut wrapped = BTreeMap::new();
+        for member in members.iter().filter(|m| m.pub_key != me.pub_key) {
+            let key = wrapping_key(&self.channel_id, &member.epub, &me.epriv)?;
+            let aad = wrap_aad(&self.channel_id, own.epoch, &me.pub_key, &member.pub_key);
+            let mut nonce = [0u8; NONCE_LENGTH];
+            rand::thread_rng().fill_bytes(&mut nonce);
+            let ct = Aes256Gcm::new(key.as_slice().into())
+                .encrypt(
+                    Nonce::from_slice(&nonce),
+                    Payload {
+                        msg: &plain,
+                        aad: &aad,
+                    },
+                )
+                .map_err(|_| GroupError::Malformed("wrapping failed".into()))?;
+            let mut bytes = nonce.to_vec();
+            bytes.extend_from_slice(&ct);
+            wrapped.insert(member.pub_key.clone(), BASE64.encode(bytes));
+        }
+
+        Ok(Distribution {
+            channel_id: self.channel_id.clone(),
+            epoch: own.epoch,
+            sender_pub: me.pub_key.clone(),
+            key_id: own.key_id,
+            wrapped,
+        })
+    }
+
+    /// Take in another member's sender key. `sender_epub` must come from the
+    /// sender's verified profile, not from the distribution itself.
+    ///
+    /// `rotated` is set when the moderation flow confirmed that the
+    /// distribution comes from a kick or ban; only then does our epoch move
+    /// to the distribution's, which must be the next one.
+    pub fn accept(
+        &mut self,
+        me: &KeyPair,
+        sender_epub: &str,
+        distribution: &Distribution,
+        rotated: bool,
+    ) -> Result<(), GroupError> {
+        if distribution.channel_id != self.channel_id {
+            return Err(GroupError::Malformed(
+                "distribution is for another channel".into(),
+            ));
+        }
+        if distribution.epoch > self.epoch.saturating_add(1) {
+            return Err(GroupError::EpochAhead);
+        }
+        let wrapped = distribution
+            .wrapped
+            .get(&me.pub_key)
+            .ok_or(GroupError::NotARecipient)?;
+        let bytes = BASE64
+            .decode(wrapped)
+            .map_err(|e| GroupError::Malformed(e.to_string()))?;
+        if bytes.len() < NONCE_LENGTH {
+            return Err(GroupError::Malformed("wrapped key is truncated".into()));
+        }
+        let (nonce, ct) = bytes.split_at(NONCE_LENGTH);
+        let key = wrapping_key(&self.channel_id, sender_epub, &me.epriv)?;
+        let aad = wrap_aad(
+            &self.channel_id,
+            distribution.epoch,
+            &distribution.sender_pub,
+            &me.pub_key,
+        );
+        let plain = Zeroizing::new(
+            Aes256Gcm::new(key.as_slice().into())
+                .decrypt(Nonce::from_slice(nonce), Payload { msg: ct, aad: &aad })
+                .map_err(|_| GroupError::Decrypt)?,
+        );
+        let material: SenderKeyMaterial =
+            serde_json::from_slice(&plain).map_err(|e| GroupError::Malformed(e.to_string()))?;
+
+        // Redistributions of a key we already follow would only rewind it.
+        if self
+            .peer_index(&distribution.sender_pub, &distribution.key_id)
+            .is_none()
+        {
+            self.peers.push(PeerKey {
+                sender_pub: distribution.sender_pub.clone(),
+                key_id: distribution.key_id.clone(),
+                epoch: distribution.epoch,
+                chain: material.chain,
+                signing_pub: material.signing_pub,
+                skipped: VecDeque::new(),
+            });
+        }
+        if rotated && distribution.epoch > self.epoch {
+            self.epoch = distribution.epoch;
+            self.prune();
+        }
+        Ok(())
+    }
+
+    /// Encrypt a channel message with our sender key for the current epoch.
+    pub fn encrypt(&mut self, me: &KeyPair, plaintext: &str) -> Result<String, GroupError> {
+        let epoch = self.epoch;
+        let own = self
+            .own
+            .as_mut()
+            .filter(|own| own.epoch == epoch)
+            .ok_or(GroupError::NoSenderKey)?;
+        let n = own.chain.n;
+        let message_key = step_chain(&mut own.chain);
+        let signing = SigningKey::from_slice(&own.signing.0)
+            .map_err(|_| GroupError::Malformed("corrupt signing key".into()))?;
+        let (sender, key_id) = (me.pub_key.clone(), own.key_id.clone());
+
+        let aad = message_aad(&self.channel_id, epoch, &sender, &key_id, n);
+        let (key, nonce) = message_cipher(&message_key);
+        let ct = Aes256Gcm::new(key.as_slice().into())
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: plaintext.as_bytes(),
+                    aad: &aad,
+                },
+            )
+            .map_err(|_| GroupError::Malformed("encryption failed".into()))?;
+        let ct = BASE64.encode(ct);
+        let signature: Signature = signing.sign(&signed_bytes(&aad, &ct));
+
+        let envelope = Envelope {
+            e: epoch,
+            s: sender,
+            k: key_id,
+            n,
+            ct,
+            sig: BASE64_URL.encode(signature.to_bytes()),
+        };
+        let json =
+            serde_json::to_string(&envelope).map_err(|e| GroupError::Malformed(e.to_string()))?;
+        Ok(format!("{ENVELOPE_PREFIX}{json}"))
+    }
+
+    /// Decrypt a channel message. State only changes if it authenticates.
+    pub fn decrypt(&mut self, envelope: &str) -> Result<String, GroupError> {
+        let json = envelope
+            .strip_prefix(ENVELOPE_PREFIX)
+            .ok_or_else(|| GroupError::Malformed("not a group message".into()))?;
+        let envelope: Envelope =
+            serde_json::from_str(json).map_err(|e| GroupError::Malformed(e.to_string()))?;
+        let index = self
+            .peer_index(&envelope.s, &envelope.k)
+            .ok_or(GroupError::UnknownSenderKey)?;
+
+        let mut peer = self.peers[index].clone();
+        if peer.epoch != envelope.e {
+            return Err(GroupError::Malformed(
+                "epoch does not match sender key".into(),
+            ));
+        }
+        let aad = message_aad(
+            &self.channel_id,
+            envelope.e,
+            &envelope.s,
+            &envelope.k,
+            envelope.n,
+        );
+
+        let verifying = VerifyingKey::from(&sea::public_key(&peer.signing_pub)?);
+        let signature = BASE64_URL
+            .decode(&envelope.sig)
+            .ok()
+            .and_then(|bytes| Signature::from_slice(&bytes).ok())
+            .ok_or(GroupError::BadSignature)?;
+        verifying
+            .verify(&signed_bytes(&aad, &envelope.ct), &signature)
+            .map_err(|_| GroupError::BadSignature)?;
+
+        let message_key = peer.message_key(envelope.n)?;
+        let ct = BASE64
+            .decode(&envelope.ct)
+            .map_err(|e| GroupError::Malformed(e.to_string()))?;
+        let (key, nonce) = message_cipher(&message_key);
+        let plain = Aes256Gcm::new(key.as_slice().into())
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &ct,
+                    aad: &aad,
+                },
+            )
+            .map_err(|_| GroupError::Decrypt)?;
+        let text = String::from_utf8(plain).map_err(|_| GroupError::Decrypt)?;
+
+        self.peers[index] = peer;
+        Ok(text)
+    }
+
+    fn create_own_key(&mut self, me: &KeyPair) -> OwnKey {
+        let mut chain_key = [0u8; 32];
+        let mut key_id = [0u8; 8];
+        rand::thread_rng().fill_bytes(&mut chain_key);
+        rand::thread_rng().fill_bytes(&mut key_id);
+        let signing = SigningKey::random(&mut rand::thread_rng());
+
+        let own = OwnKey {
+            key_id: key_id.iter().map(|b| format!("{b:02x}")).collect(),
+            epoch: self.epoch,
+            chain: Chain {
+                key: Key(chain_key),
+                n: 0,
+            },
+            signing: Key(signing.to_bytes().into()),
+        };
+        // Our own messages come back through the channel too.
+        self.peers.push(PeerKey {
+            sender_pub: me.pub_key.clone(),
+            key_id: own.key_id.clone(),
+            epoch: own.epoch,
+            chain: own.chain.clone(),
+            signing_pub: sea::public_key_string(&signing.verifying_key().into()),
+            skipped: VecDeque::new(),
+        });
+        self.own = Some(own.clone());
+        own
+    }
+
+    fn peer_index(&self, sender_pub: &str, key_id: &str) -> Option<usize> {
+        self.peers
+            .iter()
+            .position(|p| p.sender_pub == sender_pub && p.key_id == key_id)
+    }
+
+    fn prune(&mut self) {
+        let oldest = self.epoch.saturating_sub(KEPT_EPOCHS);
+        self.peers.retain(|p| p.epoch >= oldest);
+    }
+}
+
+impl PeerKey {
+    fn message_key(&mut self, n: u32) -> Result<Key, GroupError> {
+        if n < self.chain.n {
+            let index = self
+                .skipped
+                .iter()
+                .position(|(skipped, _)| *skipped == n)
+                .ok_or(GroupError::Replayed)?;
+            return self
+                .skipped
+                .remove(index)
+                .map(|(_, key)| key)
+                .ok_or(GroupError::Replayed);
+        }
+        if n - self.chain.n > MAX_SKIP {
+            return Err(GroupError::TooManySkipped);
+        }
+        while self.chain.n < n {
+            let skipped = self.chain.n;
+            let key = step_chain(&mut self.chain);
+            self.skipped.push_back((skipped, key));
+            if self.skipped.len() > MAX_STORED_SKIPPED {
+                self.skipped.pop_front();
+            }
+        }
+        Ok(step_chain(&mut self.chain))
+    }
+}
+
+fn wrapping_key(
+    channel_id: &str,
+    their_epub: &str,
+    my_epriv: &str,
+) -> Result<Zeroizing<[u8; 32]>, GroupError> {
+    let shared = Zeroizing::new(
+        BASE64_URL
+            .decode(sea::secret(their_epub, my_epriv)?)
+            .map_err(|e| GroupError::Malformed(e.to_string()))?,
+    );
+    let mut key = Zeroizing::new([0u8; 32]);
+    Hkdf::<Sha256>::new(Some(channel_id.as_bytes()), &shared)
+        .expand(b"nodes/group-key/wrap", key.as_mut())
+        .expect("32 bytes is a valid HKDF-SHA256 length");
+    Ok(key)
+}
+
+fn wrap_aad(channel_id: &str, epoch: u64, sender_pub: &str, member_pub: &str) -> Vec<u8> {
+    format!("{channel_id}|{epoch}|{sender_pub}>{member_pub}").into_bytes()
+}
+
+fn message_aad(channel_id: &str, epoch: u64, sender_pub: &str, key_id: &str, n: u32) -> Vec<u8> {
+    format!("{channel_id}|{epoch}|{sender_pub}|{key_id}|{n}").into_bytes()
+}
+
+fn signed_bytes(aad: &[u8], ct: &str) -> Vec<u8> {
+    let mut bytes = aad.to_vec();
+    bytes.push(b'|');
+    bytes.extend_from_slice(ct.as_bytes());
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::random_keypair;
+    use super::*;
+
+    const CHANNEL: &str = "channel-1";
+
+    struct Person {
+        keys: KeyPair,
+        state: GroupState,
+    }
+
+    impl Person {
+        fn new() -> Self {
+            Self {
+                keys: random_keypair(),
+                state: GroupState::new(CHANNEL),
+            }
+        }
+
+        fn member(&self) -> Member {
+            Member {
+                pub_key: self.keys.pub_key.clone(),
+                epub: self.keys.epub.clone(),
+            }
+        }
+
+        fn send(&mut self, text: &str) -> String {
+            self.state.encrypt(&self.keys, text).unwrap()
+        }
+
+        fn read(&mut self, envelope: &str) -> Result<String, GroupError> {
+            self.state.decrypt(envelope)
+        }
+    }
+
+    /// Have `sender`'s distribution accepted by everyone in `others`.
+    fn hand_out(sender: &Person, distribution: &Distribution, others: &mut [&mut Person]) {
+        for other in others {
+            other
+                .state
+                .accept(&other.keys, &sender.keys.epub, distribution, false)
+                .unwrap();
+        }
+    }
+
+    /// As the moderation flow does with a rotation after a kick or ban.
+    fn hand_out_rotation(sender: &Person, distribution: &Distribution, others: &mut [&mut Person]) {
+        for other in others {
+            other
+                .state
+                .accept(&other.keys, &sender.keys.epub, distribution, true)
+                .unwrap();
+        }
+    }
+
+    /// Alice, Bob and Carol, each holding everyone's sender key.
+    fn channel() -> (Person, Person, Person) {
+        let (mut alice, mut bob, mut carol) = (Person::new(), Person::new(), Person::new());
+        let members = [alice.member(), bob.member(), carol.member()];
+        let from_alice = alice.state.distribute(&alice.keys, &members).unwrap();
+        let from_bob = bob.state.distribute(&bob.keys, &members).unwrap();
+        let from_carol = carol.state.distribute(&carol.keys, &members).unwrap();
+        hand_out(&alice, &from_alice, &mut [&mut bob, &mut carol]);
+        hand_out(&bob, &from_bob, &mut [&mut alice, &mut carol]);
+        hand_out(&carol, &from_carol, &mut [&mut alice, &mut bob]);
+        (alice, bob, carol)
+    }
+
+    #[test]
+    fn members_read_each_other() {
+        let (mut alice, mut bob, mut carol) = channel();
+        let message = alice.send("hello all");
+        assert!(message.starts_with(ENVELOPE_PREFIX));
+        assert_eq!(bob.read(&message).unwrap(), "hello all");
+        assert_eq!(carol.read(&message).unwrap(), "hello all");
+        assert_eq!(alice.read(&message).unwrap(), "hello all");
+
+        let reply = carol.send("hi alice");
+        assert_eq!(alice.read(&reply).unwrap(), "hi alice");
+        assert_eq!(bob.read(&reply).unwrap(), "hi alice");
+    }
+
+    #[test]
+    fn removed_member_cannot_read_after_rotation() {
+        let (mut alice, mut bob, mut carol) = channel();
+        let before = alice.send("before the kick");
+
+        // Alice removes Carol and rotates for the remaining members.
+        let remaining = [alice.member(), bob.member()];
+        let rotated = alice.state.rotate(&alice.keys, &remaining).unwrap();
+        assert_eq!(rotated.epoch, 1);
+        assert!(!rotated.wrapped.contains_key(&carol.keys.pub_key));
+        hand_out_rotation(&alice, &rotated, &mut [&mut bob]);
+        assert!(matches!(
+            carol
+                .state
+                .accept(&carol.keys, &alice.keys.epub, &rotated, true),
+            Err(GroupError::NotARecipient)
+        ));
+
+        // Bob must distribute a fresh key for the new epoch before sending.
+        assert!(matches!(
+            bob.state.encrypt(&bob.keys, "too early"),
+            Err(GroupError::NoSenderKey)
+        ));
+        let from_bob = bob.state.distribute(&bob.keys, &remaining).unwrap();
+        assert_eq!(from_bob.epoch, 1);
+        hand_out(&bob, &from_bob, &mut [&mut alice]);
+
+        let from_alice = alice.send("after the kick");
+        let from_bob = bob.send("agreed");
+        assert_eq!(bob.read(&from_alice).unwrap(), "after the kick");
+        assert_eq!(alice.read(&from_bob).unwrap(), "agreed");
+        assert!(matches!(
+            carol.read(&from_alice),
+            Err(GroupError::UnknownSenderKey)
+        ));
+        assert!(matches!(
+            carol.read(&from_bob),
+            Err(GroupError::UnknownSenderKey)
+        ));
+
+        // What Carol could read before stays readable.
+        assert_eq!(carol.read(&before).unwrap(), "before the kick");
+        assert_eq!(bob.read(&before).unwrap(), "before the kick");
+    }
+
+    #[test]
+    fn out_of_order_and_replays() {
+        let (mut alice, mut bob, _) = channel();
+        let sent: Vec<String> = (0..4).map(|i| alice.send(&format!("m{i}"))).collect();
+        for i in [3, 1, 0, 2] {
+            assert_eq!(bob.read(&sent[i]).unwrap(), format!("m{i}"));
+        }
+        assert!(matches!(bob.read(&sent[3]), Err(GroupError::Replayed)));
+        assert!(matches!(bob.read(&sent[1]), Err(GroupError::Replayed)));
+    }
+
+    #[test]
+    fn rejects_forgeries() {
+        let (mut alice, mut bob, _) = channel();
+        let message = alice.send("genuine");
+        let tampered = message.replacen(r#""ct":""#, r#""ct":"A"#, 1);
+        assert!(matches!(bob.read(&tampered), Err(GroupError::BadSignature)));
+        // Rejected messages don't advance the chain.
+        assert_eq!(bob.read(&message).unwrap(), "genuine");
+    }
+
+    #[test]
+    fn accept_checks_the_sender_epub() {
+        let (alice, mut bob) = (Person::new(), Person::new());
+        let mallory = random_keypair();
+        let distribution = GroupState::new(CHANNEL)
+            .distribute(&alice.keys, &[bob.member()])
+            .unwrap();
+        assert!(matches!(
+            bob.state
+                .accept(&bob.keys, &mallory.epub, &distribution, false),
+            Err(GroupError::Decrypt)
+        ));
+        let mut elsewhere = GroupState::new("channel-2");
+        assert!(matches!(
+            elsewhere.accept(&bob.keys, &alice.keys.epub, &distribution, false),
+            Err(GroupError::Malformed(_))
+        ));
+    }
+
+    #[test]
+    fn forged_epoch_jumps_are_rejected() {
+        let (mut alice, mut bob, mut carol) = channel();
+        let members = [alice.member(), bob.member(), carol.member()];
+
+        // Carol signs a distribution for a far-off epoch.
+        let mut forged = GroupState::new(CHANNEL);
+        forged.epoch = u64::MAX;
+        let jump = forged.distribute(&carol.keys, &members).unwrap();
+        assert!(matches!(
+            bob.state.accept(&bob.keys, &carol.keys.epub, &jump, true),
+            Err(GroupError::EpochAhead)
+        ));
+
+        // The next epoch without a rotation behind it: kept, but Bob stays.
+        let mut next = GroupState::new(CHANNEL);
+        next.epoch = 1;
+        let early = next.distribute(&carol.keys, &members).unwrap();
+        bob.state
+            .accept(&bob.keys, &carol.keys.epub, &early, false)
+            .unwrap();
+        assert_eq!(bob.state.epoch, 0);
+
+        // Nothing was cut off.
+        let message = alice.send("still here");
+        assert_eq!(bob.read(&message).unwrap(), "still here");
+        assert_eq!(carol.read(&message).unwrap(), "still here");
+        let from_bob = bob.send("me too");
+        assert_eq!(alice.read(&from_bob).unwrap(), "me too");
+    }
+
+    #[test]
+    fn rotate_refuses_to_wrap_the_epoch() {
+        let alice = Person::new();
+        let mut state = GroupState::new(CHANNEL);
+        state.epoch = u64::MAX;
+        assert!(matches!(
+            state.rotate(&alice.keys, &[alice.member()]),
+            Err(GroupError::EpochOverflow)
+        ));
+    }
+}
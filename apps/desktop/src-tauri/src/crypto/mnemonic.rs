@@ -0,0 +1,228 @@
+//! BIP39 mnemonic backup of a SEA keypair.
+//!
+//! Only the two private scalars are encoded — the signing key (`priv`) and
+//! the encryption key (`epriv`) — each as its own 24-word BIP39 phrase, giving
+//! 48 words with two independent checksums. Public halves are recomputed on
+//! import, so the restored keypair (and therefore the user's soul) is
+//! identical to the original.
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL;
+use base64::Engine;
+use bip39::Mnemonic;
+use p256::SecretKey;
+use zeroize::Zeroizing;
+
+use super::{sea, KeyPair};
+
+/// Words per private key (256 bits of entropy + 8 checksum bits).
+const WORDS_PER_KEY: usize = 24;
+const SCALAR_LENGTH: usize = 32;
+
+#[derive(Debug, thiserror::Error)]
+pub enum MnemonicError {
+    #[error("Recovery phrase must have {expected} words, found {found}")]
+    WordCount { expected: usize, found: usize },
+    #[error("Invalid recovery phrase ({part} key): {reason}")]
+    Invalid { part: &'static str, reason: String },
+    #[error("Keypair is malformed: {0}")]
+    MalformedKeyPair(String),
+}
+
+/// Encode a keypair as a 48-word recovery phrase.
+pub fn export(keypair: &KeyPair) -> Result<Zeroizing<String>, MnemonicError> {
+    // Refuse to hand out a phrase that wouldn't restore this exact identity.
+    let rebuilt = keypair_from_scalars(
+        &decode_scalar(&keypair.priv_key)?,
+        &decode_scalar(&keypair.epriv)?,
+    )?;
+    if rebuilt.pub_key != keypair.pub_key || rebuilt.epub != keypair.epub {
+        return Err(MnemonicError::MalformedKeyPair(
+            "public keys do not match private keys".into(),
+        ));
+    }
+
+    let sign = phrase_for(&keypair.priv_key)?;
+    let encrypt = phrase_for(&keypair.epriv)?;
+    Ok(Zeroizing::new(format!("{} {}", *sign, *encrypt)))
+}
+
+/// Rebuild a keypair from a recovery phrase, validating both checksums.
+pub fn import(phrase: &str) -> Result<KeyPair, MnemonicError> {
+    let words: Vec<&str> = phrase.split_whitespace().collect();
+    if words.len() != WORDS_PER_KEY * 2 {
+        return Err(MnemonicError::WordCount {
+            expected: WORDS_PER_KEY * 2,
+            found: words.len(),
+        });
+    }
+
+    let (sign_words, encrypt_words) = words.split_at(WORDS_PER_KEY);
+    let priv_scalar = entropy_of(sign_words, "signing")?;
+    let epriv_scalar = entropy_of(encrypt_words, "encryption")?;
+    keypair_from_scalars(&priv_scalar, &epriv_scalar)
+}
+
+fn phrase_for(scalar_b64: &str) -> Result<Zeroizing<String>, MnemonicError> {
+    let scalar = decode_scalar(scalar_b64)?;
+    let mnemonic = Mnemonic::from_entropy(scalar.as_slice())
+        .map_err(|e| MnemonicError::MalformedKeyPair(e.to_string()))?;
+    Ok(Zeroizing::new(mnemonic.to_string()))
+}
+
+fn entropy_of(words: &[&str], part: &'static str) -> Result<Zeroizing<Vec<u8>>, MnemonicError> {
+    let normalized = Zeroizing::new(words.join(" ").to_lowercase());
+    let mnemonic = Mnemonic::parse_normalized(&normalized).map_err(|e| MnemonicError::Invalid {
+        part,
+        reason: e.to_string(),
+    })?;
+    Ok(Zeroizing::new(mnemonic.to_entropy()))
+}
+
+fn decode_scalar(value: &str) -> Result<Zeroizing<Vec<u8>>, MnemonicError> {
+    let bytes = Zeroizing::new(
+        BASE64_URL
+            .decode(value)
+            .map_err(|e| MnemonicError::MalformedKeyPair(e.to_string()))?,
+    );
+    if bytes.len() != SCALAR_LENGTH {
+        return Err(MnemonicError::MalformedKeyPair(format!(
+            "private key is {} bytes, expected {SCALAR_LENGTH}",
+            bytes.len()
+        )));
+    }
+    Ok(bytes)
+}
+
+fn keypair_from_scalars(priv_scalar: &[u8], epriv_scalar: &[u8]) -> Result<KeyPair, MnemonicError> {
+    Ok(KeyPair {
+        pub_key: public_key_for(priv_scalar)?,
+        priv_key: BASE64_URL.encode(priv_scalar),
+        epub: public_key_for(epriv_scalar)?,
+        epriv: BASE64_URL.encode(epriv_scalar),
+    })
+}
+
+fn public_key_for(scalar: &[u8]) -> Result<String, MnemonicError> {
+    let secret = SecretKey::from_slice(scalar)
+        .map_err(|_| MnemonicError::MalformedKeyPair("private key out of range".into()))?;
+    Ok(sea::public_key_string(&secret.public_key()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A keypair made by `SEA.pair()`, and its recovery phrase.
+    const PUB: &str =
+        "fFgfvZbDli5JUNcbGAPanfkSrZqYosmF67MLWJZPDgI.pFZ20AuDFF8O6Eq1TS6BgdGshxamlvr8Gp-ID-R-0c8";
+    const PRIV: &str = "B_3Meg0eVdbrjK_4dQDraSHQpe2nTsBeGQqhvRtnjVc";
+    const EPUB: &str =
+        "KX0M568Mj5yyDyPUJEzJvCmj0ljPTYMpoJxuIVSSoHQ.4OdvT75Ey-pauqCX1E7oHSYDcdy1u-m19xuTOIEpvXc";
+    const EPRIV: &str = "kXJwy6GPs4b81PGDKjvzKCzdNe2ZpxbBdL7qTQMs51A";
+    const PHRASE: &str = "among unveil burden boss torch twin purchase nominee wear pool depart \
+        spoil brown fancy repair truth scare valid dress drop spider record crystal visa \
+        multiply near cream drink will sell viable owner lobster fade wolf expect soccer \
+        online reopen crunch bitter arm cook stand south nose outer air";
+
+    fn keypair() -> KeyPair {
+        KeyPair {
+            pub_key: PUB.into(),
+            priv_key: PRIV.into(),
+            epub: EPUB.into(),
+            epriv: EPRIV.into(),
+        }
+    }
+
+    fn with_word(index: usize, word: &str) -> String {
+        let mut words: Vec<&str> = PHRASE.split_whitespace().collect();
+        words[index] = word;
+        words.join(" ")
+    }
+
+    #[test]
+    fn exports_48_words() {
+        let phrase = export(&keypair()).unwrap();
+        assert_eq!(phrase.split_whitespace().count(), WORDS_PER_KEY * 2);
+        assert_eq!(
+            phrase.split_whitespace().collect::<Vec<_>>(),
+            PHRASE.split_whitespace().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn import_restores_the_same_keypair() {
+        let restored = import(PHRASE).unwrap();
+        // Same public key, so the same `~pub` soul.
+        assert_eq!(restored.pub_key, PUB);
+        assert_eq!(restored.priv_key, PRIV);
+        assert_eq!(restored.epub, EPUB);
+        assert_eq!(restored.epriv, EPRIV);
+
+        let shouted = format!("  {}\n", PHRASE.to_uppercase());
+        assert!(import(&shouted).unwrap() == keypair());
+    }
+
+    #[test]
+    fn export_rejects_mismatched_keypair() {
+        let mut keypair = keypair();
+        keypair.epub = PUB.into();
+        assert!(matches!(
+            export(&keypair),
+            Err(MnemonicError::MalformedKeyPair(_))
+        ));
+    }
+
+    #[test]
+    fn rejects_bad_checksums() {
+        assert!(matches!(
+            import(&with_word(47, "able")),
+            Err(MnemonicError::Invalid {
+                part: "encryption",
+                ..
+            })
+        ));
+        assert!(matches!(
+            import(&with_word(3, "boat")),
+            Err(MnemonicError::Invalid {
+                part: "signing",
+                ..
+            })
+        ));
+        assert!(matches!(
+            import(&with_word(10, "depar")),
+            Err(MnemonicError::Invalid { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_wrong_word_counts() {
+        let short: Vec<&str> = PHRASE.split_whitespace().take(47).collect();
+        assert!(matches!(
+            import(&short.join(" ")),
+            Err(MnemonicError::WordCount {
+                expected: 48,
+                found: 47
+            })
+        ));
+        let first: Vec<&str> = PHRASE.split_whitespace().take(24).collect();
+        assert!(matches!(
+            import(&first.join(" ")),
+            Err(MnemonicError::WordCount { found: 24, .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_out_of_range_scalars() {
+        let second: Vec<&str> = PHRASE.split_whitespace().skip(24).collect();
+        // Valid BIP39 phrases for all-zero and all-one entropy, neither of
+        // which is a P-256 private key.
+        let zero = format!("{} art", "abandon ".repeat(23));
+        let max = format!("{} vote", "zoo ".repeat(23));
+        for first in [zero, max] {
+            assert!(matches!(
+                import(&format!("{first} {}", second.join(" "))),
+                Err(MnemonicError::MalformedKeyPair(_))
+            ));
+        }
+    }
+}
@@ -0,0 +1,52 @@
+//! Native cryptography shared by the identity and messaging subsystems.
+
+pub mod file;
+pub mod group;
+pub mod mnemonic;
+pub mod ratchet;
+pub mod sea;
+pub mod shamir;
+
+use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, ZeroizeOnDrop};
+
+/// SEA keypair, field-for-field compatible with `KeyPair` in `@nodes/crypto`.
+///
+/// Private halves are wiped from memory when the value is dropped.
+#[derive(Clone, PartialEq, Eq, Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+pub struct KeyPair {
+    #[serde(rename = "pub")]
+    pub pub_key: String,
+    #[serde(rename = "priv")]
+    pub priv_key: String,
+    pub epub: String,
+    pub epriv: String,
+}
+
+/// Passphrase-encrypted identity backup, as produced by `KeyManager.exportBackup`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyBackup {
+    pub version: u32,
+    pub encrypted: String,
+    #[serde(rename = "pub")]
+    pub pub_key: String,
+    pub exported_at: u64,
+    pub label: String,
+}
+
+/// A fresh random identity, for tests.
+#[cfg(test)]
+pub(crate) fn random_keypair() -> KeyPair {
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL;
+    use base64::Engine;
+
+    let sign = p256::SecretKey::random(&mut rand::thread_rng());
+    let encrypt = p256::SecretKey::random(&mut rand::thread_rng());
+    KeyPair {
+        pub_key: sea::public_key_string(&sign.public_key()),
+        priv_key: BASE64_URL.encode(sign.to_bytes()),
+        epub: sea::public_key_string(&encrypt.public_key()),
+        epriv: BASE64_URL.encode(encrypt.to_bytes()),
+    }
+}
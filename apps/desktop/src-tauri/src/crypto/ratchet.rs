@@ -0,0 +1,614 @@
+//! Double-ratchet sessions for forward-secret DMs.
+//!
+//! This follows the Signal double ratchet over P-256: every message gets its
+//! own key from a symmetric chain, and each reply rotates the chains through
+//! a fresh ECDH step. A leaked `epriv` therefore no longer opens past DMs.
+//!
+//! There are no prekeys in Nodes, so sessions bootstrap from the static
+//! `SEA.secret` of the two identities. Roles are fixed by public key order:
+//! the lower `pub` acts as the initiator and ratchets immediately; the other
+//! side may send on a one-off "boot" chain until the initiator's first
+//! message arrives. Either user can therefore write first.
+//!
+//! Gun delivers out of order, so keys for skipped message numbers are kept
+//! (bounded by [`MAX_SKIP`] per gap and [`MAX_STORED_SKIPPED`] in total).
+//! Decryption works on a copy of the state that is only committed once the
+//! message authenticates.
+
+use std::collections::VecDeque;
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::engine::general_purpose::{STANDARD as BASE64, URL_SAFE_NO_PAD as BASE64_URL};
+use base64::Engine;
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use p256::SecretKey;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha2::Sha256;
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+use super::{sea, KeyPair};
+
+/// Prefix of a ratchet envelope, distinguishing it from legacy `SEA{…}`.
+pub const ENVELOPE_PREFIX: &str = "NDR1";
+
+/// Most message keys skipped in one chain before a message is rejected.
+pub const MAX_SKIP: u32 = 1000;
+/// Most skipped keys retained per session; the oldest are dropped first.
+pub const MAX_STORED_SKIPPED: usize = 2000;
+
+const BOOT_CHAIN: &str = "boot";
+
+#[derive(Debug, thiserror::Error)]
+pub enum RatchetError {
+    #[error("Malformed ratchet message: {0}")]
+    Malformed(String),
+    #[error("Failed to decrypt message. Wrong session?")]
+    Decrypt,
+    #[error("Message is too far ahead of this session")]
+    TooManySkipped,
+    #[error("Message was already decrypted or its key has expired")]
+    Replayed,
+    #[error(transparent)]
+    Key(#[from] sea::SeaError),
+}
+
+/// 32 bytes of key material, base64 on disk and wiped on drop.
+#[derive(Clone, PartialEq, Eq, Zeroize, ZeroizeOnDrop)]
+pub struct Key(pub(super) [u8; 32]);
+
+impl Serialize for Key {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&BASE64.encode(self.0))
+    }
+}
+
+impl<'de> Deserialize<'de> for Key {
+    fn deserialize<D: Deserializer<'de>>(d: D) -> Result<Self, D::Error> {
+        let encoded = Zeroizing::new(String::deserialize(d)?);
+        let bytes = Zeroizing::new(
+            BASE64
+                .decode(encoded.as_bytes())
+                .map_err(serde::de::Error::custom)?,
+        );
+        let array: [u8; 32] = bytes
+            .as_slice()
+            .try_into()
+            .map_err(|_| serde::de::Error::custom("key must be 32 bytes"))?;
+        Ok(Key(array))
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(super) struct Chain {
+    pub(super) key: Key,
+    pub(super) n: u32,
+}
+
+/// Our current ratchet key: the identity `epriv` until the first DH step.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", tag = "kind", content = "secret")]
+enum DhSelf {
+    Identity,
+    Ephemeral(Key),
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SkippedKey {
+    chain: String,
+    n: u32,
+    key: Key,
+}
+
+/// Per-conversation ratchet state, serialized as-is by the session store.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Session {
+    their_pub: String,
+    their_epub: String,
+    root_key: Key,
+    dh_self: DhSelf,
+    dh_remote: Option<String>,
+    send: Option<Chain>,
+    recv: Option<Chain>,
+    prev_send_count: u32,
+    boot_send: Option<Chain>,
+    boot_recv: Option<Chain>,
+    skipped: VecDeque<SkippedKey>,
+}
+
+/// Cleartext (but authenticated) message header.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct Header {
+    dh: String,
+    pn: u32,
+    n: u32,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    boot: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Envelope {
+    h: Header,
+    ct: String,
+}
+
+impl Session {
+    /// Start a session with a peer. Both sides derive compatible state
+    /// independently, so no handshake message is needed.
+    pub fn new(me: &KeyPair, their_pub: &str, their_epub: &str) -> Result<Self, RatchetError> {
+        let shared = Zeroizing::new(
+            BASE64_URL
+                .decode(sea::secret(their_epub, &me.epriv)?)
+                .map_err(|e| RatchetError::Malformed(e.to_string()))?,
+        );
+        let ad = associated_data(&me.pub_key, their_pub);
+        let root = Key(hkdf_32(
+            Some(b"nodes/dm-ratchet/v1"),
+            &shared,
+            ad.as_bytes(),
+        ));
+        let boot = Chain {
+            key: Key(hkdf_32(Some(&root.0), b"", b"nodes/dm-ratchet/boot")),
+            n: 0,
+        };
+
+        let mut session = Session {
+            their_pub: their_pub.to_string(),
+            their_epub: their_epub.to_string(),
+            root_key: root,
+            dh_self: DhSelf::Identity,
+            dh_remote: None,
+            send: None,
+            recv: None,
+            prev_send_count: 0,
+            boot_send: None,
+            boot_recv: None,
+            skipped: VecDeque::new(),
+        };
+
+        if me.pub_key.as_str() < their_pub {
+            // Initiator: ratchet against the peer's identity key right away.
+            let ephemeral = SecretKey::random(&mut rand::thread_rng());
+            let dh = dh(&ephemeral, their_epub)?;
+            let (root, chain) = kdf_root(&session.root_key, &dh);
+            session.root_key = root;
+            session.send = Some(Chain { key: chain, n: 0 });
+            session.dh_self = DhSelf::Ephemeral(Key(ephemeral.to_bytes().into()));
+            session.dh_remote = Some(their_epub.to_string());
+            session.boot_recv = Some(boot);
+        } else {
+            session.boot_send = Some(boot);
+        }
+        Ok(session)
+    }
+
+    /// Whether this session was set up for the given peer keys. A changed
+    /// `epub` means the peer reset their identity and the session is stale.
+    pub fn matches(&self, their_pub: &str, their_epub: &str) -> bool {
+        self.their_pub == their_pub && self.their_epub == their_epub
+    }
+
+    /// Encrypt one message, advancing the sending chain.
+    pub fn encrypt(&mut self, me: &KeyPair, plaintext: &str) -> Result<String, RatchetError> {
+        let (header, message_key) = if let Some(chain) = self.send.as_mut() {
+            let header = Header {
+                dh: public_of(&self.dh_self, me)?,
+                pn: self.prev_send_count,
+                n: chain.n,
+                boot: false,
+            };
+            (header, step_chain(chain))
+        } else if let Some(chain) = self.boot_send.as_mut() {
+            let header = Header {
+                dh: me.epub.clone(),
+                pn: 0,
+                n: chain.n,
+                boot: true,
+            };
+            (header, step_chain(chain))
+        } else {
+            return Err(RatchetError::Malformed(
+                "session has no sending chain".into(),
+            ));
+        };
+
+        let aad = self.aad(me, &header, true)?;
+        let (key, nonce) = message_cipher(&message_key);
+        let ct = Aes256Gcm::new(key.as_slice().into())
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: plaintext.as_bytes(),
+                    aad: &aad,
+                },
+            )
+            .map_err(|_| RatchetError::Malformed("encryption failed".into()))?;
+
+        let envelope = Envelope {
+            h: header,
+            ct: BASE64.encode(ct),
+        };
+        let json =
+            serde_json::to_string(&envelope).map_err(|e| RatchetError::Malformed(e.to_string()))?;
+        Ok(format!("{ENVELOPE_PREFIX}{json}"))
+    }
+
+    /// Decrypt one message. State only changes if the message authenticates.
+    pub fn decrypt(&mut self, me: &KeyPair, envelope: &str) -> Result<String, RatchetError> {
+        let json = envelope
+            .strip_prefix(ENVELOPE_PREFIX)
+            .ok_or_else(|| RatchetError::Malformed("not a ratchet message".into()))?;
+        let envelope: Envelope =
+            serde_json::from_str(json).map_err(|e| RatchetError::Malformed(e.to_string()))?;
+        let ct = BASE64
+            .decode(&envelope.ct)
+            .map_err(|e| RatchetError::Malformed(e.to_string()))?;
+        let header = envelope.h;
+
+        let mut next = self.clone();
+        let message_key = next.message_key_for(me, &header)?;
+        let aad = next.aad(me, &header, false)?;
+        let (key, nonce) = message_cipher(&message_key);
+        let plain = Aes256Gcm::new(key.as_slice().into())
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &ct,
+                    aad: &aad,
+                },
+            )
+            .map_err(|_| RatchetError::Decrypt)?;
+        let text = String::from_utf8(plain).map_err(|_| RatchetError::Decrypt)?;
+
+        *self = next;
+        Ok(text)
+    }
+
+    fn message_key_for(&mut self, me: &KeyPair, header: &Header) -> Result<Key, RatchetError> {
+        if header.boot {
+            if let Some(key) = self.take_skipped(BOOT_CHAIN, header.n) {
+                return Ok(key);
+            }
+            let mut chain = self.boot_recv.take().ok_or(RatchetError::Replayed)?;
+            let result = self.advance_to(&mut chain, BOOT_CHAIN, header.n);
+            self.boot_recv = Some(chain);
+            return result;
+        }
+
+        if let Some(key) = self.take_skipped(&header.dh, header.n) {
+            return Ok(key);
+        }
+
+        if self.dh_remote.as_deref() != Some(header.dh.as_str()) {
+            // Stash what's left of the previous receiving chain, then step.
+            if let (Some(mut chain), Some(remote)) = (self.recv.take(), self.dh_remote.clone()) {
+                self.skip_until(&mut chain, &remote, header.pn)?;
+            }
+            self.dh_ratchet(me, &header.dh)?;
+        }
+
+        let mut chain = self.recv.take().ok_or(RatchetError::Replayed)?;
+        let result = self.advance_to(&mut chain, &header.dh, header.n);
+        self.recv = Some(chain);
+        result
+    }
+
+    fn dh_ratchet(&mut self, me: &KeyPair, remote: &str) -> Result<(), RatchetError> {
+        let current = secret_of(&self.dh_self, me)?;
+        let received = dh(&current, remote)?;
+        let (root, recv) = kdf_root(&self.root_key, &received);
+
+        let ephemeral = SecretKey::random(&mut rand::thread_rng());
+        let sent = dh(&ephemeral, remote)?;
+        let (root, send) = kdf_root(&root, &sent);
+
+        self.prev_send_count = self.send.as_ref().map_or(0, |c| c.n);
+        self.root_key = root;
+        self.recv = Some(Chain { key: recv, n: 0 });
+        self.send = Some(Chain { key: send, n: 0 });
+        self.dh_self = DhSelf::Ephemeral(Key(ephemeral.to_bytes().into()));
+        self.dh_remote = Some(remote.to_string());
+        // Once the initiator has been heard from, the boot chain is retired.
+        self.boot_send = None;
+        Ok(())
+    }
+
+    /// Derive the key for message `n`, stashing any keys skipped on the way.
+    fn advance_to(&mut self, chain: &mut Chain, id: &str, n: u32) -> Result<Key, RatchetError> {
+        if n < chain.n {
+            return Err(RatchetError::Replayed);
+        }
+        self.skip_until(chain, id, n)?;
+        Ok(step_chain(chain))
+    }
+
+    fn skip_until(&mut self, chain: &mut Chain, id: &str, until: u32) -> Result<(), RatchetError> {
+        if until.saturating_sub(chain.n) > MAX_SKIP {
+            return Err(RatchetError::TooManySkipped);
+        }
+        while chain.n < until {
+            let n = chain.n;
+            let key = step_chain(chain);
+            self.skipped.push_back(SkippedKey {
+                chain: id.to_string(),
+                n,
+                key,
+            });
+            if self.skipped.len() > MAX_STORED_SKIPPED {
+                self.skipped.pop_front();
+            }
+        }
+        Ok(())
+    }
+
+    fn take_skipped(&mut self, id: &str, n: u32) -> Option<Key> {
+        let index = self
+            .skipped
+            .iter()
+            .position(|s| s.chain == id && s.n == n)?;
+        self.skipped.remove(index).map(|s| s.key.clone())
+    }
+
+    /// Associated data: both identities in sender → recipient order, then the
+    /// header.
+    fn aad(&self, me: &KeyPair, header: &Header, sending: bool) -> Result<Vec<u8>, RatchetError> {
+        let (from, to) = if sending {
+            (me.pub_key.as_str(), self.their_pub.as_str())
+        } else {
+            (self.their_pub.as_str(), me.pub_key.as_str())
+        };
+        let mut aad = format!("{from}>{to}|").into_bytes();
+        aad.extend(serde_json::to_vec(header).map_err(|e| RatchetError::Malformed(e.to_string()))?);
+        Ok(aad)
+    }
+}
+
+/// Order-independent conversation binding for the root key.
+fn associated_data(a: &str, b: &str) -> String {
+    let (lo, hi) = if a < b { (a, b) } else { (b, a) };
+    format!("{lo}|{hi}")
+}
+
+fn secret_of(dh_self: &DhSelf, me: &KeyPair) -> Result<SecretKey, RatchetError> {
+    match dh_self {
+        DhSelf::Identity => Ok(sea::private_key(&me.epriv)?),
+        DhSelf::Ephemeral(key) => SecretKey::from_slice(&key.0)
+            .map_err(|_| RatchetError::Malformed("corrupt ratchet key".into())),
+    }
+}
+
+fn public_of(dh_self: &DhSelf, me: &KeyPair) -> Result<String, RatchetError> {
+    match dh_self {
+        DhSelf::Identity => Ok(me.epub.clone()),
+        DhSelf::Ephemeral(_) => Ok(sea::public_key_string(
+            &secret_of(dh_self, me)?.public_key(),
+        )),
+    }
+}
+
+fn dh(secret: &SecretKey, their_public: &str) -> Result<Zeroizing<[u8; 32]>, RatchetError> {
+    let public = sea::public_key(their_public)?;
+    let shared = p256::ecdh::diffie_hellman(secret.to_nonzero_scalar(), public.as_affine());
+    let mut out = Zeroizing::new([0u8; 32]);
+    out.copy_from_slice(shared.raw_secret_bytes());
+    Ok(out)
+}
+
+/// Root KDF: HKDF keyed by the current root key over a DH output.
+fn kdf_root(root: &Key, dh_out: &[u8; 32]) -> (Key, Key) {
+    let mut okm = Zeroizing::new([0u8; 64]);
+    Hkdf::<Sha256>::new(Some(&root.0), dh_out)
+        .expand(b"nodes/dm-ratchet/root", okm.as_mut())
+        .expect("64 bytes is a valid HKDF-SHA256 length");
+    let mut next_root = [0u8; 32];
+    let mut chain = [0u8; 32];
+    next_root.copy_from_slice(&okm[..32]);
+    chain.copy_from_slice(&okm[32..]);
+    (Key(next_root), Key(chain))
+}
+
+/// Chain KDF: returns the message key and advances the chain.
+pub(super) fn step_chain(chain: &mut Chain) -> Key {
+    let message = hmac_32(&chain.key.0, &[0x01]);
+    chain.key = Key(hmac_32(&chain.key.0, &[0x02]));
+    chain.n += 1;
+    Key(message)
+}
+
+/// AES key and nonce for one message key. Each message key is used once,
+/// so a derived nonce is safe.
+pub(super) fn message_cipher(message_key: &Key) -> (Zeroizing<[u8; 32]>, [u8; 12]) {
+    let mut okm = Zeroizing::new([0u8; 44]);
+    Hkdf::<Sha256>::new(None, &message_key.0)
+        .expand(b"nodes/dm-ratchet/message", okm.as_mut())
+        .expect("44 bytes is a valid HKDF-SHA256 length");
+    let mut key = Zeroizing::new([0u8; 32]);
+    let mut nonce = [0u8; 12];
+    key.copy_from_slice(&okm[..32]);
+    nonce.copy_from_slice(&okm[32..]);
+    (key, nonce)
+}
+
+fn hkdf_32(salt: Option<&[u8]>, ikm: &[u8], info: &[u8]) -> [u8; 32] {
+    let mut out = [0u8; 32];
+    Hkdf::<Sha256>::new(salt, ikm)
+        .expand(info, &mut out)
+        .expect("32 bytes is a valid HKDF-SHA256 length");
+    out
+}
+
+fn hmac_32(key: &[u8], data: &[u8]) -> [u8; 32] {
+    let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data);
+    mac.finalize().into_bytes().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::random_keypair;
+    use super::*;
+
+    /// Two identities with sessions for each other; the initiator first.
+    struct Pair {
+        init: KeyPair,
+        init_session: Session,
+        resp: KeyPair,
+        resp_session: Session,
+    }
+
+    fn pair() -> Pair {
+        let (a, b) = (random_keypair(), random_keypair());
+        let (init, resp) = if a.pub_key < b.pub_key {
+            (a, b)
+        } else {
+            (b, a)
+        };
+        Pair {
+            init_session: Session::new(&init, &resp.pub_key, &resp.epub).unwrap(),
+            resp_session: Session::new(&resp, &init.pub_key, &init.epub).unwrap(),
+            init,
+            resp,
+        }
+    }
+
+    impl Pair {
+        fn init_sends(&mut self, text: &str) -> String {
+            self.init_session.encrypt(&self.init, text).unwrap()
+        }
+
+        fn resp_sends(&mut self, text: &str) -> String {
+            self.resp_session.encrypt(&self.resp, text).unwrap()
+        }
+
+        fn resp_reads(&mut self, envelope: &str) -> Result<String, RatchetError> {
+            self.resp_session.decrypt(&self.resp, envelope)
+        }
+
+        fn init_reads(&mut self, envelope: &str) -> Result<String, RatchetError> {
+            self.init_session.decrypt(&self.init, envelope)
+        }
+    }
+
+    #[test]
+    fn in_order_conversation() {
+        let mut p = pair();
+        for round in 0..3 {
+            for i in 0..3 {
+                let text = format!("init {round}.{i}");
+                let envelope = p.init_sends(&text);
+                assert!(envelope.starts_with(ENVELOPE_PREFIX));
+                assert_eq!(p.resp_reads(&envelope).unwrap(), text);
+            }
+            let text = format!("resp {round}");
+            let envelope = p.resp_sends(&text);
+            assert_eq!(p.init_reads(&envelope).unwrap(), text);
+        }
+    }
+
+    #[test]
+    fn responder_can_write_first() {
+        let mut p = pair();
+        let first = p.resp_sends("hello");
+        let second = p.resp_sends("anyone there?");
+        assert!(first.contains(r#""boot":true"#));
+        assert_eq!(p.init_reads(&second).unwrap(), "anyone there?");
+        assert_eq!(p.init_reads(&first).unwrap(), "hello");
+
+        let reply = p.init_sends("hi");
+        assert_eq!(p.resp_reads(&reply).unwrap(), "hi");
+        // Having heard from the initiator, the responder leaves the boot chain.
+        let next = p.resp_sends("great");
+        assert!(!next.contains(r#""boot""#));
+        assert_eq!(p.init_reads(&next).unwrap(), "great");
+    }
+
+    #[test]
+    fn out_of_order_delivery() {
+        let mut p = pair();
+        let sent: Vec<String> = (0..5).map(|i| p.init_sends(&format!("m{i}"))).collect();
+        for i in [4, 0, 2, 1, 3] {
+            assert_eq!(p.resp_reads(&sent[i]).unwrap(), format!("m{i}"));
+        }
+    }
+
+    #[test]
+    fn skipped_keys_survive_a_ratchet_step() {
+        let mut p = pair();
+        let m0 = p.init_sends("m0");
+        let m1 = p.init_sends("m1");
+        assert_eq!(p.resp_reads(&m0).unwrap(), "m0");
+
+        let reply = p.resp_sends("r0");
+        assert_eq!(p.init_reads(&reply).unwrap(), "r0");
+        // Sent on the next chain; `m1` from the old one is still in flight.
+        let m2 = p.init_sends("m2");
+        assert_eq!(p.resp_reads(&m2).unwrap(), "m2");
+        assert_eq!(p.resp_reads(&m1).unwrap(), "m1");
+    }
+
+    #[test]
+    fn rejects_gaps_over_max_skip() {
+        let mut p = pair();
+        let sent: Vec<String> = (0..=MAX_SKIP + 1)
+            .map(|i| p.init_sends(&i.to_string()))
+            .collect();
+        assert!(matches!(
+            p.resp_reads(&sent[MAX_SKIP as usize + 1]),
+            Err(RatchetError::TooManySkipped)
+        ));
+        // Nothing was committed, and a gap of exactly MAX_SKIP is fine.
+        assert_eq!(
+            p.resp_reads(&sent[MAX_SKIP as usize]).unwrap(),
+            MAX_SKIP.to_string()
+        );
+        assert_eq!(p.resp_reads(&sent[0]).unwrap(), "0");
+    }
+
+    #[test]
+    fn rejects_replays() {
+        let mut p = pair();
+        let m0 = p.init_sends("m0");
+        let m1 = p.init_sends("m1");
+        assert_eq!(p.resp_reads(&m1).unwrap(), "m1");
+        assert!(matches!(p.resp_reads(&m1), Err(RatchetError::Replayed)));
+        // A skipped key is used once too.
+        assert_eq!(p.resp_reads(&m0).unwrap(), "m0");
+        assert!(matches!(p.resp_reads(&m0), Err(RatchetError::Replayed)));
+    }
+
+    #[test]
+    fn rejects_tampering_without_losing_state() {
+        let mut p = pair();
+        let m0 = p.init_sends("m0");
+        let tampered = m0.replacen(r#""n":0"#, r#""n":1"#, 1);
+        assert!(matches!(
+            p.resp_reads(&tampered),
+            Err(RatchetError::Decrypt)
+        ));
+        assert_eq!(p.resp_reads(&m0).unwrap(), "m0");
+
+        let outsider = random_keypair();
+        let mut theirs = Session::new(&outsider, &p.init.pub_key, &p.init.epub).unwrap();
+        let m1 = p.init_sends("m1");
+        assert!(theirs.decrypt(&outsider, &m1).is_err());
+    }
+
+    #[test]
+    fn sessions_survive_serialization() {
+        let mut p = pair();
+        let m0 = p.init_sends("m0");
+        let m1 = p.init_sends("m1");
+        assert_eq!(p.resp_reads(&m1).unwrap(), "m1");
+
+        let stored = serde_json::to_string(&p.resp_session).unwrap();
+        p.resp_session = serde_json::from_str(&stored).unwrap();
+        assert!(p.resp_session.matches(&p.init.pub_key, &p.init.epub));
+        assert_eq!(p.resp_reads(&m0).unwrap(), "m0");
+        let reply = p.resp_sends("r0");
+        assert_eq!(p.init_reads(&reply).unwrap(), "r0");
+    }
+}
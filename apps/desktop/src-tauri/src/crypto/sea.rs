@@ -0,0 +1,472 @@
+//! Gun SEA wire-format primitives.
+//!
+//! These mirror `SEA.work`, `SEA.sign`, `SEA.verify`, `SEA.secret`,
+//! `SEA.encrypt` and `SEA.decrypt` bit-for-bit, so data can move freely
+//! between this module and the JavaScript implementation:
+//!
+//! - `work`: PBKDF2-SHA256, 100 000 iterations, 64 bytes, base64 encoded.
+//! - `sign`: ECDSA P-256 over `SHA-256(message)` (WebCrypto hashes again),
+//!   serialized as `SEA{"m":…,"s":…}` with a raw `r‖s` signature.
+//! - `secret`: ECDH P-256, the shared x-coordinate as base64url.
+//! - `encrypt`: AES-256-GCM with a 15-byte IV, keyed by
+//!   `SHA-256(key ‖ salt)`, serialized as `SEA{"ct":…,"iv":…,"s":…}`.
+//!
+//! SEA turns the random salt into a string one byte per character before
+//! hashing, so the salt is widened the same way here (Latin-1, then UTF-8).
+//! Anything SEA passes through `JSON.stringify` goes through
+//! [`js_stringify`] here so hashes and envelopes match byte-for-byte.
+
+use aes_gcm::aead::consts::U15;
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::aes::Aes256;
+use aes_gcm::{AesGcm, Nonce};
+use base64::engine::general_purpose::{STANDARD as BASE64, URL_SAFE_NO_PAD as BASE64_URL};
+use base64::Engine;
+use p256::ecdsa::signature::{Signer, Verifier};
+use p256::ecdsa::{Signature, SigningKey, VerifyingKey};
+use p256::elliptic_curve::sec1::{FromEncodedPoint, ToEncodedPoint};
+use p256::{EncodedPoint, FieldBytes, PublicKey, SecretKey};
+use rand::RngCore;
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+/// AES-GCM as configured by SEA (non-standard 15-byte IV).
+type SeaCipher = AesGcm<Aes256, U15>;
+
+const PBKDF2_ITERATIONS: u32 = 100_000;
+const PBKDF2_LENGTH: usize = 64;
+const SALT_LENGTH: usize = 9;
+const IV_LENGTH: usize = 15;
+const COORDINATE_LENGTH: usize = 32;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SeaError {
+    #[error("Malformed SEA payload: {0}")]
+    Malformed(String),
+    #[error("Invalid key: {0}")]
+    InvalidKey(String),
+    #[error("Could not decrypt")]
+    Decrypt,
+    #[error("Could not encrypt")]
+    Encrypt,
+}
+
+/// Encrypted envelope. Field order matches SEA's `JSON.stringify` output.
+#[derive(Debug, Deserialize)]
+struct Envelope {
+    ct: String,
+    iv: String,
+    s: String,
+}
+
+/// Equivalent of `SEA.work(data, salt)` with default options.
+pub fn work(data: &str, salt: &str) -> String {
+    let mut out = [0u8; PBKDF2_LENGTH];
+    pbkdf2::pbkdf2_hmac::<Sha256>(
+        data.as_bytes(),
+        salt.as_bytes(),
+        PBKDF2_ITERATIONS,
+        &mut out,
+    );
+    BASE64.encode(out)
+}
+
+/// Equivalent of `SEA.sign(data, pair)`.
+///
+/// `data` is what the frontend would have passed to SEA: strings holding JSON
+/// are parsed first, exactly as `S.parse` does.
+pub fn sign(data: &Value, priv_key: &str) -> Result<String, SeaError> {
+    let message = parse(data);
+    let hash = Sha256::digest(hash_input(&message).as_bytes());
+
+    let key = SigningKey::from(private_key(priv_key)?);
+    let signature: Signature = key.sign(&hash);
+
+    let mut envelope = serde_json::Map::new();
+    envelope.insert("m".into(), message);
+    envelope.insert(
+        "s".into(),
+        Value::String(BASE64.encode(signature.to_bytes())),
+    );
+    Ok(format!("SEA{}", js_stringify(&Value::Object(envelope))))
+}
+
+/// Equivalent of `SEA.verify(signed, pub)`: the signed message on success,
+/// `None` if the signature doesn't match.
+pub fn verify(signed: &str, pub_key: &str) -> Result<Option<Value>, SeaError> {
+    let Value::Object(envelope) = parse_str(signed) else {
+        return Err(SeaError::Malformed("not a signed envelope".into()));
+    };
+    let (Some(message), Some(Value::String(sig))) = (envelope.get("m"), envelope.get("s")) else {
+        return Err(SeaError::Malformed("missing m or s".into()));
+    };
+
+    let key = VerifyingKey::from(public_key(pub_key)?);
+    let Ok(signature) = BASE64
+        .decode(sig)
+        .map_err(|_| ())
+        .and_then(|bytes| Signature::from_slice(&bytes).map_err(|_| ()))
+    else {
+        return Ok(None);
+    };
+
+    let hash = Sha256::digest(hash_input(message).as_bytes());
+    Ok(key.verify(&hash, &signature).ok().map(|()| parse(message)))
+}
+
+/// Equivalent of `SEA.secret(theirEpub, myPair)`.
+pub fn secret(their_epub: &str, my_epriv: &str) -> Result<String, SeaError> {
+    let theirs = public_key(their_epub)?;
+    let mine = private_key(my_epriv)?;
+    let shared = p256::ecdh::diffie_hellman(mine.to_nonzero_scalar(), theirs.as_affine());
+    Ok(BASE64_URL.encode(shared.raw_secret_bytes()))
+}
+
+/// Equivalent of `SEA.encrypt(data, key)` where `key` is a string secret.
+pub fn encrypt(data: &Value, key: &str) -> Result<String, SeaError> {
+    let message = match data {
+        Value::String(s) => s.clone(),
+        other => js_stringify(other),
+    };
+
+    let mut salt = [0u8; SALT_LENGTH];
+    let mut iv = [0u8; IV_LENGTH];
+    rand::thread_rng().fill_bytes(&mut salt);
+    rand::thread_rng().fill_bytes(&mut iv);
+
+    let ct = cipher_for(key, &salt)
+        .encrypt(Nonce::from_slice(&iv), message.as_bytes())
+        .map_err(|_| SeaError::Encrypt)?;
+
+    let mut envelope = serde_json::Map::new();
+    envelope.insert("ct".into(), Value::String(BASE64.encode(ct)));
+    envelope.insert("iv".into(), Value::String(BASE64.encode(iv)));
+    envelope.insert("s".into(), Value::String(BASE64.encode(salt)));
+    Ok(format!("SEA{}", js_stringify(&Value::Object(envelope))))
+}
+
+/// Equivalent of `SEA.decrypt(data, key)`, returning the raw plaintext.
+///
+/// SEA additionally `JSON.parse`s the result; see [`decrypt_value`].
+pub fn decrypt(data: &str, key: &str) -> Result<String, SeaError> {
+    let json = data.strip_prefix("SEA").unwrap_or(data);
+    let envelope: Envelope =
+        serde_json::from_str(json).map_err(|e| SeaError::Malformed(e.to_string()))?;
+
+    let ct = decode(&envelope.ct)?;
+    let iv = decode(&envelope.iv)?;
+    let salt = decode(&envelope.s)?;
+    if iv.len() != IV_LENGTH {
+        return Err(SeaError::Malformed(format!("iv length {}", iv.len())));
+    }
+
+    let plain = cipher_for(key, &salt)
+        .decrypt(Nonce::from_slice(&iv), ct.as_slice())
+        .map_err(|_| SeaError::Decrypt)?;
+    String::from_utf8(plain).map_err(|_| SeaError::Decrypt)
+}
+
+/// `SEA.decrypt` including its final `S.parse` of the plaintext.
+pub fn decrypt_value(data: &str, key: &str) -> Result<Value, SeaError> {
+    decrypt(data, key).map(|plain| parse_str(&plain))
+}
+
+/// SEA public key string: base64url `x` and `y` coordinates joined by `.`.
+pub fn public_key_string(key: &PublicKey) -> String {
+    let point = key.to_encoded_point(false);
+    // Uncompressed points always carry both coordinates.
+    let x = point.x().map(|x| BASE64_URL.encode(x)).unwrap_or_default();
+    let y = point.y().map(|y| BASE64_URL.encode(y)).unwrap_or_default();
+    format!("{x}.{y}")
+}
+
+/// Parse a SEA `pub`/`epub` string.
+pub fn public_key(value: &str) -> Result<PublicKey, SeaError> {
+    let (x, y) = value
+        .split_once('.')
+        .ok_or_else(|| SeaError::InvalidKey("expected x.y".into()))?;
+    let x = coordinate(x)?;
+    let y = coordinate(y)?;
+    let point = EncodedPoint::from_affine_coordinates(&x, &y, false);
+    Option::from(PublicKey::from_encoded_point(&point))
+        .ok_or_else(|| SeaError::InvalidKey("point not on curve".into()))
+}
+
+/// Parse a SEA `priv`/`epriv` string.
+pub fn private_key(value: &str) -> Result<SecretKey, SeaError> {
+    SecretKey::from_bytes(&coordinate(value)?)
+        .map_err(|_| SeaError::InvalidKey("private key out of range".into()))
+}
+
+fn coordinate(value: &str) -> Result<FieldBytes, SeaError> {
+    let bytes = BASE64_URL
+        .decode(value)
+        .map_err(|e| SeaError::InvalidKey(e.to_string()))?;
+    if bytes.len() != COORDINATE_LENGTH {
+        return Err(SeaError::InvalidKey(format!(
+            "expected {COORDINATE_LENGTH} bytes, got {}",
+            bytes.len()
+        )));
+    }
+    Ok(*FieldBytes::from_slice(&bytes))
+}
+
+/// `S.parse`: JSON-decode strings (dropping a `SEA` prefix), leave anything
+/// that isn't valid JSON as the original string.
+fn parse(value: &Value) -> Value {
+    match value {
+        Value::String(s) => parse_str(s),
+        other => other.clone(),
+    }
+}
+
+fn parse_str(s: &str) -> Value {
+    let json = if s.starts_with("SEA{") { &s[3..] } else { s };
+    serde_json::from_str(json).unwrap_or_else(|_| Value::String(s.to_string()))
+}
+
+/// What SEA's `sha256` helper hashes: strings as-is, everything else
+/// stringified.
+fn hash_input(message: &Value) -> String {
+    match message {
+        Value::String(s) => s.clone(),
+        other => js_stringify(other),
+    }
+}
+
+/// AES key derivation from SEA's `aeskey.js`.
+fn cipher_for(key: &str, salt: &[u8]) -> SeaCipher {
+    let mut combo = String::with_capacity(key.len() + salt.len() * 2);
+    combo.push_str(key);
+    combo.extend(salt.iter().map(|&b| char::from(b)));
+    let digest = Sha256::digest(combo.as_bytes());
+    SeaCipher::new(&digest)
+}
+
+fn decode(value: &str) -> Result<Vec<u8>, SeaError> {
+    BASE64
+        .decode(value)
+        .map_err(|e| SeaError::Malformed(e.to_string()))
+}
+
+/// `JSON.stringify` as a JavaScript engine would produce it.
+///
+/// Differs from `serde_json::to_string` in two ways that matter for hashing:
+/// integer-like keys are emitted first in ascending order (JS property
+/// order), and non-integral numbers use JavaScript's `Number#toString` rules.
+pub fn js_stringify(value: &Value) -> String {
+    let mut out = String::new();
+    write_js(value, &mut out);
+    out
+}
+
+fn write_js(value: &Value, out: &mut String) {
+    match value {
+        Value::Null => out.push_str("null"),
+        Value::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        Value::Number(n) => out.push_str(&js_number(n)),
+        Value::String(s) => out.push_str(&Value::String(s.clone()).to_string()),
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_js(item, out);
+            }
+            out.push(']');
+        }
+        Value::Object(map) => {
+            let mut index_keys: Vec<(u32, &String)> = map
+                .keys()
+                .filter_map(|k| array_index(k).map(|i| (i, k)))
+                .collect();
+            index_keys.sort_unstable_by_key(|(i, _)| *i);
+            let ordered = index_keys
+                .into_iter()
+                .map(|(_, k)| k)
+                .chain(map.keys().filter(|k| array_index(k).is_none()));
+
+            out.push('{');
+            for (i, key) in ordered.enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::String(key.clone()).to_string());
+                out.push(':');
+                write_js(&map[key], out);
+            }
+            out.push('}');
+        }
+    }
+}
+
+/// Canonical array-index keys, which JS objects enumerate first.
+fn array_index(key: &str) -> Option<u32> {
+    let index: u32 = key.parse().ok()?;
+    (index != u32::MAX && index.to_string() == key).then_some(index)
+}
+
+/// `Number.prototype.toString()` for finite values.
+fn js_number(n: &serde_json::Number) -> String {
+    if n.is_i64() || n.is_u64() {
+        return n.to_string();
+    }
+    let value = n.as_f64().unwrap_or(0.0);
+    if value == 0.0 {
+        return "0".into();
+    }
+
+    // Shortest round-trip digits and decimal exponent, e.g. "1.25e-7".
+    let sci = format!("{:e}", value.abs());
+    let (mantissa, exponent) = sci.split_once('e').unwrap_or((&sci, "0"));
+    let digits: String = mantissa.chars().filter(|c| *c != '.').collect();
+    let k = digits.len() as i32;
+    let n = exponent.parse::<i32>().unwrap_or(0) + 1;
+
+    let body = if k <= n && n <= 21 {
+        format!("{digits}{}", "0".repeat((n - k) as usize))
+    } else if 0 < n && n <= 21 {
+        format!("{}.{}", &digits[..n as usize], &digits[n as usize..])
+    } else if -6 < n && n <= 0 {
+        format!("0.{}{digits}", "0".repeat((-n) as usize))
+    } else {
+        let sign = if n > 0 { '+' } else { '-' };
+        let exp = (n - 1).abs();
+        if k == 1 {
+            format!("{digits}e{sign}{exp}")
+        } else {
+            format!("{}.{}e{sign}{exp}", &digits[..1], &digits[1..])
+        }
+    };
+    if value < 0.0 {
+        format!("-{body}")
+    } else {
+        body
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    // Produced by gun/sea with `node scripts/sea-vectors.mjs`.
+    const ALICE_PUB: &str =
+        "fFgfvZbDli5JUNcbGAPanfkSrZqYosmF67MLWJZPDgI.pFZ20AuDFF8O6Eq1TS6BgdGshxamlvr8Gp-ID-R-0c8";
+    const ALICE_PRIV: &str = "B_3Meg0eVdbrjK_4dQDraSHQpe2nTsBeGQqhvRtnjVc";
+    const ALICE_EPUB: &str =
+        "KX0M568Mj5yyDyPUJEzJvCmj0ljPTYMpoJxuIVSSoHQ.4OdvT75Ey-pauqCX1E7oHSYDcdy1u-m19xuTOIEpvXc";
+    const ALICE_EPRIV: &str = "kXJwy6GPs4b81PGDKjvzKCzdNe2ZpxbBdL7qTQMs51A";
+    const BOB_PUB: &str =
+        "UxS3-yrVhUtyGA1UhvhVh3us-Zo2FDAio6qvZTFK5AU.ZjtWuM1Lw_7ws6E9PvZW7BX4Q99AeFGDs96dZ4TFA-s";
+    const BOB_EPUB: &str =
+        "jTw5pmVVPmw1zwkMFqVvOWHNaLrxOo3sel-EdMnf03k._ZeIAntenFlxwF4bLohRZ8WT7mX2MllqJ7EL_2FvZ04";
+    const BOB_EPRIV: &str = "_Hnr7w-kk0P0VMcIsDLwrWUCqEhKDch2rK5O-eSkCFs";
+
+    const WORK: &str =
+        "wY7PZkOT8cUZIAOtF29eYVPm4dDxFxKidlcSeVedm4qD4PVJ/OR63cfavnqrtp86RaTHmce7LtuSiiXebEIh5A==";
+    const SIGNED_OBJECT: &str = r#"SEA{"m":{"2":"two","10":"ten","text":"hello","n":1.5},"s":"WiVuDBrdGy+JWh6ifZ+xsmE+i4iPsxRbRgIW8jCYd8lG8mo6ML3Mpze1HOPKxHBSegb5lbP1YLZUSwy3Jzad5A=="}"#;
+    const SIGNED_STRING: &str = r#"SEA{"m":"plain text","s":"MC1V8dUzcipaXhxQCjl4dMTAE5lCpGf/wVv8wKcd0S8i/fT2S0UfP9a9UJ78SpCEtRah0uW3ehYV6Y8J8opHIA=="}"#;
+    const SIGNED_JSON: &str = r#"SEA{"m":{"id":"msg-1"},"s":"b2LItzMYRvzLIJkonCvmhmjaMZ82cSigaX93eVE/gem37Ltr2FVrWKgzi+3vK8vxJJWH+u0cR7B1SPW/dJXf9A=="}"#;
+    const SECRET: &str = "PUFOvqMjbeAaIPIUebw28_Z8DKAo1qMyoLyFQX2ju6o";
+    const ENCRYPTED_OBJECT: &str = r#"SEA{"ct":"WoTS3LMIwwsSSBrnWUhKMoZ6THcePbmY3zhFC/Ew9tfGoByQAnX0AAu8eWqB9pX5","iv":"ck9MtzwdVOFl1TCLkRaY","s":"a1KaEkvh9S1B"}"#;
+    const ENCRYPTED_STRING: &str = r#"SEA{"ct":"Lea4ZmQlezPN4pi9JI9m22MeDe5VmgtPVyw=","iv":"9B1CTP0hMAXAPecpQwMy","s":"ob1FHsUovk+v"}"#;
+
+    #[test]
+    fn work_matches_sea() {
+        assert_eq!(work("correct horse", ALICE_PUB), WORK);
+    }
+
+    #[test]
+    fn verifies_sea_signatures() {
+        assert_eq!(
+            verify(SIGNED_OBJECT, ALICE_PUB).unwrap(),
+            Some(json!({ "2": "two", "10": "ten", "text": "hello", "n": 1.5 }))
+        );
+        assert_eq!(
+            verify(SIGNED_STRING, ALICE_PUB).unwrap(),
+            Some(json!("plain text"))
+        );
+        assert_eq!(
+            verify(SIGNED_JSON, ALICE_PUB).unwrap(),
+            Some(json!({ "id": "msg-1" }))
+        );
+        // Without the prefix, as Gun stores it once parsed.
+        assert_eq!(
+            verify(&SIGNED_STRING[3..], ALICE_PUB).unwrap(),
+            Some(json!("plain text"))
+        );
+    }
+
+    #[test]
+    fn rejects_wrong_key_and_tampering() {
+        assert_eq!(verify(SIGNED_STRING, BOB_PUB).unwrap(), None);
+        let tampered = SIGNED_STRING.replace("plain text", "plain texT");
+        assert_eq!(verify(&tampered, ALICE_PUB).unwrap(), None);
+        assert!(verify("not signed", ALICE_PUB).is_err());
+    }
+
+    #[test]
+    fn signatures_verify() {
+        // JS key order: integer keys first, whatever order they're given in.
+        let message = json!({ "text": "hello", "10": "ten", "2": "two", "n": 1.5 });
+        let signed = sign(&message, ALICE_PRIV).unwrap();
+        assert!(signed.starts_with(r#"SEA{"m":{"2":"two","10":"ten","text":"hello","n":1.5},"s":"#));
+        assert_eq!(verify(&signed, ALICE_PUB).unwrap(), Some(message));
+        // A string holding JSON is signed as the parsed value, like SEA does.
+        let signed = sign(&json!(r#"{"id":"msg-1"}"#), ALICE_PRIV).unwrap();
+        assert_eq!(
+            verify(&signed, ALICE_PUB).unwrap(),
+            Some(json!({ "id": "msg-1" }))
+        );
+    }
+
+    #[test]
+    fn secret_matches_sea() {
+        assert_eq!(secret(BOB_EPUB, ALICE_EPRIV).unwrap(), SECRET);
+        assert_eq!(secret(ALICE_EPUB, BOB_EPRIV).unwrap(), SECRET);
+    }
+
+    #[test]
+    fn decrypts_sea_payloads() {
+        assert_eq!(
+            decrypt_value(ENCRYPTED_OBJECT, SECRET).unwrap(),
+            json!({ "text": "hi", "at": 1700000000000u64 })
+        );
+        assert_eq!(
+            decrypt(ENCRYPTED_STRING, "passphrase").unwrap(),
+            "plain text"
+        );
+        assert_eq!(
+            decrypt(&ENCRYPTED_STRING[3..], "passphrase").unwrap(),
+            "plain text"
+        );
+        assert!(matches!(
+            decrypt(ENCRYPTED_STRING, "wrong"),
+            Err(SeaError::Decrypt)
+        ));
+    }
+
+    #[test]
+    fn encrypted_payloads_decrypt() {
+        let message = json!({ "text": "hi", "at": 1700000000000u64 });
+        let encrypted = encrypt(&message, SECRET).unwrap();
+        assert!(encrypted.starts_with(r#"SEA{"ct":"#));
+        assert_eq!(decrypt_value(&encrypted, SECRET).unwrap(), message);
+        assert_eq!(decrypt_value(&encrypted[3..], SECRET).unwrap(), message);
+
+        let encrypted = encrypt(&json!("plain text"), "passphrase").unwrap();
+        assert_eq!(decrypt(&encrypted, "passphrase").unwrap(), "plain text");
+    }
+
+    #[test]
+    fn stringifies_like_javascript() {
+        let value = json!({ "b": 1, "1": [0.1, 1e21, 1.5e-7, -0.0], "a": null });
+        assert_eq!(
+            js_stringify(&value),
+            r#"{"1":[0.1,1e+21,1.5e-7,0],"b":1,"a":null}"#
+        );
+    }
+}
@@ -0,0 +1,400 @@
+//! Shamir secret sharing over GF(256) for social recovery.
+//!
+//! An identity backup is split into `total` shares, any `threshold` of which
+//! reconstruct it; fewer reveal nothing about the secret. Shares travel as
+//! a single line of text (easy to paste into a message or print) and can be
+//! wrapped in a small JSON file for people who prefer an attachment.
+//!
+//! Text form: `NODES-SHARE:1:<set>:<threshold>:<total>:<index>:<data>:<check>`
+//! where `data` is base64url and `check` is a truncated SHA-256 over the rest,
+//! so a mistyped share is rejected before it can corrupt recovery.
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64_URL;
+use base64::Engine;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+const PREFIX: &str = "NODES-SHARE";
+const FORMAT_VERSION: u32 = 1;
+const SET_ID_LENGTH: usize = 8;
+const CHECK_LENGTH: usize = 4;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ShamirError {
+    #[error("Threshold must be between 2 and {total}, got {threshold}")]
+    InvalidThreshold { threshold: u8, total: u8 },
+    #[error("Malformed share: {0}")]
+    Malformed(String),
+    #[error("Share checksum mismatch — check for typos")]
+    Checksum,
+    #[error("Shares come from different backups")]
+    MixedSets,
+    #[error("Share {0} was provided more than once")]
+    DuplicateIndex(u8),
+    #[error("Need {needed} shares, only {found} provided")]
+    NotEnoughShares { needed: u8, found: usize },
+}
+
+/// One share of a split secret.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Share {
+    pub set_id: String,
+    pub threshold: u8,
+    pub total: u8,
+    pub index: u8,
+    pub data: Vec<u8>,
+}
+
+/// JSON wrapper used when a share is saved as a file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShareFile {
+    pub version: u32,
+    #[serde(rename = "pub")]
+    pub pub_key: String,
+    pub label: String,
+    pub threshold: u8,
+    pub total: u8,
+    pub index: u8,
+    pub share: String,
+}
+
+/// Split `secret` into `total` shares requiring `threshold` to recombine.
+pub fn split(secret: &[u8], threshold: u8, total: u8) -> Result<Vec<Share>, ShamirError> {
+    if threshold < 2 || threshold > total {
+        return Err(ShamirError::InvalidThreshold { threshold, total });
+    }
+
+    let mut rng = rand::thread_rng();
+    let mut set = [0u8; SET_ID_LENGTH];
+    rng.fill_bytes(&mut set);
+    let set_id = hex(&set);
+
+    let mut shares: Vec<Share> = (1..=total)
+        .map(|index| Share {
+            set_id: set_id.clone(),
+            threshold,
+            total,
+            index,
+            data: Vec::with_capacity(secret.len()),
+        })
+        .collect();
+
+    // One random polynomial per byte, constant term = the secret byte.
+    let mut coefficients = Zeroizing::new(vec![0u8; threshold as usize]);
+    for &byte in secret {
+        coefficients[0] = byte;
+        rng.fill_bytes(&mut coefficients[1..]);
+        for share in &mut shares {
+            share.data.push(evaluate(&coefficients, share.index));
+        }
+    }
+    Ok(shares)
+}
+
+/// Recombine shares. Any `threshold` distinct shares of one set suffice.
+pub fn combine(shares: &[Share]) -> Result<Zeroizing<Vec<u8>>, ShamirError> {
+    let first = shares.first().ok_or(ShamirError::NotEnoughShares {
+        needed: 2,
+        found: 0,
+    })?;
+    if first.threshold < 2 {
+        return Err(ShamirError::InvalidThreshold {
+            threshold: first.threshold,
+            total: first.total,
+        });
+    }
+
+    let mut seen = [false; 256];
+    for share in shares {
+        if share.set_id != first.set_id
+            || share.threshold != first.threshold
+            || share.total != first.total
+            || share.data.len() != first.data.len()
+        {
+            return Err(ShamirError::MixedSets);
+        }
+        if std::mem::replace(&mut seen[share.index as usize], true) {
+            return Err(ShamirError::DuplicateIndex(share.index));
+        }
+    }
+    if shares.len() < first.threshold as usize {
+        return Err(ShamirError::NotEnoughShares {
+            needed: first.threshold,
+            found: shares.len(),
+        });
+    }
+
+    // Lagrange interpolation at x = 0 using exactly `threshold` shares.
+    let used = &shares[..first.threshold as usize];
+    let weights: Vec<u8> = used
+        .iter()
+        .map(|si| {
+            used.iter()
+                .filter(|sj| sj.index != si.index)
+                .fold(1u8, |acc, sj| {
+                    gf_mul(acc, gf_div(sj.index, sj.index ^ si.index))
+                })
+        })
+        .collect();
+
+    let mut secret = Zeroizing::new(vec![0u8; first.data.len()]);
+    for (i, byte) in secret.iter_mut().enumerate() {
+        *byte = used
+            .iter()
+            .zip(&weights)
+            .fold(0u8, |acc, (share, &w)| acc ^ gf_mul(share.data[i], w));
+    }
+    Ok(secret)
+}
+
+impl Share {
+    /// Encode as a single line of text.
+    pub fn to_text(&self) -> String {
+        let body = format!(
+            "{PREFIX}:{FORMAT_VERSION}:{}:{}:{}:{}:{}",
+            self.set_id,
+            self.threshold,
+            self.total,
+            self.index,
+            BASE64_URL.encode(&self.data)
+        );
+        let check = checksum(&body);
+        format!("{body}:{check}")
+    }
+
+    /// Parse the text form, verifying its checksum.
+    pub fn from_text(text: &str) -> Result<Self, ShamirError> {
+        let text = text.trim();
+        let (body, check) = text
+            .rsplit_once(':')
+            .ok_or_else(|| ShamirError::Malformed("missing checksum".into()))?;
+        if checksum(body) != check {
+            return Err(ShamirError::Checksum);
+        }
+
+        let parts: Vec<&str> = body.split(':').collect();
+        let [prefix, version, set_id, threshold, total, index, data] = parts[..] else {
+            return Err(ShamirError::Malformed("wrong number of fields".into()));
+        };
+        if prefix != PREFIX {
+            return Err(ShamirError::Malformed("not a Nodes recovery share".into()));
+        }
+        if version != FORMAT_VERSION.to_string() {
+            return Err(ShamirError::Malformed(format!(
+                "unsupported share version {version}"
+            )));
+        }
+
+        let number = |field: &str, name: &str| {
+            field
+                .parse::<u8>()
+                .map_err(|_| ShamirError::Malformed(format!("bad {name}")))
+        };
+        let share = Share {
+            set_id: set_id.to_string(),
+            threshold: number(threshold, "threshold")?,
+            total: number(total, "total")?,
+            index: number(index, "index")?,
+            data: BASE64_URL
+                .decode(data)
+                .map_err(|e| ShamirError::Malformed(e.to_string()))?,
+        };
+        if share.index == 0
+            || share.index > share.total
+            || share.threshold < 2
+            || share.threshold > share.total
+        {
+            return Err(ShamirError::Malformed(
+                "share numbering out of range".into(),
+            ));
+        }
+        Ok(share)
+    }
+}
+
+fn evaluate(coefficients: &[u8], x: u8) -> u8 {
+    // Horner's method, highest degree first.
+    coefficients
+        .iter()
+        .rev()
+        .fold(0u8, |acc, &c| gf_mul(acc, x) ^ c)
+}
+
+/// Multiplication in GF(2^8) with the AES polynomial, without
+/// data-dependent branches.
+fn gf_mul(mut a: u8, mut b: u8) -> u8 {
+    let mut product = 0u8;
+    for _ in 0..8 {
+        product ^= a & 0u8.wrapping_sub(b & 1);
+        let carry = 0u8.wrapping_sub(a >> 7);
+        a = (a << 1) ^ (0x1b & carry);
+        b >>= 1;
+    }
+    product
+}
+
+/// Division via the multiplicative inverse `b^254`.
+fn gf_div(a: u8, b: u8) -> u8 {
+    let mut inverse = 1u8;
+    let mut base = b;
+    let mut exp = 254u8;
+    while exp > 0 {
+        if exp & 1 == 1 {
+            inverse = gf_mul(inverse, base);
+        }
+        base = gf_mul(base, base);
+        exp >>= 1;
+    }
+    gf_mul(a, inverse)
+}
+
+fn checksum(body: &str) -> String {
+    let digest = Sha256::digest(body.as_bytes());
+    BASE64_URL.encode(&digest[..CHECK_LENGTH])
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &[u8] = b"{\"pub\":\"...\",\"priv\":\"...\"} backup bytes \x00\xff";
+
+    /// Every subset of `shares`, as bitmasks over their positions.
+    fn subsets(shares: &[Share]) -> impl Iterator<Item = Vec<Share>> + '_ {
+        (0u32..1 << shares.len()).map(move |mask| {
+            shares
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| mask & (1 << i) != 0)
+                .map(|(_, share)| share.clone())
+                .collect()
+        })
+    }
+
+    #[test]
+    fn every_k_of_n_subset_recombines() {
+        for (threshold, total) in [(2, 2), (2, 3), (3, 5), (4, 7), (5, 8)] {
+            let shares = split(SECRET, threshold, total).unwrap();
+            assert_eq!(shares.len(), total as usize);
+            for mut subset in subsets(&shares) {
+                if subset.len() < threshold as usize {
+                    assert!(matches!(
+                        combine(&subset),
+                        Err(ShamirError::NotEnoughShares { .. })
+                    ));
+                    continue;
+                }
+                assert_eq!(combine(&subset).unwrap().as_slice(), SECRET);
+                subset.reverse();
+                assert_eq!(combine(&subset).unwrap().as_slice(), SECRET);
+            }
+        }
+    }
+
+    #[test]
+    fn largest_split_recombines() {
+        let shares = split(SECRET, 3, 255).unwrap();
+        assert_eq!(combine(&shares[252..]).unwrap().as_slice(), SECRET);
+        assert_eq!(combine(&shares[..3]).unwrap().as_slice(), SECRET);
+    }
+
+    #[test]
+    fn rejects_invalid_thresholds() {
+        for (threshold, total) in [(0, 3), (1, 3), (4, 3)] {
+            assert!(matches!(
+                split(SECRET, threshold, total),
+                Err(ShamirError::InvalidThreshold { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn rejects_fewer_than_threshold() {
+        let shares = split(SECRET, 3, 5).unwrap();
+        assert!(matches!(
+            combine(&shares[..2]),
+            Err(ShamirError::NotEnoughShares {
+                needed: 3,
+                found: 2
+            })
+        ));
+        assert!(matches!(
+            combine(&[]),
+            Err(ShamirError::NotEnoughShares { .. })
+        ));
+    }
+
+    #[test]
+    fn rejects_mixed_sets() {
+        let a = split(SECRET, 2, 3).unwrap();
+        let b = split(SECRET, 2, 3).unwrap();
+        assert!(matches!(
+            combine(&[a[0].clone(), b[1].clone()]),
+            Err(ShamirError::MixedSets)
+        ));
+    }
+
+    #[test]
+    fn rejects_duplicate_indexes() {
+        let shares = split(SECRET, 2, 3).unwrap();
+        assert!(matches!(
+            combine(&[shares[1].clone(), shares[1].clone()]),
+            Err(ShamirError::DuplicateIndex(2))
+        ));
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let shares = split(SECRET, 2, 3).unwrap();
+        let parsed: Vec<Share> = shares
+            .iter()
+            .map(|share| Share::from_text(&format!(" {}\n", share.to_text())).unwrap())
+            .collect();
+        assert_eq!(parsed, shares);
+        assert_eq!(combine(&parsed[1..]).unwrap().as_slice(), SECRET);
+    }
+
+    #[test]
+    fn rejects_typos() {
+        let text = split(SECRET, 2, 3).unwrap()[0].to_text();
+        let (body, check) = text.rsplit_once(':').unwrap();
+        // Flip one character of the data.
+        let at = body.len() - 1;
+        let flipped = if &body[at..] == "A" { "B" } else { "A" };
+        let typo = format!("{}{flipped}:{check}", &body[..at]);
+        assert!(matches!(
+            Share::from_text(&typo),
+            Err(ShamirError::Checksum)
+        ));
+        assert!(matches!(
+            Share::from_text(&text[..text.len() - 1]),
+            Err(ShamirError::Checksum)
+        ));
+    }
+
+    #[test]
+    fn rejects_crafted_thresholds() {
+        let share = &split(SECRET, 2, 3).unwrap()[0];
+        for threshold in [0, 1] {
+            let crafted = Share {
+                threshold,
+                ..share.clone()
+            };
+            assert!(matches!(
+                Share::from_text(&crafted.to_text()),
+                Err(ShamirError::Malformed(_))
+            ));
+            assert!(matches!(
+                combine(&[crafted]),
+                Err(ShamirError::InvalidThreshold { .. })
+            ));
+        }
+    }
+}
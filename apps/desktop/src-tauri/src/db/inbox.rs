@@ -0,0 +1,700 @@
+//! The notification inbox: mentions, DMs and replies the user was notified
+//! about, kept per identity like the message cache.
+//!
+//! Records mirror `AppNotification` from `@nodes/core`. A message is only
+//! ever in the inbox once, so adding it again is a no-op. Pages are newest
+//! first and continue from the last record of the previous page. Adding
+//! applies the [`InboxRetention`] policy from the settings.
+
+use std::collections::BTreeMap;
+
+use rand::RngCore;
+use rusqlite::types::{FromSql, FromSqlError, FromSqlResult, ToSql, ToSqlOutput, ValueRef};
+use rusqlite::{params, Connection, Row, Transaction};
+use serde::{Deserialize, Serialize};
+
+use super::{Database, DbError};
+use crate::notifications::NotificationKind;
+
+const DEFAULT_PAGE_SIZE: u32 = 50;
+const MAX_PAGE_SIZE: u32 = 500;
+const MS_PER_DAY: i64 = 24 * 60 * 60 * 1000;
+
+pub const SCHEMA: &str = "
+    CREATE TABLE notifications (
+        id TEXT PRIMARY KEY,
+        type TEXT NOT NULL,
+        node_id TEXT,
+        node_name TEXT,
+        channel_id TEXT,
+        channel_name TEXT,
+        sender_key TEXT NOT NULL,
+        sender_name TEXT NOT NULL,
+        message_id TEXT NOT NULL UNIQUE,
+        message_preview TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        read INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE INDEX notifications_time ON notifications (timestamp, id);
+    CREATE INDEX notifications_unread ON notifications (read, channel_id);";
+
+/// An `AppNotification`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InboxNotification {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: NotificationKind,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub node_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel_name: Option<String>,
+    pub sender_key: String,
+    pub sender_name: String,
+    pub message_id: String,
+    pub message_preview: String,
+    pub timestamp: i64,
+    pub read: bool,
+}
+
+/// An `AppNotification` to add. The ID is generated if it's missing, which
+/// it only isn't when importing an older inbox.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NewNotification {
+    pub id: Option<String>,
+    #[serde(rename = "type")]
+    pub kind: NotificationKind,
+    pub node_id: Option<String>,
+    pub node_name: Option<String>,
+    pub channel_id: Option<String>,
+    pub channel_name: Option<String>,
+    pub sender_key: String,
+    pub sender_name: String,
+    pub message_id: String,
+    pub message_preview: String,
+    pub timestamp: i64,
+    #[serde(default)]
+    pub read: bool,
+}
+
+/// Where a page starts: just after this record.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InboxCursor {
+    pub timestamp: i64,
+    pub id: String,
+}
+
+/// Which notifications to list. Unset filters match everything.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct InboxQuery {
+    pub limit: Option<u32>,
+    pub after: Option<InboxCursor>,
+    pub types: Option<Vec<NotificationKind>>,
+    pub unread_only: bool,
+    pub node_id: Option<String>,
+    pub channel_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InboxPage {
+    pub notifications: Vec<InboxNotification>,
+    /// Pass as `after` for the next page; `None` on the last one.
+    pub next: Option<InboxCursor>,
+}
+
+/// A node, a channel, or with neither set, the whole inbox.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct InboxScope {
+    pub node_id: Option<String>,
+    pub channel_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnreadCounts {
+    pub total: u32,
+    pub by_type: BTreeMap<NotificationKind, u32>,
+    pub by_channel: BTreeMap<String, u32>,
+}
+
+/// How much of the inbox to keep.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct InboxRetention {
+    /// Drop read notifications older than this; 0 keeps them forever.
+    pub max_age_days: u32,
+    /// Drop the oldest beyond this many, read or not; 0 keeps them all.
+    pub max_count: u32,
+}
+
+impl Default for InboxRetention {
+    fn default() -> Self {
+        Self {
+            max_age_days: 90,
+            max_count: 2_000,
+        }
+    }
+}
+
+const COLUMNS: &str = "id, type, node_id, node_name, channel_id, channel_name, sender_key, \
+     sender_name, message_id, message_preview, timestamp, read";
+
+/// Matches `scope`, taking the node ID as `?1` and the channel ID as `?2`.
+const IN_SCOPE: &str = "(?1 IS NULL OR node_id = ?1) AND (?2 IS NULL OR channel_id = ?2)";
+
+impl Database {
+    /// Add notifications that aren't in the inbox yet, then apply
+    /// `retention`. Returns the ones added.
+    pub fn inbox_add(
+        &self,
+        notifications: &[NewNotification],
+        retention: &InboxRetention,
+    ) -> Result<Vec<InboxNotification>, DbError> {
+        self.with_conn(|conn| {
+            let tx = conn.transaction()?;
+            let mut added = Vec::new();
+            {
+                let mut insert = tx.prepare_cached(&format!(
+                    "INSERT OR IGNORE INTO notifications ({COLUMNS})
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)"
+                ))?;
+                for new in notifications {
+                    let notification = InboxNotification {
+                        id: new.id.clone().unwrap_or_else(|| new_id(new.timestamp)),
+                        kind: new.kind,
+                        node_id: new.node_id.clone(),
+                        node_name: new.node_name.clone(),
+                        channel_id: new.channel_id.clone(),
+                        channel_name: new.channel_name.clone(),
+                        sender_key: new.sender_key.clone(),
+                        sender_name: new.sender_name.clone(),
+                        message_id: new.message_id.clone(),
+                        message_preview: new.message_preview.clone(),
+                        timestamp: new.timestamp,
+                        read: new.read,
+                    };
+                    let n = &notification;
+                    let inserted = insert.execute(params![
+                        n.id,
+                        n.kind,
+                        n.node_id,
+                        n.node_name,
+                        n.channel_id,
+                        n.channel_name,
+                        n.sender_key,
+                        n.sender_name,
+                        n.message_id,
+                        n.message_preview,
+                        n.timestamp,
+                        n.read,
+                    ])?;
+                    if inserted > 0 {
+                        added.push(notification);
+                    }
+                }
+            }
+            prune(&tx, retention)?;
+            // Pruning may have dropped some of what was just added.
+            let mut kept = Vec::with_capacity(added.len());
+            for notification in added {
+                if exists(&tx, &notification.id)? {
+                    kept.push(notification);
+                }
+            }
+            tx.commit()?;
+            Ok(kept)
+        })
+    }
+
+    /// A page of notifications, newest first.
+    pub fn inbox_page(&self, query: &InboxQuery) -> Result<InboxPage, DbError> {
+        self.with_conn(|conn| {
+            let limit = query
+                .limit
+                .filter(|&l| l > 0)
+                .unwrap_or(DEFAULT_PAGE_SIZE)
+                .min(MAX_PAGE_SIZE);
+            let types = query
+                .types
+                .as_ref()
+                .map(serde_json::to_string)
+                .transpose()
+                .map_err(|e| DbError::Corrupt(e.to_string()))?;
+            let (after_time, after_id) = match &query.after {
+                Some(cursor) => (Some(cursor.timestamp), Some(cursor.id.as_str())),
+                None => (None, None),
+            };
+            let mut stmt = conn.prepare_cached(&format!(
+                "SELECT {COLUMNS} FROM notifications
+                 WHERE {IN_SCOPE}
+                   AND (?3 IS NULL OR type IN (SELECT value FROM json_each(?3)))
+                   AND (NOT ?4 OR read = 0)
+                   AND (?5 IS NULL OR (timestamp, id) < (?5, ?6))
+                 ORDER BY timestamp DESC, id DESC
+                 LIMIT ?7"
+            ))?;
+            let notifications = stmt
+                .query_map(
+                    params![
+                        query.node_id,
+                        query.channel_id,
+                        types,
+                        query.unread_only,
+                        after_time,
+                        after_id,
+                        limit + 1
+                    ],
+                    from_row,
+                )?
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(page(notifications, limit as usize))
+        })
+    }
+
+    /// Mark the notifications with these IDs read. Returns how many changed.
+    pub fn inbox_mark_read(&self, ids: &[String]) -> Result<usize, DbError> {
+        self.with_conn(|conn| {
+            let tx = conn.transaction()?;
+            let mut changed = 0;
+            {
+                let mut update = tx.prepare_cached(
+                    "UPDATE notifications SET read = 1 WHERE id = ?1 AND read = 0",
+                )?;
+                for id in ids {
+                    changed += update.execute([id])?;
+                }
+            }
+            tx.commit()?;
+            Ok(changed)
+        })
+    }
+
+    /// Mark everything in `scope` read. Returns how many changed.
+    pub fn inbox_mark_all_read(&self, scope: &InboxScope) -> Result<usize, DbError> {
+        self.with_conn(|conn| {
+            Ok(conn.execute(
+                &format!("UPDATE notifications SET read = 1 WHERE {IN_SCOPE} AND read = 0"),
+                params![scope.node_id, scope.channel_id],
+            )?)
+        })
+    }
+
+    /// Delete the notifications with these IDs. Returns how many existed.
+    pub fn inbox_remove(&self, ids: &[String]) -> Result<usize, DbError> {
+        self.with_conn(|conn| {
+            let tx = conn.transaction()?;
+            let mut removed = 0;
+            {
+                let mut delete = tx.prepare_cached("DELETE FROM notifications WHERE id = ?1")?;
+                for id in ids {
+                    removed += delete.execute([id])?;
+                }
+            }
+            tx.commit()?;
+            Ok(removed)
+        })
+    }
+
+    /// Delete everything in `scope`. Returns how many existed.
+    pub fn inbox_clear(&self, scope: &InboxScope) -> Result<usize, DbError> {
+        self.with_conn(|conn| {
+            Ok(conn.execute(
+                &format!("DELETE FROM notifications WHERE {IN_SCOPE}"),
+                params![scope.node_id, scope.channel_id],
+            )?)
+        })
+    }
+
+    pub fn inbox_unread(&self) -> Result<UnreadCounts, DbError> {
+        self.with_conn(|conn| unread(conn))
+    }
+}
+
+/// Split off the extra row fetched to tell whether there's another page.
+fn page(mut notifications: Vec<InboxNotification>, limit: usize) -> InboxPage {
+    let more = notifications.len() > limit;
+    notifications.truncate(limit);
+    let next = more
+        .then(|| notifications.last())
+        .flatten()
+        .map(|last| InboxCursor {
+            timestamp: last.timestamp,
+            id: last.id.clone(),
+        });
+    InboxPage {
+        notifications,
+        next,
+    }
+}
+
+fn unread(conn: &Connection) -> Result<UnreadCounts, DbError> {
+    let mut stmt = conn.prepare_cached(
+        "SELECT type, channel_id, count(*) FROM notifications
+         WHERE read = 0 GROUP BY type, channel_id",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, NotificationKind>(0)?,
+            row.get::<_, Option<String>>(1)?,
+            row.get::<_, u32>(2)?,
+        ))
+    })?;
+    let mut counts = UnreadCounts::default();
+    for row in rows {
+        let (kind, channel_id, count) = row?;
+        counts.total += count;
+        *counts.by_type.entry(kind).or_default() += count;
+        if let Some(channel_id) = channel_id {
+            *counts.by_channel.entry(channel_id).or_default() += count;
+        }
+    }
+    Ok(counts)
+}
+
+fn prune(tx: &Transaction<'_>, retention: &InboxRetention) -> Result<(), DbError> {
+    if retention.max_age_days > 0 {
+        let cutoff = now_ms() - i64::from(retention.max_age_days) * MS_PER_DAY;
+        tx.prepare_cached("DELETE FROM notifications WHERE read = 1 AND timestamp < ?1")?
+            .execute([cutoff])?;
+    }
+    if retention.max_count > 0 {
+        tx.prepare_cached(
+            "DELETE FROM notifications WHERE id IN (
+                SELECT id FROM notifications
+                ORDER BY timestamp DESC, id DESC
+                LIMIT -1 OFFSET ?1
+             )",
+        )?
+        .execute([retention.max_count])?;
+    }
+    Ok(())
+}
+
+fn exists(tx: &Transaction<'_>, id: &str) -> rusqlite::Result<bool> {
+    tx.prepare_cached("SELECT 1 FROM notifications WHERE id = ?1")?
+        .exists([id])
+}
+
+/// Same shape as the IDs the frontend used to generate.
+fn new_id(timestamp: i64) -> String {
+    let mut bytes = [0u8; 4];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    let suffix: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+    format!("{timestamp}-{suffix}")
+}
+
+fn now_ms() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_millis() as i64)
+        .unwrap_or(0)
+}
+
+fn from_row(row: &Row<'_>) -> rusqlite::Result<InboxNotification> {
+    Ok(InboxNotification {
+        id: row.get(0)?,
+        kind: row.get(1)?,
+        node_id: row.get(2)?,
+        node_name: row.get(3)?,
+        channel_id: row.get(4)?,
+        channel_name: row.get(5)?,
+        sender_key: row.get(6)?,
+        sender_name: row.get(7)?,
+        message_id: row.get(8)?,
+        message_preview: row.get(9)?,
+        timestamp: row.get(10)?,
+        read: row.get(11)?,
+    })
+}
+
+/// Stored as the same string the frontend uses.
+impl ToSql for NotificationKind {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(ToSqlOutput::from(self.as_str()))
+    }
+}
+
+impl FromSql for NotificationKind {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let text = value.as_str()?;
+        NotificationKind::parse(text)
+            .ok_or_else(|| FromSqlError::Other(format!("unknown notification type {text}").into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::temp_database;
+
+    fn new(message_id: &str, kind: NotificationKind, timestamp: i64) -> NewNotification {
+        NewNotification {
+            id: None,
+            kind,
+            node_id: Some("node".into()),
+            node_name: Some("Node".into()),
+            channel_id: Some("general".into()),
+            channel_name: Some("general".into()),
+            sender_key: "bob".into(),
+            sender_name: "Bob".into(),
+            message_id: message_id.into(),
+            message_preview: "hi".into(),
+            timestamp,
+            read: false,
+        }
+    }
+
+    fn in_channel(mut notification: NewNotification, channel_id: &str) -> NewNotification {
+        notification.channel_id = Some(channel_id.into());
+        notification
+    }
+
+    fn keep_all() -> InboxRetention {
+        InboxRetention {
+            max_age_days: 0,
+            max_count: 0,
+        }
+    }
+
+    fn message_ids(page: &InboxPage) -> Vec<&str> {
+        page.notifications
+            .iter()
+            .map(|n| n.message_id.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn pages_continue_from_the_cursor() {
+        let (_dir, db) = temp_database();
+        // Two share a timestamp, so the cursor has to break the tie by ID.
+        let mut notifications: Vec<_> = (1..=5)
+            .map(|i| new(&format!("m{i}"), NotificationKind::Mention, i))
+            .collect();
+        notifications.push(NewNotification {
+            id: Some("zz".into()),
+            ..new("m3b", NotificationKind::Mention, 3)
+        });
+        db.inbox_add(&notifications, &keep_all()).unwrap();
+
+        let mut query = InboxQuery {
+            limit: Some(2),
+            ..Default::default()
+        };
+        let mut seen = Vec::new();
+        loop {
+            let page = db.inbox_page(&query).unwrap();
+            assert!(page.notifications.len() <= 2);
+            seen.extend(message_ids(&page).into_iter().map(str::to_owned));
+            match page.next {
+                Some(next) => query.after = Some(next),
+                None => break,
+            }
+        }
+        assert_eq!(seen, ["m5", "m4", "m3b", "m3", "m2", "m1"]);
+
+        // An oversized limit is capped rather than overflowing.
+        let page = db
+            .inbox_page(&InboxQuery {
+                limit: Some(u32::MAX),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(page.notifications.len(), 6);
+        assert!(page.next.is_none());
+    }
+
+    #[test]
+    fn filters_by_type_read_state_and_scope() {
+        let (_dir, db) = temp_database();
+        let mut dm = new("dm", NotificationKind::Dm, 4);
+        dm.node_id = None;
+        dm.channel_id = None;
+        let mut elsewhere = new("elsewhere", NotificationKind::Mention, 5);
+        elsewhere.node_id = Some("other".into());
+        let added = db
+            .inbox_add(
+                &[
+                    new("mention", NotificationKind::Mention, 1),
+                    new("reply", NotificationKind::Reply, 2),
+                    in_channel(new("random", NotificationKind::Everyone, 3), "random"),
+                    dm,
+                    elsewhere,
+                ],
+                &keep_all(),
+            )
+            .unwrap();
+        assert_eq!(added.len(), 5);
+        db.inbox_mark_read(&[added[1].id.clone()]).unwrap();
+
+        let listed = |query: InboxQuery| {
+            let page = db.inbox_page(&query).unwrap();
+            message_ids(&page)
+                .into_iter()
+                .map(str::to_owned)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            listed(InboxQuery {
+                types: Some(vec![NotificationKind::Reply, NotificationKind::Dm]),
+                ..Default::default()
+            }),
+            ["dm", "reply"]
+        );
+        assert_eq!(
+            listed(InboxQuery {
+                unread_only: true,
+                node_id: Some("node".into()),
+                ..Default::default()
+            }),
+            ["random", "mention"]
+        );
+        assert_eq!(
+            listed(InboxQuery {
+                channel_id: Some("general".into()),
+                ..Default::default()
+            }),
+            ["elsewhere", "reply", "mention"]
+        );
+        assert_eq!(
+            listed(InboxQuery {
+                node_id: Some("node".into()),
+                channel_id: Some("general".into()),
+                ..Default::default()
+            }),
+            ["reply", "mention"]
+        );
+        assert!(listed(InboxQuery {
+            types: Some(Vec::new()),
+            ..Default::default()
+        })
+        .is_empty());
+    }
+
+    #[test]
+    fn a_message_is_only_added_once() {
+        let (_dir, db) = temp_database();
+        let first = db
+            .inbox_add(&[new("m1", NotificationKind::Mention, 1)], &keep_all())
+            .unwrap();
+        assert_eq!(first.len(), 1);
+        assert!(first[0].id.starts_with("1-"));
+
+        let again = db
+            .inbox_add(
+                &[
+                    new("m1", NotificationKind::Reply, 2),
+                    new("m2", NotificationKind::Mention, 2),
+                    new("m2", NotificationKind::Mention, 3),
+                ],
+                &keep_all(),
+            )
+            .unwrap();
+        assert_eq!(again.len(), 1);
+        assert_eq!(again[0].message_id, "m2");
+
+        let page = db.inbox_page(&InboxQuery::default()).unwrap();
+        assert_eq!(message_ids(&page), ["m2", "m1"]);
+        assert_eq!(page.notifications[1], first[0]);
+    }
+
+    #[test]
+    fn retention_prunes_old_read_and_excess_notifications() {
+        let (_dir, db) = temp_database();
+        let old = now_ms() - 10 * MS_PER_DAY;
+        let mut old_read = new("old-read", NotificationKind::Mention, old);
+        old_read.read = true;
+        let retention = InboxRetention {
+            max_age_days: 7,
+            max_count: 3,
+        };
+        let added = db
+            .inbox_add(
+                &[
+                    old_read,
+                    new("old-unread", NotificationKind::Mention, old),
+                    new("recent", NotificationKind::Mention, now_ms()),
+                ],
+                &retention,
+            )
+            .unwrap();
+        // Read notifications past the age limit go; unread ones stay.
+        let kept: Vec<_> = added.iter().map(|n| n.message_id.as_str()).collect();
+        assert_eq!(kept, ["old-unread", "recent"]);
+
+        // Beyond the count, the oldest go, read or not.
+        let now = now_ms();
+        let added = db
+            .inbox_add(
+                &[
+                    new("a", NotificationKind::Mention, now + 1),
+                    new("b", NotificationKind::Mention, now + 2),
+                ],
+                &retention,
+            )
+            .unwrap();
+        assert_eq!(added.len(), 2);
+        let page = db.inbox_page(&InboxQuery::default()).unwrap();
+        assert_eq!(message_ids(&page), ["b", "a", "recent"]);
+
+        // Pruning can drop what was just added if it's the oldest.
+        let added = db
+            .inbox_add(&[new("ancient", NotificationKind::Mention, 0)], &retention)
+            .unwrap();
+        assert!(added.is_empty());
+    }
+
+    #[test]
+    fn unread_counts_follow_reads_and_removals() {
+        let (_dir, db) = temp_database();
+        assert_eq!(db.inbox_unread().unwrap(), UnreadCounts::default());
+        let mut dm = new("dm", NotificationKind::Dm, 4);
+        dm.channel_id = None;
+        let added = db
+            .inbox_add(
+                &[
+                    new("m1", NotificationKind::Mention, 1),
+                    new("m2", NotificationKind::Mention, 2),
+                    in_channel(new("r1", NotificationKind::Reply, 3), "random"),
+                    dm,
+                ],
+                &keep_all(),
+            )
+            .unwrap();
+
+        let counts = db.inbox_unread().unwrap();
+        assert_eq!(counts.total, 4);
+        assert_eq!(counts.by_type[&NotificationKind::Mention], 2);
+        assert_eq!(counts.by_type[&NotificationKind::Reply], 1);
+        assert_eq!(counts.by_type[&NotificationKind::Dm], 1);
+        assert_eq!(counts.by_channel["general"], 2);
+        assert_eq!(counts.by_channel["random"], 1);
+        assert_eq!(counts.by_channel.len(), 2);
+
+        assert_eq!(db.inbox_mark_read(&[added[0].id.clone()]).unwrap(), 1);
+        assert_eq!(db.inbox_mark_read(&[added[0].id.clone()]).unwrap(), 0);
+        assert_eq!(db.inbox_unread().unwrap().by_channel["general"], 1);
+
+        let random = InboxScope {
+            node_id: None,
+            channel_id: Some("random".into()),
+        };
+        assert_eq!(db.inbox_mark_all_read(&random).unwrap(), 1);
+        let counts = db.inbox_unread().unwrap();
+        assert_eq!(counts.total, 2);
+        assert!(!counts.by_channel.contains_key("random"));
+
+        assert_eq!(db.inbox_remove(&[added[3].id.clone()]).unwrap(), 1);
+        assert_eq!(db.inbox_unread().unwrap().total, 1);
+        assert_eq!(db.inbox_clear(&InboxScope::default()).unwrap(), 3);
+        assert_eq!(db.inbox_unread().unwrap(), UnreadCounts::default());
+    }
+}
@@ -0,0 +1,567 @@
+//! Offline cache of channel messages.
+//!
+//! Records mirror `TransportMessage` and are upserted as they arrive from
+//! Gun, so the same message may be written many times: once on send, again
+//! for every relay echo, edit and deletion. Merging is deterministic —
+//! deletion is sticky, the latest edit wins, and edit histories are unioned.
+
+use std::collections::BTreeMap;
+
+use rusqlite::types::Type;
+use rusqlite::{params, OptionalExtension, Row, Transaction};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::{Database, DbError};
+
+/// Same default as `GunMessageTransport.getHistory`.
+const DEFAULT_HISTORY_LIMIT: u32 = 50;
+
+/// A `TransportMessage`, plus the reactions cached for it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedMessage {
+    pub id: String,
+    pub content: String,
+    pub timestamp: i64,
+    pub author_key: String,
+    pub channel_id: String,
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edited_at: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub attachments: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reply_to: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_at: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deleted_by: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edited: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edit_history: Option<Vec<EditEntry>>,
+    /// Read-only: filled in by `history`, ignored on write.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub reactions: BTreeMap<String, Vec<Reaction>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EditEntry {
+    pub content: String,
+    pub edited_at: i64,
+}
+
+/// `ReactionData` from `@nodes/transport`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Reaction {
+    pub emoji: String,
+    pub user_key: String,
+    pub timestamp: i64,
+}
+
+/// `HistoryOpts`: the most recent `limit` messages strictly between
+/// `after` and `before`.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryOpts {
+    pub limit: Option<u32>,
+    pub before: Option<i64>,
+    pub after: Option<i64>,
+}
+
+const COLUMNS: &str = "id, channel_id, timestamp, author_key, type, content, signature, \
+     attachments, reply_to, edited, edited_at, edit_history, deleted, deleted_at, deleted_by";
+
+impl Database {
+    /// Upsert messages, merging with any cached copy. Returns how many rows
+    /// were inserted or changed.
+    pub fn put_messages(&self, messages: &[CachedMessage]) -> Result<usize, DbError> {
+        self.with_conn(|conn| {
+            let tx = conn.transaction()?;
+            let mut changed = 0;
+            for incoming in messages {
+                let incoming = normalize(incoming);
+                let existing = select_one(&tx, &incoming.id)?;
+                let merged = match &existing {
+                    Some(existing) => merge(existing, &incoming),
+                    None => incoming,
+                };
+                if existing.as_ref() != Some(&merged) {
+                    write(&tx, &merged)?;
+                    changed += 1;
+                }
+            }
+            tx.commit()?;
+            Ok(changed)
+        })
+    }
+
+    /// Messages for a channel in ascending timestamp order, newest page last,
+    /// matching `sortAndFilter` in the Gun transport.
+    pub fn history(
+        &self,
+        channel_id: &str,
+        opts: &HistoryOpts,
+    ) -> Result<Vec<CachedMessage>, DbError> {
+        self.with_conn(|conn| {
+            let mut stmt = conn.prepare_cached(&format!(
+                "SELECT {COLUMNS} FROM messages
+                 WHERE channel_id = ?1
+                   AND (?2 IS NULL OR timestamp < ?2)
+                   AND (?3 IS NULL OR timestamp > ?3)
+                 ORDER BY timestamp DESC, id DESC
+                 LIMIT ?4"
+            ))?;
+            let limit = opts
+                .limit
+                .filter(|&l| l > 0)
+                .unwrap_or(DEFAULT_HISTORY_LIMIT);
+            let mut messages = stmt
+                .query_map(
+                    params![channel_id, opts.before, opts.after, limit],
+                    from_row,
+                )?
+                .collect::<Result<Vec<_>, _>>()?;
+            messages.reverse();
+
+            let mut reactions = conn.prepare_cached(
+                "SELECT emoji, user_key, timestamp FROM reactions
+                 WHERE message_id = ?1 ORDER BY emoji, timestamp",
+            )?;
+            for message in &mut messages {
+                let rows = reactions.query_map([&message.id], |row| {
+                    Ok(Reaction {
+                        emoji: row.get(0)?,
+                        user_key: row.get(1)?,
+                        timestamp: row.get(2)?,
+                    })
+                })?;
+                for reaction in rows {
+                    let reaction = reaction?;
+                    message
+                        .reactions
+                        .entry(reaction.emoji.clone())
+                        .or_default()
+                        .push(reaction);
+                }
+            }
+            Ok(messages)
+        })
+    }
+
+    /// Replace the cached reactions of one message with the current set, as
+    /// delivered by `subscribeReactions`.
+    pub fn set_reactions(
+        &self,
+        channel_id: &str,
+        message_id: &str,
+        reactions: &BTreeMap<String, Vec<Reaction>>,
+    ) -> Result<(), DbError> {
+        self.with_conn(|conn| {
+            let tx = conn.transaction()?;
+            tx.execute("DELETE FROM reactions WHERE message_id = ?1", [message_id])?;
+            {
+                let mut insert = tx.prepare_cached(
+                    "INSERT OR REPLACE INTO reactions
+                     (message_id, channel_id, emoji, user_key, timestamp)
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                )?;
+                for (emoji, users) in reactions {
+                    for reaction in users {
+                        insert.execute(params![
+                            message_id,
+                            channel_id,
+                            emoji,
+                            reaction.user_key,
+                            reaction.timestamp
+                        ])?;
+                    }
+                }
+            }
+            tx.commit()?;
+            Ok(())
+        })
+    }
+}
+
+/// Shape an incoming record the way it reads back from the table, so an
+/// unchanged echo compares equal to the cached row.
+fn normalize(message: &CachedMessage) -> CachedMessage {
+    CachedMessage {
+        edited: message.edited.filter(|&e| e),
+        deleted: message.deleted.filter(|&d| d),
+        reactions: BTreeMap::new(),
+        ..message.clone()
+    }
+}
+
+/// Combine a cached record with an incoming copy of the same message.
+fn merge(existing: &CachedMessage, incoming: &CachedMessage) -> CachedMessage {
+    let is_deleted = |m: &CachedMessage| m.deleted == Some(true);
+    let edit_time = |m: &CachedMessage| m.edited_at.unwrap_or(0);
+
+    // Deletion is sticky: the deleted copy replaces content and clears
+    // attachments and history, like `deleteMessage` does in the graph.
+    if is_deleted(existing) || is_deleted(incoming) {
+        let source = if is_deleted(existing) {
+            existing
+        } else {
+            incoming
+        };
+        return CachedMessage {
+            content: source.content.clone(),
+            signature: source.signature.clone(),
+            attachments: None,
+            edit_history: None,
+            deleted: Some(true),
+            deleted_at: source.deleted_at.or(existing.deleted_at),
+            deleted_by: source.deleted_by.clone().or(existing.deleted_by.clone()),
+            reactions: BTreeMap::new(),
+            ..existing.clone()
+        };
+    }
+
+    let latest = if edit_time(incoming) > edit_time(existing) {
+        incoming
+    } else {
+        existing
+    };
+    // A signature only vouches for the content it came with, so the two
+    // travel together. A copy of the same version may carry one ours lacks.
+    let signature = latest.signature.clone().or_else(|| {
+        [existing, incoming]
+            .into_iter()
+            .filter(|m| m.content == latest.content && m.edited_at == latest.edited_at)
+            .find_map(|m| m.signature.clone())
+    });
+    CachedMessage {
+        content: latest.content.clone(),
+        signature,
+        edited: latest.edited.or(existing.edited),
+        edited_at: latest.edited_at.or(existing.edited_at),
+        attachments: existing
+            .attachments
+            .clone()
+            .or(incoming.attachments.clone()),
+        reply_to: existing.reply_to.clone().or(incoming.reply_to.clone()),
+        edit_history: merge_history(&existing.edit_history, &incoming.edit_history),
+        reactions: BTreeMap::new(),
+        ..existing.clone()
+    }
+}
+
+fn merge_history(a: &Option<Vec<EditEntry>>, b: &Option<Vec<EditEntry>>) -> Option<Vec<EditEntry>> {
+    if a.is_none() && b.is_none() {
+        return None;
+    }
+    let mut merged: Vec<EditEntry> = a.iter().chain(b.iter()).flatten().cloned().collect();
+    merged.sort_by(|x, y| (x.edited_at, &x.content).cmp(&(y.edited_at, &y.content)));
+    merged.dedup();
+    Some(merged)
+}
+
+fn select_one(tx: &Transaction<'_>, id: &str) -> Result<Option<CachedMessage>, DbError> {
+    let message = tx
+        .prepare_cached(&format!("SELECT {COLUMNS} FROM messages WHERE id = ?1"))?
+        .query_row([id], from_row)
+        .optional()?;
+    Ok(message)
+}
+
+fn write(tx: &Transaction<'_>, m: &CachedMessage) -> Result<(), DbError> {
+    let reply_to = m.reply_to.as_ref().map(Value::to_string);
+    let edit_history = m
+        .edit_history
+        .as_ref()
+        .map(serde_json::to_string)
+        .transpose()
+        .map_err(|e| DbError::Corrupt(e.to_string()))?;
+    tx.prepare_cached(&format!(
+        "INSERT OR REPLACE INTO messages ({COLUMNS})
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)"
+    ))?
+    .execute(params![
+        m.id,
+        m.channel_id,
+        m.timestamp,
+        m.author_key,
+        m.kind,
+        m.content,
+        m.signature,
+        m.attachments,
+        reply_to,
+        m.edited.unwrap_or(false),
+        m.edited_at,
+        edit_history,
+        m.deleted.unwrap_or(false),
+        m.deleted_at,
+        m.deleted_by,
+    ])?;
+    Ok(())
+}
+
+fn from_row(row: &Row<'_>) -> rusqlite::Result<CachedMessage> {
+    let edited: bool = row.get(9)?;
+    let deleted: bool = row.get(12)?;
+    Ok(CachedMessage {
+        id: row.get(0)?,
+        channel_id: row.get(1)?,
+        timestamp: row.get(2)?,
+        author_key: row.get(3)?,
+        kind: row.get(4)?,
+        content: row.get(5)?,
+        signature: row.get(6)?,
+        attachments: row.get(7)?,
+        reply_to: json_column(row, 8)?,
+        edited: edited.then_some(true),
+        edited_at: row.get(10)?,
+        edit_history: json_column(row, 11)?,
+        deleted: deleted.then_some(true),
+        deleted_at: row.get(13)?,
+        deleted_by: row.get(14)?,
+        reactions: BTreeMap::new(),
+    })
+}
+
+fn json_column<T: DeserializeOwned>(row: &Row<'_>, index: usize) -> rusqlite::Result<Option<T>> {
+    let text: Option<String> = row.get(index)?;
+    text.map(|text| {
+        serde_json::from_str(&text)
+            .map_err(|e| rusqlite::Error::FromSqlConversionFailure(index, Type::Text, Box::new(e)))
+    })
+    .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::temp_database;
+
+    fn message(content: &str, signature: Option<&str>, edited_at: Option<i64>) -> CachedMessage {
+        CachedMessage {
+            id: "m1".into(),
+            content: content.into(),
+            timestamp: 1,
+            author_key: "alice".into(),
+            channel_id: "c1".into(),
+            kind: "text".into(),
+            signature: signature.map(Into::into),
+            edited_at,
+            attachments: None,
+            reply_to: None,
+            deleted: None,
+            deleted_at: None,
+            deleted_by: None,
+            edited: edited_at.map(|_| true),
+            edit_history: None,
+            reactions: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn unsigned_edit_drops_the_old_signature() {
+        let original = message("hello", Some("sig-hello"), None);
+        let edit = message("hello!", None, Some(5));
+        let merged = merge(&original, &edit);
+        assert_eq!(merged.content, "hello!");
+        assert_eq!(merged.signature, None);
+        // Whichever order they arrive in.
+        assert_eq!(merge(&edit, &original), merged);
+    }
+
+    #[test]
+    fn signature_follows_the_winning_edit() {
+        let original = message("hello", Some("sig-hello"), None);
+        let edit = message("hello!", Some("sig-edit"), Some(5));
+        assert_eq!(
+            merge(&original, &edit).signature.as_deref(),
+            Some("sig-edit")
+        );
+        let stale = merge(&edit, &original);
+        assert_eq!(stale.content, "hello!");
+        assert_eq!(stale.signature.as_deref(), Some("sig-edit"));
+    }
+
+    #[test]
+    fn echo_fills_in_a_missing_signature() {
+        let unsigned = message("hello", None, None);
+        let signed = message("hello", Some("sig-hello"), None);
+        assert_eq!(
+            merge(&unsigned, &signed).signature.as_deref(),
+            Some("sig-hello")
+        );
+        assert_eq!(
+            merge(&signed, &unsigned).signature.as_deref(),
+            Some("sig-hello")
+        );
+    }
+
+    fn posted(id: &str, channel_id: &str, timestamp: i64) -> CachedMessage {
+        CachedMessage {
+            id: id.into(),
+            channel_id: channel_id.into(),
+            timestamp,
+            ..message(id, None, None)
+        }
+    }
+
+    fn ids(messages: &[CachedMessage]) -> Vec<&str> {
+        messages.iter().map(|m| m.id.as_str()).collect()
+    }
+
+    fn reaction(emoji: &str, user_key: &str, timestamp: i64) -> Reaction {
+        Reaction {
+            emoji: emoji.into(),
+            user_key: user_key.into(),
+            timestamp,
+        }
+    }
+
+    #[test]
+    fn history_pages_oldest_first() {
+        let (_dir, db) = temp_database();
+        db.put_messages(&[
+            posted("m1", "c1", 1),
+            posted("m2", "c1", 2),
+            posted("m3b", "c1", 3),
+            posted("m3a", "c1", 3),
+            posted("m4", "c1", 4),
+            posted("m5", "c1", 5),
+            posted("other", "c2", 3),
+        ])
+        .unwrap();
+
+        let history = |limit, before, after| {
+            let opts = HistoryOpts {
+                limit,
+                before,
+                after,
+            };
+            db.history("c1", &opts).unwrap()
+        };
+        assert_eq!(
+            ids(&history(None, None, None)),
+            ["m1", "m2", "m3a", "m3b", "m4", "m5"]
+        );
+        assert_eq!(ids(&history(Some(0), None, None)).len(), 6);
+        // The newest page, still oldest first.
+        assert_eq!(ids(&history(Some(2), None, None)), ["m4", "m5"]);
+        assert_eq!(ids(&history(Some(2), Some(4), None)), ["m3a", "m3b"]);
+        assert_eq!(ids(&history(Some(3), Some(4), None)), ["m2", "m3a", "m3b"]);
+        assert_eq!(ids(&history(None, None, Some(3))), ["m4", "m5"]);
+        assert_eq!(
+            ids(&history(None, Some(5), Some(1))),
+            ["m2", "m3a", "m3b", "m4"]
+        );
+        assert!(history(None, Some(3), Some(2)).is_empty());
+        assert!(db
+            .history("c3", &HistoryOpts::default())
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn repeated_puts_are_merged_by_id() {
+        let (_dir, db) = temp_database();
+        let original = CachedMessage {
+            attachments: Some(r#"[{"name":"a.txt"}]"#.into()),
+            reply_to: Some(serde_json::json!({ "id": "m0", "content": "hi" })),
+            edited: Some(false),
+            deleted: Some(false),
+            ..posted("m1", "c1", 1)
+        };
+        assert_eq!(db.put_messages(std::slice::from_ref(&original)).unwrap(), 1);
+        // Relay echoes change nothing.
+        assert_eq!(
+            db.put_messages(&[original.clone(), original.clone()])
+                .unwrap(),
+            0
+        );
+        let cached = db.history("c1", &HistoryOpts::default()).unwrap();
+        assert_eq!(cached, [normalize(&original)]);
+
+        let edit = CachedMessage {
+            content: "edited".into(),
+            edited: Some(true),
+            edited_at: Some(5),
+            edit_history: Some(vec![EditEntry {
+                content: original.content.clone(),
+                edited_at: 5,
+            }]),
+            attachments: None,
+            ..original.clone()
+        };
+        // Counted once even when the edit and a stale echo arrive together.
+        assert_eq!(db.put_messages(&[edit, original.clone()]).unwrap(), 1);
+        let cached = db.history("c1", &HistoryOpts::default()).unwrap();
+        assert_eq!(cached.len(), 1);
+        assert_eq!(cached[0].content, "edited");
+        assert_eq!(cached[0].edited_at, Some(5));
+        assert_eq!(cached[0].attachments, original.attachments);
+        assert_eq!(cached[0].reply_to, original.reply_to);
+        assert_eq!(cached[0].edit_history.as_ref().map(Vec::len), Some(1));
+
+        let deleted = CachedMessage {
+            content: String::new(),
+            deleted: Some(true),
+            deleted_at: Some(9),
+            ..posted("m1", "c1", 1)
+        };
+        assert_eq!(db.put_messages(&[deleted]).unwrap(), 1);
+        assert_eq!(db.put_messages(&[original]).unwrap(), 0);
+        let cached = db.history("c1", &HistoryOpts::default()).unwrap();
+        assert_eq!(cached[0].deleted, Some(true));
+        assert_eq!(cached[0].attachments, None);
+    }
+
+    #[test]
+    fn reactions_are_replaced_per_message() {
+        let (_dir, db) = temp_database();
+        db.put_messages(&[posted("m1", "c1", 1), posted("m2", "c1", 2)])
+            .unwrap();
+        let mut reactions = BTreeMap::new();
+        reactions.insert(
+            "👍".to_owned(),
+            vec![reaction("👍", "carol", 7), reaction("👍", "bob", 3)],
+        );
+        reactions.insert("🎉".to_owned(), vec![reaction("🎉", "bob", 4)]);
+        db.set_reactions("c1", "m1", &reactions).unwrap();
+        db.set_reactions("c1", "m2", &reactions).unwrap();
+
+        let cached = db.history("c1", &HistoryOpts::default()).unwrap();
+        assert_eq!(
+            cached[0].reactions["👍"],
+            [reaction("👍", "bob", 3), reaction("👍", "carol", 7)]
+        );
+        assert_eq!(cached[0].reactions["🎉"], [reaction("🎉", "bob", 4)]);
+
+        // The new set replaces the old one, for that message only.
+        reactions.remove("🎉");
+        reactions.insert("👍".to_owned(), vec![reaction("👍", "bob", 3)]);
+        db.set_reactions("c1", "m1", &reactions).unwrap();
+        // Writing the message again leaves its reactions alone.
+        db.put_messages(&[CachedMessage {
+            content: "edited".into(),
+            edited_at: Some(9),
+            ..posted("m1", "c1", 1)
+        }])
+        .unwrap();
+        let cached = db.history("c1", &HistoryOpts::default()).unwrap();
+        assert_eq!(cached[0].reactions, reactions);
+        assert_eq!(cached[1].reactions.len(), 2);
+
+        db.set_reactions("c1", "m1", &BTreeMap::new()).unwrap();
+        let cached = db.history("c1", &HistoryOpts::default()).unwrap();
+        assert!(cached[0].reactions.is_empty());
+        assert_eq!(cached[1].reactions.len(), 2);
+    }
+}
@@ -0,0 +1,399 @@
+//! Embedded SQLite database for data the app caches locally.
+//!
+//! Each identity gets its own database, encrypted page-by-page with
+//! SQLCipher. The page key is random and kept in a file sealed to the
+//! identity (see [`crate::keystore::sealed`]), so nothing can be read until
+//! that identity has been unlocked. Changing the passphrase rotates the page
+//! key with [`Database::rekey`].
+//!
+//! While unlocked, a single connection lives behind a mutex; every query is
+//! short and runs from a blocking task (see `commands::run_blocking`). The
+//! schema is migrated forward on open using SQLite's `user_version`.
+
+pub mod inbox;
+pub mod messages;
+pub mod search;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use rand::RngCore;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use zeroize::{Zeroize, ZeroizeOnDrop, Zeroizing};
+
+use crate::crypto::{sea, KeyPair};
+use crate::keystore::sealed;
+
+/// Schema migrations, applied in order. Index + 1 is the resulting version.
+const MIGRATIONS: &[&str] = &[
+    // 1: message cache
+    "CREATE TABLE messages (
+        id TEXT PRIMARY KEY,
+        channel_id TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        author_key TEXT NOT NULL,
+        type TEXT NOT NULL,
+        content TEXT NOT NULL,
+        signature TEXT,
+        attachments TEXT,
+        reply_to TEXT,
+        edited INTEGER NOT NULL DEFAULT 0,
+        edited_at INTEGER,
+        edit_history TEXT,
+        deleted INTEGER NOT NULL DEFAULT 0,
+        deleted_at INTEGER,
+        deleted_by TEXT
+    );
+    CREATE INDEX messages_channel_time ON messages (channel_id, timestamp);
+    CREATE TABLE reactions (
+        message_id TEXT NOT NULL,
+        channel_id TEXT NOT NULL,
+        emoji TEXT NOT NULL,
+        user_key TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        PRIMARY KEY (message_id, emoji, user_key)
+    );
+    CREATE INDEX reactions_channel ON reactions (channel_id);",
+    // 2: full-text search
+    crate::search::SCHEMA,
+    // 3: notification inbox
+    inbox::SCHEMA,
+];
+
+const SEAL_INFO: &[u8] = b"nodes/db/page-key";
+/// Page cache size; negative means KiB rather than pages.
+const CACHE_KIB: i64 = -32 * 1024;
+const DB_EXT: &str = "db";
+const KEY_EXT: &str = "key";
+/// A rotated key is written here before `PRAGMA rekey`, so a crash midway
+/// leaves a key that opens the database either way.
+const PENDING_KEY_EXT: &str = "key.next";
+
+#[derive(Debug, thiserror::Error)]
+pub enum DbError {
+    #[error("Local database is locked. Unlock an identity first")]
+    Locked,
+    #[error("Local database key does not match the database file")]
+    WrongKey,
+    #[error("Database error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("Stored value is corrupt: {0}")]
+    Corrupt(String),
+    #[error(transparent)]
+    Key(#[from] sea::SeaError),
+    #[error("Database I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// The local database. One instance is managed by Tauri.
+pub struct Database {
+    dir: PathBuf,
+    open: Mutex<Option<OpenDatabase>>,
+}
+
+struct OpenDatabase {
+    owner: String,
+    conn: Connection,
+    seal_key: Zeroizing<[u8; 32]>,
+}
+
+/// Contents of the sealed key file.
+#[derive(Serialize, Deserialize, Zeroize, ZeroizeOnDrop)]
+struct PageKey {
+    hex: String,
+}
+
+impl Database {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            open: Mutex::new(None),
+        }
+    }
+
+    /// Open the database belonging to `me`, creating it on first use. Any
+    /// other identity's database is closed first.
+    pub fn unlock(&self, me: &KeyPair) -> Result<(), DbError> {
+        let mut open = self.open.lock().unwrap_or_else(|e| e.into_inner());
+        if open.as_ref().is_some_and(|db| db.owner == me.pub_key) {
+            return Ok(());
+        }
+        *open = None;
+
+        let seal_key = sealed::identity_key(me, SEAL_INFO)?;
+        let db_path = self.path_for(&me.pub_key, DB_EXT);
+        let key_path = self.path_for(&me.pub_key, KEY_EXT);
+        let pending_path = self.path_for(&me.pub_key, PENDING_KEY_EXT);
+        let aad = me.pub_key.as_bytes();
+
+        let current: Option<PageKey> = sealed::read_json(&key_path, &seal_key, aad)?;
+        let pending: Option<PageKey> = sealed::read_json(&pending_path, &seal_key, aad)?;
+
+        let mut conn = match (current, pending) {
+            (None, None) if db_path.exists() => return Err(DbError::WrongKey),
+            (None, None) => {
+                let key = PageKey::random();
+                sealed::write_json(&key_path, &seal_key, aad, &key)?;
+                open_with_key(&db_path, &key)?
+            }
+            (current, pending) => {
+                match current.map(|key| open_with_key(&db_path, &key)) {
+                    Some(Ok(conn)) => {
+                        let _ = fs::remove_file(&pending_path);
+                        conn
+                    }
+                    // The last rekey finished but the key file wasn't swapped.
+                    _ => {
+                        let key = pending.ok_or(DbError::WrongKey)?;
+                        let conn = open_with_key(&db_path, &key)?;
+                        fs::rename(&pending_path, &key_path)?;
+                        conn
+                    }
+                }
+            }
+        };
+
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+        // Every cache miss is a page decryption; keep the search index warm.
+        conn.pragma_update(None, "cache_size", CACHE_KIB)?;
+        conn.pragma_update(None, "foreign_keys", true)?;
+        migrate(&mut conn)?;
+
+        *open = Some(OpenDatabase {
+            owner: me.pub_key.clone(),
+            conn,
+            seal_key,
+        });
+        Ok(())
+    }
+
+    /// Close the database, e.g. on logout.
+    pub fn lock(&self) {
+        *self.open.lock().unwrap_or_else(|e| e.into_inner()) = None;
+    }
+
+    /// Re-encrypt every page under a fresh key. Returns false if `owner`'s
+    /// database isn't the one currently open.
+    pub fn rekey(&self, owner: &str) -> Result<bool, DbError> {
+        let mut open = self.open.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(db) = open.as_mut().filter(|db| db.owner == owner) else {
+            return Ok(false);
+        };
+        let key_path = self.path_for(owner, KEY_EXT);
+        let pending_path = self.path_for(owner, PENDING_KEY_EXT);
+
+        let key = PageKey::random();
+        sealed::write_json(&pending_path, &db.seal_key, owner.as_bytes(), &key)?;
+
+        // SQLCipher can't rekey in WAL mode.
+        db.conn.pragma_update(None, "journal_mode", "DELETE")?;
+        db.conn.execute_batch(&Zeroizing::new(format!(
+            "PRAGMA rekey = \"x'{}'\";",
+            key.hex
+        )))?;
+        db.conn.pragma_update(None, "journal_mode", "WAL")?;
+
+        fs::rename(&pending_path, &key_path)?;
+        Ok(true)
+    }
+
+    /// Run `f` against the open connection.
+    fn with_conn<T>(
+        &self,
+        f: impl FnOnce(&mut Connection) -> Result<T, DbError>,
+    ) -> Result<T, DbError> {
+        let mut open = self.open.lock().unwrap_or_else(|e| e.into_inner());
+        let db = open.as_mut().ok_or(DbError::Locked)?;
+        f(&mut db.conn)
+    }
+
+    fn path_for(&self, owner: &str, ext: &str) -> PathBuf {
+        self.dir.join(format!("{}.{ext}", sealed::file_stem(owner)))
+    }
+}
+
+impl PageKey {
+    fn random() -> Self {
+        let mut bytes = Zeroizing::new([0u8; 32]);
+        rand::thread_rng().fill_bytes(bytes.as_mut());
+        Self {
+            hex: bytes.iter().map(|b| format!("{b:02x}")).collect(),
+        }
+    }
+}
+
+/// Open and verify; SQLCipher only notices a wrong key on first read.
+fn open_with_key(path: &Path, key: &PageKey) -> Result<Connection, DbError> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let conn = Connection::open(path)?;
+    conn.execute_batch(&Zeroizing::new(format!("PRAGMA key = \"x'{}'\";", key.hex)))?;
+    match conn.query_row("SELECT count(*) FROM sqlite_master", [], |row| {
+        row.get::<_, i64>(0)
+    }) {
+        Ok(_) => Ok(conn),
+        Err(rusqlite::Error::SqliteFailure(e, _))
+            if e.code == rusqlite::ErrorCode::NotADatabase =>
+        {
+            Err(DbError::WrongKey)
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// An unlocked database for a fresh identity, for tests.
+#[cfg(test)]
+pub(crate) fn temp_database() -> (tempfile::TempDir, Database) {
+    let dir = tempfile::tempdir().unwrap();
+    let db = Database::new(dir.path().to_path_buf());
+    db.unlock(&crate::crypto::random_keypair()).unwrap();
+    (dir, db)
+}
+
+fn migrate(conn: &mut Connection) -> Result<(), DbError> {
+    let version: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    for (index, sql) in MIGRATIONS.iter().enumerate().skip(version) {
+        let tx = conn.transaction()?;
+        tx.execute_batch(sql)?;
+        tx.pragma_update(None, "user_version", index + 1)?;
+        tx.commit()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::crypto::random_keypair;
+
+    fn write(db: &Database, id: &str) {
+        db.with_conn(|conn| {
+            conn.execute(
+                "INSERT INTO messages (id, channel_id, timestamp, author_key, type, content)
+                 VALUES (?1, 'c1', 1, 'alice', 'text', 'hello')",
+                [id],
+            )?;
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    fn ids(db: &Database) -> Vec<String> {
+        db.with_conn(|conn| {
+            let mut stmt = conn.prepare("SELECT id FROM messages ORDER BY id")?;
+            let ids = stmt.query_map([], |row| row.get(0))?;
+            Ok(ids.collect::<Result<_, _>>()?)
+        })
+        .unwrap()
+    }
+
+    fn sealed_key(db: &Database, me: &KeyPair, ext: &str) -> Option<PageKey> {
+        let seal_key = sealed::identity_key(me, SEAL_INFO).unwrap();
+        let path = db.path_for(&me.pub_key, ext);
+        sealed::read_json(&path, &seal_key, me.pub_key.as_bytes()).unwrap()
+    }
+
+    fn seal(db: &Database, me: &KeyPair, ext: &str, key: &PageKey) {
+        let seal_key = sealed::identity_key(me, SEAL_INFO).unwrap();
+        let path = db.path_for(&me.pub_key, ext);
+        sealed::write_json(&path, &seal_key, me.pub_key.as_bytes(), key).unwrap();
+    }
+
+    #[test]
+    fn data_survives_a_rekey() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::new(dir.path().to_path_buf());
+        let me = random_keypair();
+        assert!(matches!(db.with_conn(|_| Ok(())), Err(DbError::Locked)));
+
+        db.unlock(&me).unwrap();
+        write(&db, "m1");
+        let before = sealed_key(&db, &me, KEY_EXT).unwrap();
+        assert!(db.rekey(&me.pub_key).unwrap());
+        assert!(!db.rekey(&random_keypair().pub_key).unwrap());
+        let after = sealed_key(&db, &me, KEY_EXT).unwrap();
+        assert_ne!(before.hex, after.hex);
+        assert!(!db.path_for(&me.pub_key, PENDING_KEY_EXT).exists());
+        write(&db, "m2");
+
+        db.lock();
+        assert!(matches!(db.with_conn(|_| Ok(())), Err(DbError::Locked)));
+        db.unlock(&me).unwrap();
+        assert_eq!(ids(&db), ["m1", "m2"]);
+        // The old key no longer opens it.
+        let path = db.path_for(&me.pub_key, DB_EXT);
+        assert!(matches!(
+            open_with_key(&path, &before),
+            Err(DbError::WrongKey)
+        ));
+    }
+
+    #[test]
+    fn wrong_key_is_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::new(dir.path().to_path_buf());
+        let me = random_keypair();
+        db.unlock(&me).unwrap();
+        write(&db, "m1");
+        db.lock();
+
+        seal(&db, &me, KEY_EXT, &PageKey::random());
+        assert!(matches!(db.unlock(&me), Err(DbError::WrongKey)));
+        fs::remove_file(db.path_for(&me.pub_key, KEY_EXT)).unwrap();
+        assert!(matches!(db.unlock(&me), Err(DbError::WrongKey)));
+    }
+
+    #[test]
+    fn leftover_pending_key_is_discarded() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::new(dir.path().to_path_buf());
+        let me = random_keypair();
+        db.unlock(&me).unwrap();
+        write(&db, "m1");
+        db.lock();
+
+        // A rekey that crashed before `PRAGMA rekey` ran.
+        seal(&db, &me, PENDING_KEY_EXT, &PageKey::random());
+        db.unlock(&me).unwrap();
+        assert_eq!(ids(&db), ["m1"]);
+        assert!(!db.path_for(&me.pub_key, PENDING_KEY_EXT).exists());
+    }
+
+    #[test]
+    fn finished_rekey_is_completed_on_unlock() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = Database::new(dir.path().to_path_buf());
+        let me = random_keypair();
+        db.unlock(&me).unwrap();
+        write(&db, "m1");
+        let old = sealed_key(&db, &me, KEY_EXT).unwrap();
+        db.rekey(&me.pub_key).unwrap();
+        let new = sealed_key(&db, &me, KEY_EXT).unwrap();
+        db.lock();
+
+        // A rekey that crashed after `PRAGMA rekey` but before the rename.
+        seal(&db, &me, PENDING_KEY_EXT, &new);
+        seal(&db, &me, KEY_EXT, &old);
+        db.unlock(&me).unwrap();
+        assert_eq!(ids(&db), ["m1"]);
+        assert!(!db.path_for(&me.pub_key, PENDING_KEY_EXT).exists());
+        assert_eq!(sealed_key(&db, &me, KEY_EXT).unwrap().hex, new.hex);
+
+        db.lock();
+        db.unlock(&me).unwrap();
+        assert_eq!(ids(&db), ["m1"]);
+    }
+
+    #[test]
+    fn schema_is_current() {
+        let (_dir, db) = temp_database();
+        let version: usize = db
+            .with_conn(|conn| Ok(conn.pragma_query_value(None, "user_version", |row| row.get(0))?))
+            .unwrap();
+        assert_eq!(version, MIGRATIONS.len());
+    }
+}
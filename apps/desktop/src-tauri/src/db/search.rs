@@ -0,0 +1,42 @@
+//! The search index, stored alongside the message cache so it is encrypted
+//! and scoped to the unlocked identity the same way.
+
+use super::{Database, DbError};
+use crate::search::{self, SearchDocument, SearchFilters, SearchPage, SearchStats};
+
+impl Database {
+    pub fn search_index(&self, documents: &[SearchDocument]) -> Result<usize, DbError> {
+        self.with_conn(|conn| {
+            let tx = conn.transaction()?;
+            let changed = search::index(&tx, documents)?;
+            tx.commit()?;
+            Ok(changed)
+        })
+    }
+
+    pub fn search_remove(&self, ids: &[String]) -> Result<usize, DbError> {
+        self.with_conn(|conn| {
+            let tx = conn.transaction()?;
+            let removed = search::remove(&tx, ids)?;
+            tx.commit()?;
+            Ok(removed)
+        })
+    }
+
+    pub fn search(
+        &self,
+        terms: &[String],
+        filters: &SearchFilters,
+        limit: Option<u32>,
+    ) -> Result<SearchPage, DbError> {
+        self.with_conn(|conn| Ok(search::search(conn, terms, filters, limit)?))
+    }
+
+    pub fn search_stats(&self) -> Result<SearchStats, DbError> {
+        self.with_conn(|conn| Ok(search::stats(conn)?))
+    }
+
+    pub fn search_clear(&self) -> Result<(), DbError> {
+        self.with_conn(|conn| Ok(search::clear(conn)?))
+    }
+}
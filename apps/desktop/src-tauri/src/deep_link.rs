@@ -0,0 +1,503 @@
+//! `nodes://` links: invites, message permalinks and user profiles.
+//!
+//! Links reach the app three ways: as a launch argument on a cold start
+//! (Windows and Linux), in the argv a second instance forwards through the
+//! single-instance plugin, or through the deep-link plugin's open-url event
+//! (macOS). All of them go through [`handle_urls`], which parses each link
+//! and hands the result to the main window as a `deep-link:open` or
+//! `deep-link:error` event, or to the pop-out showing the link's channel.
+//! Until the frontend calls `deep_link_ready`, links are queued instead, so
+//! one that launched the app isn't lost.
+
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+
+use crate::crypto::sea;
+use crate::popout::{self, Popout};
+
+pub const SCHEME: &str = "nodes";
+
+const MAX_LINK_LENGTH: usize = 2048;
+const MAX_ID_LENGTH: usize = 128;
+
+/// A parsed link, serialized as `{ "kind": "invite", ... }`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum DeepLink {
+    /// `nodes://invite/<nodeId>/<inviteKey>`. `code` is the invite string
+    /// `NodeManager.parseInvite` accepts.
+    Invite {
+        code: String,
+        node_id: String,
+        invite_key: String,
+    },
+    /// `nodes://node/<nodeId>/channel/<channelId>/message/<messageId>`.
+    Message {
+        node_id: String,
+        channel_id: String,
+        message_id: String,
+    },
+    /// `nodes://user/<publicKey>`.
+    User { public_key: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, thiserror::Error)]
+#[serde(
+    tag = "code",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum DeepLinkError {
+    #[error("Not a nodes:// link")]
+    WrongScheme,
+    #[error("Link is too long")]
+    TooLong,
+    #[error("Unknown link type \"{route}\"")]
+    UnknownRoute { route: String },
+    #[error("Link is missing the {field}")]
+    Missing { field: &'static str },
+    #[error("Link has an invalid {field}")]
+    Invalid { field: &'static str },
+    #[error("Expected \"{segment}\" in link")]
+    Expected { segment: &'static str },
+    #[error("Link has unexpected extra path segments")]
+    Trailing,
+}
+
+/// Payload of `deep-link:error`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeepLinkFailure {
+    pub url: String,
+    pub message: String,
+    pub error: DeepLinkError,
+}
+
+/// Links received before the frontend was listening.
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingLinks {
+    pub links: Vec<DeepLink>,
+    pub errors: Vec<DeepLinkFailure>,
+}
+
+/// Managed state: the queue, and whether the frontend has drained it.
+#[derive(Default)]
+pub struct DeepLinks(Mutex<Inbox>);
+
+#[derive(Default)]
+struct Inbox {
+    ready: bool,
+    pending: PendingLinks,
+}
+
+impl DeepLinks {
+    /// Mark the frontend as listening and return everything queued so far.
+    pub fn ready(&self) -> PendingLinks {
+        let mut inbox = self.0.lock().unwrap_or_else(|e| e.into_inner());
+        inbox.ready = true;
+        std::mem::take(&mut inbox.pending)
+    }
+}
+
+/// Parse a `nodes://` URL.
+pub fn parse(url: &str) -> Result<DeepLink, DeepLinkError> {
+    let url = url.trim();
+    if url.len() > MAX_LINK_LENGTH {
+        return Err(DeepLinkError::TooLong);
+    }
+    let rest = strip_scheme(url).ok_or(DeepLinkError::WrongScheme)?;
+    // Query strings and fragments carry nothing we use.
+    let path = rest.split(['?', '#']).next().unwrap_or_default();
+    let path = path.strip_suffix('/').unwrap_or(path);
+    let mut segments = path.split('/');
+
+    let route = segment(segments.next(), "link type")?;
+    let link = match route.to_ascii_lowercase().as_str() {
+        "invite" => {
+            let node_id = id(segments.next(), "node ID")?;
+            let invite_key = segment(segments.next(), "invite key")?;
+            if invite_key.len() > MAX_ID_LENGTH
+                || !invite_key
+                    .bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+            {
+                return Err(DeepLinkError::Invalid {
+                    field: "invite key",
+                });
+            }
+            DeepLink::Invite {
+                code: format!("{node_id}/{invite_key}"),
+                node_id,
+                invite_key: invite_key.to_owned(),
+            }
+        }
+        "node" => {
+            let node_id = id(segments.next(), "node ID")?;
+            keyword(segments.next(), "channel")?;
+            let channel_id = id(segments.next(), "channel ID")?;
+            keyword(segments.next(), "message")?;
+            let message_id = id(segments.next(), "message ID")?;
+            DeepLink::Message {
+                node_id,
+                channel_id,
+                message_id,
+            }
+        }
+        "user" => {
+            let public_key = segment(segments.next(), "public key")?;
+            sea::public_key(public_key).map_err(|_| DeepLinkError::Invalid {
+                field: "public key",
+            })?;
+            DeepLink::User {
+                public_key: public_key.to_owned(),
+            }
+        }
+        _ => {
+            return Err(DeepLinkError::UnknownRoute {
+                route: route.to_owned(),
+            })
+        }
+    };
+    if segments.next().is_some() {
+        return Err(DeepLinkError::Trailing);
+    }
+    Ok(link)
+}
+
+/// Parse and deliver every `nodes://` URL in `args`. Other arguments (the
+/// executable path, flags) are skipped.
+pub fn handle_urls<R, I>(app: &AppHandle<R>, args: I)
+where
+    R: Runtime,
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let mut opened = false;
+    for arg in args {
+        let url = arg.as_ref();
+        if strip_scheme(url.trim()).is_none() {
+            continue;
+        }
+        let deep_links = app.state::<DeepLinks>();
+        let mut inbox = deep_links.0.lock().unwrap_or_else(|e| e.into_inner());
+        match parse(url) {
+            Ok(link) => {
+                if inbox.ready {
+                    // A message in a popped-out channel opens in that window.
+                    match popout_for(&link).and_then(|target| popout::focus(app, &target)) {
+                        Some(window) => {
+                            let _ = app.emit_to(window.label(), "deep-link:open", &link);
+                        }
+                        None => {
+                            opened = true;
+                            let _ = app.emit_to(popout::MAIN_LABEL, "deep-link:open", &link);
+                        }
+                    }
+                } else {
+                    opened = true;
+                    inbox.pending.links.push(link);
+                }
+            }
+            Err(error) => {
+                eprintln!("[deep-link] Rejected link: {error}");
+                let failure = DeepLinkFailure {
+                    url: url.to_owned(),
+                    message: error.to_string(),
+                    error,
+                };
+                if inbox.ready {
+                    let _ = app.emit_to(popout::MAIN_LABEL, "deep-link:error", &failure);
+                } else {
+                    inbox.pending.errors.push(failure);
+                }
+            }
+        }
+    }
+
+    if opened {
+        if let Some(window) = app.get_webview_window("main") {
+            let _ = window.show();
+            let _ = window.unminimize();
+            let _ = window.set_focus();
+        }
+    }
+}
+
+/// The pop-out that shows `link`'s target, if it can be popped out.
+fn popout_for(link: &DeepLink) -> Option<Popout> {
+    match link {
+        DeepLink::Message {
+            node_id,
+            channel_id,
+            ..
+        } => Some(Popout::Channel {
+            node_id: node_id.clone(),
+            channel_id: channel_id.clone(),
+        }),
+        DeepLink::Invite { .. } | DeepLink::User { .. } => None,
+    }
+}
+
+/// The part after `nodes://`, matching the scheme case-insensitively.
+fn strip_scheme(url: &str) -> Option<&str> {
+    let (scheme, rest) = url.split_once("://")?;
+    scheme.eq_ignore_ascii_case(SCHEME).then_some(rest)
+}
+
+fn segment<'a>(value: Option<&'a str>, field: &'static str) -> Result<&'a str, DeepLinkError> {
+    value
+        .filter(|v| !v.is_empty())
+        .ok_or(DeepLinkError::Missing { field })
+}
+
+/// Node, channel and message IDs are short ASCII tokens like `node-lx2k-1a2b`.
+fn id(value: Option<&str>, field: &'static str) -> Result<String, DeepLinkError> {
+    let value = segment(value, field)?;
+    let valid = value.len() <= MAX_ID_LENGTH
+        && value
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.'));
+    if !valid {
+        return Err(DeepLinkError::Invalid { field });
+    }
+    Ok(value.to_owned())
+}
+
+fn keyword(value: Option<&str>, expected: &'static str) -> Result<(), DeepLinkError> {
+    match value {
+        Some(v) if v.eq_ignore_ascii_case(expected) => Ok(()),
+        _ => Err(DeepLinkError::Expected { segment: expected }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    const PUBLIC_KEY: &str =
+        "fFgfvZbDli5JUNcbGAPanfkSrZqYosmF67MLWJZPDgI.pFZ20AuDFF8O6Eq1TS6BgdGshxamlvr8Gp-ID-R-0c8";
+
+    fn invite(node_id: &str, invite_key: &str) -> DeepLink {
+        DeepLink::Invite {
+            code: format!("{node_id}/{invite_key}"),
+            node_id: node_id.into(),
+            invite_key: invite_key.into(),
+        }
+    }
+
+    fn message(node_id: &str, channel_id: &str, message_id: &str) -> DeepLink {
+        DeepLink::Message {
+            node_id: node_id.into(),
+            channel_id: channel_id.into(),
+            message_id: message_id.into(),
+        }
+    }
+
+    fn user() -> DeepLink {
+        DeepLink::User {
+            public_key: PUBLIC_KEY.into(),
+        }
+    }
+
+    #[test]
+    fn parses_every_route() {
+        let user_link = format!("nodes://user/{PUBLIC_KEY}");
+        let cases: &[(&str, DeepLink)] = &[
+            (
+                "nodes://invite/node-lx2k-1a2b/Ab_9-z",
+                invite("node-lx2k-1a2b", "Ab_9-z"),
+            ),
+            (
+                "nodes://node/n1/channel/c.1/message/m_1",
+                message("n1", "c.1", "m_1"),
+            ),
+            (&user_link, user()),
+            // Scheme and keywords in any case; IDs keep theirs.
+            ("NODES://Invite/Node1/Key", invite("Node1", "Key")),
+            (
+                "Nodes://NODE/n1/Channel/c1/MESSAGE/m1",
+                message("n1", "c1", "m1"),
+            ),
+            // Query strings, fragments, a trailing slash and surrounding
+            // whitespace are ignored.
+            ("nodes://invite/n1/k1?ref=web#top", invite("n1", "k1")),
+            ("nodes://invite/n1/k1#top?ref=web", invite("n1", "k1")),
+            (
+                "nodes://node/n1/channel/c1/message/m1/",
+                message("n1", "c1", "m1"),
+            ),
+            ("  nodes://invite/n1/k1/?x=1\n", invite("n1", "k1")),
+        ];
+        for (url, expected) in cases {
+            assert_eq!(parse(url).as_ref(), Ok(expected), "{url}");
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_links() {
+        let long_id = "a".repeat(MAX_ID_LENGTH + 1);
+        let too_long = format!("nodes://invite/n1/{}", "a".repeat(MAX_LINK_LENGTH));
+        let long_node = format!("nodes://node/{long_id}/channel/c1/message/m1");
+        let long_key = format!("nodes://invite/n1/{long_id}");
+        let cases: &[(&str, DeepLinkError)] = &[
+            (
+                "https://example.com/invite/n1/k1",
+                DeepLinkError::WrongScheme,
+            ),
+            ("nodes:invite/n1/k1", DeepLinkError::WrongScheme),
+            ("nodesx://invite/n1/k1", DeepLinkError::WrongScheme),
+            (&too_long, DeepLinkError::TooLong),
+            ("nodes://", DeepLinkError::Missing { field: "link type" }),
+            (
+                "nodes://?invite",
+                DeepLinkError::Missing { field: "link type" },
+            ),
+            (
+                "nodes://channel/c1",
+                DeepLinkError::UnknownRoute {
+                    route: "channel".into(),
+                },
+            ),
+            (
+                "nodes://invite",
+                DeepLinkError::Missing { field: "node ID" },
+            ),
+            (
+                "nodes://invite/n1",
+                DeepLinkError::Missing {
+                    field: "invite key",
+                },
+            ),
+            (
+                "nodes://invite//k1",
+                DeepLinkError::Missing { field: "node ID" },
+            ),
+            ("nodes://invite/n1/k1/extra", DeepLinkError::Trailing),
+            ("nodes://invite/n1/k1//", DeepLinkError::Trailing),
+            (
+                "nodes://node/n1/chan/c1/message/m1",
+                DeepLinkError::Expected { segment: "channel" },
+            ),
+            (
+                "nodes://node/n1/channel/c1",
+                DeepLinkError::Expected { segment: "message" },
+            ),
+            (
+                "nodes://node/n1/channel/c1/message",
+                DeepLinkError::Missing {
+                    field: "message ID",
+                },
+            ),
+            (
+                "nodes://node/n1/channel/c1/message/m1/reply",
+                DeepLinkError::Trailing,
+            ),
+            // IDs are limited to letters, digits, `-`, `_` and `.`.
+            (
+                "nodes://node/n%201/channel/c1/message/m1",
+                DeepLinkError::Invalid { field: "node ID" },
+            ),
+            (
+                "nodes://node/n1/channel/c:1/message/m1",
+                DeepLinkError::Invalid {
+                    field: "channel ID",
+                },
+            ),
+            (
+                "nodes://node/n1/channel/c1/message/m\u{e9}",
+                DeepLinkError::Invalid {
+                    field: "message ID",
+                },
+            ),
+            (&long_node, DeepLinkError::Invalid { field: "node ID" }),
+            // Invite keys don't allow `.`.
+            (
+                "nodes://invite/n1/k.1",
+                DeepLinkError::Invalid {
+                    field: "invite key",
+                },
+            ),
+            (
+                &long_key,
+                DeepLinkError::Invalid {
+                    field: "invite key",
+                },
+            ),
+            (
+                "nodes://user",
+                DeepLinkError::Missing {
+                    field: "public key",
+                },
+            ),
+            (
+                "nodes://user/not-a-key",
+                DeepLinkError::Invalid {
+                    field: "public key",
+                },
+            ),
+            (
+                "nodes://user/AAAA.BBBB",
+                DeepLinkError::Invalid {
+                    field: "public key",
+                },
+            ),
+        ];
+        for (url, expected) in cases {
+            assert_eq!(parse(url).as_ref(), Err(expected), "{url}");
+        }
+    }
+
+    #[test]
+    fn serializes_for_the_frontend() {
+        assert_eq!(
+            serde_json::to_value(invite("n1", "k1")).unwrap(),
+            json!({ "kind": "invite", "code": "n1/k1", "nodeId": "n1", "inviteKey": "k1" })
+        );
+        assert_eq!(
+            serde_json::to_value(message("n1", "c1", "m1")).unwrap(),
+            json!({ "kind": "message", "nodeId": "n1", "channelId": "c1", "messageId": "m1" })
+        );
+        assert_eq!(
+            serde_json::to_value(user()).unwrap(),
+            json!({ "kind": "user", "publicKey": PUBLIC_KEY })
+        );
+
+        assert_eq!(
+            serde_json::to_value(DeepLinkError::WrongScheme).unwrap(),
+            json!({ "code": "wrongScheme" })
+        );
+        assert_eq!(
+            serde_json::to_value(DeepLinkError::UnknownRoute { route: "x".into() }).unwrap(),
+            json!({ "code": "unknownRoute", "route": "x" })
+        );
+        assert_eq!(
+            serde_json::to_value(DeepLinkError::Missing { field: "node ID" }).unwrap(),
+            json!({ "code": "missing", "field": "node ID" })
+        );
+        assert_eq!(
+            serde_json::to_value(DeepLinkError::Expected { segment: "channel" }).unwrap(),
+            json!({ "code": "expected", "segment": "channel" })
+        );
+        let failure = DeepLinkFailure {
+            url: "nodes://x".into(),
+            message: DeepLinkError::TooLong.to_string(),
+            error: DeepLinkError::TooLong,
+        };
+        assert_eq!(
+            serde_json::to_value(failure).unwrap(),
+            json!({
+                "url": "nodes://x",
+                "message": "Link is too long",
+                "error": { "code": "tooLong" },
+            })
+        );
+    }
+}
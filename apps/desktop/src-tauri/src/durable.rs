@@ -0,0 +1,68 @@
+//! Crash-safe file writes for the app's own state files.
+//!
+//! [`write_atomic`] writes a `.tmp` sibling, syncs it, renames it over the
+//! target and syncs the directory, so after a crash or power loss a reader
+//! finds either the old contents or the new ones, never a torn file.
+
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Replace `path` with `bytes`, creating its directory if needed.
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let dir = path.parent().unwrap_or(Path::new("."));
+    fs::create_dir_all(dir)?;
+    let mut temp = path.to_path_buf();
+    temp.as_mut_os_string().push(".tmp");
+    let written = write_synced(&temp, bytes).and_then(|()| fs::rename(&temp, path));
+    if written.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    written?;
+    sync_dir(dir);
+    Ok(())
+}
+
+/// Write `bytes` to `path` and wait for them to reach the disk.
+fn write_synced(path: &Path, bytes: &[u8]) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    file.write_all(bytes)?;
+    file.sync_all()
+}
+
+/// Best-effort fsync of the directory so a rename in it is durable.
+fn sync_dir(dir: &Path) {
+    #[cfg(unix)]
+    if let Ok(d) = File::open(dir) {
+        let _ = d.sync_all();
+    }
+    #[cfg(not(unix))]
+    let _ = dir;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_the_file_and_leaves_no_temp() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nested/state.json");
+        write_atomic(&path, b"one").unwrap();
+        write_atomic(&path, b"two").unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"two");
+        assert!(!dir.path().join("nested/state.json.tmp").exists());
+    }
+
+    #[test]
+    fn a_failed_rename_keeps_the_old_file() {
+        let dir = tempfile::tempdir().unwrap();
+        // Renaming a file over a non-empty directory fails everywhere.
+        let path = dir.path().join("state");
+        fs::create_dir(&path).unwrap();
+        fs::write(path.join("keep"), b"old").unwrap();
+        assert!(write_atomic(&path, b"new").is_err());
+        assert_eq!(fs::read(path.join("keep")).unwrap(), b"old");
+        assert!(!dir.path().join("state.tmp").exists());
+    }
+}
@@ -0,0 +1,204 @@
+//! Global hotkeys: push-to-talk, toggle mute, toggle deafen and show/hide
+//! window, working while the app is in the background.
+//!
+//! Bindings are accelerator strings such as `CommandOrControl+Shift+M`,
+//! stored with the other settings. Whenever they change, every hotkey is
+//! registered again. A binding that can't be used (it doesn't parse, another
+//! action already has it, or another app holds it) is skipped and reported
+//! as a [`Conflict`]; the current list is emitted as `hotkeys:conflicts` and
+//! available from `hotkeys_conflicts`.
+//!
+//! Voice actions are left to the frontend: push-to-talk emits
+//! `hotkeys:push-to-talk` with `{ pressed }` on press and release, and the
+//! toggles emit `hotkeys:toggle-mute` and `hotkeys:toggle-deafen`. Show/hide
+//! is handled here.
+
+use std::sync::{Mutex, MutexGuard};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutEvent, ShortcutState};
+
+/// Accelerators per action; `None` leaves the action unbound.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct HotkeyBindings {
+    pub push_to_talk: Option<String>,
+    pub toggle_mute: Option<String>,
+    pub toggle_deafen: Option<String>,
+    pub toggle_window: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum HotkeyAction {
+    PushToTalk,
+    ToggleMute,
+    ToggleDeafen,
+    ToggleWindow,
+}
+
+/// A binding that isn't registered.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum Conflict {
+    /// The accelerator doesn't parse.
+    Invalid {
+        action: HotkeyAction,
+        accelerator: String,
+        message: String,
+    },
+    /// An earlier action in the list already uses the same keys.
+    Duplicate {
+        action: HotkeyAction,
+        accelerator: String,
+        other: HotkeyAction,
+    },
+    /// The OS refused it, usually because another app holds it.
+    Unavailable {
+        action: HotkeyAction,
+        accelerator: String,
+        message: String,
+    },
+}
+
+impl HotkeyBindings {
+    fn iter(&self) -> impl Iterator<Item = (HotkeyAction, &str)> {
+        [
+            (HotkeyAction::PushToTalk, &self.push_to_talk),
+            (HotkeyAction::ToggleMute, &self.toggle_mute),
+            (HotkeyAction::ToggleDeafen, &self.toggle_deafen),
+            (HotkeyAction::ToggleWindow, &self.toggle_window),
+        ]
+        .into_iter()
+        .filter_map(|(action, accelerator)| {
+            let accelerator = accelerator.as_deref()?.trim();
+            (!accelerator.is_empty()).then_some((action, accelerator))
+        })
+    }
+}
+
+/// Registered hotkeys and the bindings that couldn't be. One instance is
+/// managed by Tauri.
+#[derive(Default)]
+pub struct Hotkeys {
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    registered: Vec<(Shortcut, HotkeyAction)>,
+    conflicts: Vec<Conflict>,
+    /// Key repeat sends more presses; only the first one counts.
+    push_to_talk_down: bool,
+}
+
+impl Hotkeys {
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        self.lock().conflicts.clone()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Replace the registered hotkeys with `bindings` and publish the conflicts.
+pub fn apply<R: Runtime>(app: &AppHandle<R>, bindings: &HotkeyBindings) {
+    let hotkeys = app.state::<Hotkeys>();
+    let global = app.global_shortcut();
+    // The plugin calls `on_shortcut` with its own lock held, so don't hold
+    // ours while calling into it.
+    let previous = std::mem::take(&mut hotkeys.lock().registered);
+    for (shortcut, _) in previous {
+        if let Err(e) = global.unregister(shortcut) {
+            eprintln!("[hotkeys] Failed to unregister {shortcut}: {e}");
+        }
+    }
+
+    let mut registered: Vec<(Shortcut, HotkeyAction)> = Vec::new();
+    let mut conflicts = Vec::new();
+    for (action, accelerator) in bindings.iter() {
+        let accelerator = accelerator.to_string();
+        let shortcut = match accelerator.parse::<Shortcut>() {
+            Ok(shortcut) => shortcut,
+            Err(e) => {
+                conflicts.push(Conflict::Invalid {
+                    action,
+                    accelerator,
+                    message: e.to_string(),
+                });
+                continue;
+            }
+        };
+        if let Some(&(_, other)) = registered.iter().find(|(s, _)| *s == shortcut) {
+            conflicts.push(Conflict::Duplicate {
+                action,
+                accelerator,
+                other,
+            });
+            continue;
+        }
+        match global.register(shortcut) {
+            Ok(()) => registered.push((shortcut, action)),
+            Err(e) => conflicts.push(Conflict::Unavailable {
+                action,
+                accelerator,
+                message: e.to_string(),
+            }),
+        }
+    }
+    for conflict in &conflicts {
+        eprintln!("[hotkeys] Not registered: {conflict:?}");
+    }
+
+    let mut state = hotkeys.lock();
+    state.registered = registered;
+    state.conflicts = conflicts.clone();
+    state.push_to_talk_down = false;
+    drop(state);
+    let _ = app.emit("hotkeys:conflicts", conflicts);
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct PushToTalk {
+    pressed: bool,
+}
+
+/// Handler for the global shortcut plugin.
+pub fn on_shortcut<R: Runtime>(app: &AppHandle<R>, shortcut: &Shortcut, event: ShortcutEvent) {
+    let hotkeys = app.state::<Hotkeys>();
+    let mut state = hotkeys.lock();
+    let Some(&(_, action)) = state.registered.iter().find(|(s, _)| s == shortcut) else {
+        return;
+    };
+    let pressed = event.state == ShortcutState::Pressed;
+    match action {
+        HotkeyAction::PushToTalk if state.push_to_talk_down != pressed => {
+            state.push_to_talk_down = pressed;
+            let _ = app.emit("hotkeys:push-to-talk", PushToTalk { pressed });
+        }
+        HotkeyAction::ToggleMute if pressed => {
+            let _ = app.emit("hotkeys:toggle-mute", ());
+        }
+        HotkeyAction::ToggleDeafen if pressed => {
+            let _ = app.emit("hotkeys:toggle-deafen", ());
+        }
+        HotkeyAction::ToggleWindow if pressed => toggle_main_window(app),
+        _ => {}
+    }
+}
+
+fn toggle_main_window<R: Runtime>(app: &AppHandle<R>) {
+    let Some(window) = app.get_webview_window("main") else {
+        return;
+    };
+    let focused = window.is_focused().unwrap_or(false);
+    if window.is_visible().unwrap_or(false) && focused {
+        let _ = window.hide();
+    } else {
+        let _ = window.show();
+        let _ = window.unminimize();
+        let _ = window.set_focus();
+    }
+}
@@ -0,0 +1,286 @@
+//! Idle time, screen lock and suspend on Linux, over D-Bus.
+//!
+//! Input idle time comes from Mutter's `IdleMonitor` on GNOME and from
+//! `org.freedesktop.ScreenSaver.GetSessionIdleTime` elsewhere (KDE and most
+//! other desktops). Lock state comes from logind's `LockedHint` on the
+//! system bus, which GNOME and KDE keep up to date, and from the session's
+//! `ScreenSaver.ActiveChanged` for lockers that only speak that. Suspend and
+//! resume come from logind's `PrepareForSleep`. Any service that's missing
+//! is logged and skipped.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::{stream, Stream, StreamExt};
+use zbus::{proxy, Connection};
+
+use super::{Change, Monitor};
+
+#[proxy(
+    interface = "org.gnome.Mutter.IdleMonitor",
+    default_service = "org.gnome.Mutter.IdleMonitor",
+    default_path = "/org/gnome/Mutter/IdleMonitor/Core"
+)]
+trait MutterIdleMonitor {
+    /// Milliseconds since the last input.
+    fn get_idletime(&self) -> zbus::Result<u64>;
+}
+
+#[proxy(
+    interface = "org.freedesktop.ScreenSaver",
+    default_service = "org.freedesktop.ScreenSaver",
+    default_path = "/org/freedesktop/ScreenSaver"
+)]
+trait ScreenSaver {
+    /// Milliseconds since the last input.
+    fn get_session_idle_time(&self) -> zbus::Result<u32>;
+
+    #[zbus(signal)]
+    fn active_changed(&self, active: bool) -> zbus::Result<()>;
+}
+
+#[proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1"
+)]
+trait LoginManager {
+    fn get_session(&self, session_id: &str) -> zbus::Result<zbus::zvariant::OwnedObjectPath>;
+
+    /// `start` is true before suspending and false after resuming.
+    #[zbus(signal)]
+    fn prepare_for_sleep(&self, start: bool) -> zbus::Result<()>;
+}
+
+#[proxy(
+    interface = "org.freedesktop.login1.Session",
+    default_service = "org.freedesktop.login1"
+)]
+trait LoginSession {
+    #[zbus(property)]
+    fn locked_hint(&self) -> zbus::Result<bool>;
+}
+
+/// Reads input idle time from the session bus.
+pub struct IdleClock {
+    session: Option<Connection>,
+    warned: AtomicBool,
+}
+
+impl IdleClock {
+    pub fn new() -> Self {
+        let session = tauri::async_runtime::block_on(Connection::session())
+            .map_err(|e| eprintln!("[idle] Failed to connect to the session bus: {e}"))
+            .ok();
+        Self {
+            session,
+            warned: AtomicBool::new(false),
+        }
+    }
+
+    pub fn idle_time(&self) -> Option<Duration> {
+        let session = self.session.as_ref()?;
+        let idle = tauri::async_runtime::block_on(idle_time(session));
+        if idle.is_none() && !self.warned.swap(true, Ordering::Relaxed) {
+            eprintln!(
+                "[idle] No idle time service on the session bus; only lock and suspend are tracked"
+            );
+        }
+        idle
+    }
+}
+
+/// Time since the last input, from whichever service answers.
+pub async fn idle_time(session: &Connection) -> Option<Duration> {
+    if let Ok(mutter) = MutterIdleMonitorProxy::new(session).await {
+        if let Ok(ms) = mutter.get_idletime().await {
+            return Some(Duration::from_millis(ms));
+        }
+    }
+    let screensaver = ScreenSaverProxy::new(session).await.ok()?;
+    let ms = screensaver.get_session_idle_time().await.ok()?;
+    Some(Duration::from_millis(ms.into()))
+}
+
+/// Connect to both buses and report lock and suspend changes until they
+/// disconnect.
+pub async fn watch_session(monitor: Arc<Monitor>) {
+    let session = Connection::session()
+        .await
+        .map_err(|e| eprintln!("[idle] Failed to connect to the session bus: {e}"))
+        .ok();
+    let system = Connection::system()
+        .await
+        .map_err(|e| eprintln!("[idle] Failed to connect to the system bus: {e}"))
+        .ok();
+    watch(session.as_ref(), system.as_ref(), &monitor).await;
+}
+
+pub async fn watch(session: Option<&Connection>, system: Option<&Connection>, monitor: &Monitor) {
+    let log = |source: &str, result: zbus::Result<()>| {
+        if let Err(e) = result {
+            eprintln!("[idle] Not watching {source}: {e}");
+        }
+    };
+    let locked = async {
+        if let Some(system) = system {
+            log("screen lock", watch_locked_hint(system, monitor).await);
+        }
+    };
+    let sleep = async {
+        if let Some(system) = system {
+            log("suspend", watch_sleep(system, monitor).await);
+        }
+    };
+    let screensaver = async {
+        if let Some(session) = session {
+            log("screensaver", watch_screensaver(session, monitor).await);
+        }
+    };
+    futures_util::join!(locked, sleep, screensaver);
+}
+
+async fn watch_locked_hint(system: &Connection, monitor: &Monitor) -> zbus::Result<()> {
+    let path = LoginManagerProxy::new(system)
+        .await?
+        .get_session("auto")
+        .await?;
+    let session = LoginSessionProxy::builder(system)
+        .path(path)?
+        .build()
+        .await?;
+    let changes = session
+        .receive_locked_hint_changed()
+        .await
+        .then(|change| async move { change.get().await });
+    let locked = session.locked_hint().await;
+    forward(
+        stream::once(async { locked }).chain(changes),
+        Change::Locked,
+        monitor,
+    )
+    .await
+}
+
+async fn watch_sleep(system: &Connection, monitor: &Monitor) -> zbus::Result<()> {
+    let signals = LoginManagerProxy::new(system)
+        .await?
+        .receive_prepare_for_sleep()
+        .await?;
+    let sleeping = signals.map(|signal| signal.args().map(|args| args.start));
+    forward(sleeping, Change::Suspended, monitor).await
+}
+
+async fn watch_screensaver(session: &Connection, monitor: &Monitor) -> zbus::Result<()> {
+    let signals = ScreenSaverProxy::new(session)
+        .await?
+        .receive_active_changed()
+        .await?;
+    let active = signals.map(|signal| signal.args().map(|args| args.active));
+    forward(active, Change::ScreenSaver, monitor).await
+}
+
+/// Report each state as a `change` until the stream ends or fails. Kept
+/// apart from the D-Bus plumbing so transitions can be driven directly.
+async fn forward(
+    states: impl Stream<Item = zbus::Result<bool>>,
+    change: fn(bool) -> Change,
+    monitor: &Monitor,
+) -> zbus::Result<()> {
+    let mut states = std::pin::pin!(states);
+    while let Some(state) = states.next().await {
+        monitor.report(change(state?));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::idle::{IdleConfig, IdleReason, Transition};
+
+    /// A monitor that records its transitions, with `on_lock` as given.
+    fn monitor(on_lock: bool) -> (Monitor, Arc<Mutex<Vec<Transition>>>) {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let record = seen.clone();
+        let monitor = Monitor::new(
+            move || IdleConfig {
+                after: Some(Duration::from_secs(300)),
+                on_lock,
+            },
+            move |transition| record.lock().unwrap().push(transition),
+        );
+        (monitor, seen)
+    }
+
+    fn drive(states: &[bool], change: fn(bool) -> Change, monitor: &Monitor) {
+        let states = stream::iter(states.iter().copied().map(Ok));
+        tauri::async_runtime::block_on(forward(states, change, monitor)).unwrap();
+    }
+
+    fn take(seen: &Mutex<Vec<Transition>>) -> Vec<Transition> {
+        std::mem::take(&mut seen.lock().unwrap())
+    }
+
+    #[test]
+    fn lock_and_unlock() {
+        let (monitor, seen) = monitor(true);
+        // logind's current value comes first, then each change.
+        drive(&[false, true, true, false], Change::Locked, &monitor);
+        assert_eq!(
+            take(&seen),
+            [Transition::Idle(IdleReason::Locked), Transition::Active]
+        );
+
+        drive(&[true, false], Change::ScreenSaver, &monitor);
+        assert_eq!(
+            take(&seen),
+            [Transition::Idle(IdleReason::Locked), Transition::Active]
+        );
+    }
+
+    #[test]
+    fn suspend_and_resume() {
+        let (monitor, seen) = monitor(true);
+        drive(&[true, false], Change::Suspended, &monitor);
+        assert_eq!(
+            take(&seen),
+            [Transition::Idle(IdleReason::Suspended), Transition::Active]
+        );
+    }
+
+    #[test]
+    fn resuming_to_a_locked_screen_stays_idle() {
+        let (monitor, seen) = monitor(true);
+        drive(&[true], Change::Locked, &monitor);
+        drive(&[true, false], Change::Suspended, &monitor);
+        assert_eq!(take(&seen), [Transition::Idle(IdleReason::Locked)]);
+
+        drive(&[false], Change::Locked, &monitor);
+        assert_eq!(take(&seen), [Transition::Active]);
+    }
+
+    #[test]
+    fn lock_and_suspend_can_be_ignored() {
+        let (monitor, seen) = monitor(false);
+        drive(&[true, false], Change::Locked, &monitor);
+        drive(&[true, false], Change::Suspended, &monitor);
+        assert_eq!(take(&seen), []);
+    }
+
+    #[test]
+    fn stops_at_the_first_error() {
+        let (monitor, seen) = monitor(true);
+        let states = stream::iter([
+            Ok(true),
+            Err(zbus::Error::Failure("disconnected".into())),
+            Ok(false),
+        ]);
+        let result = tauri::async_runtime::block_on(forward(states, Change::Locked, &monitor));
+        assert!(result.is_err());
+        assert_eq!(take(&seen), [Transition::Idle(IdleReason::Locked)]);
+    }
+}
@@ -0,0 +1,29 @@
+//! Input idle time on macOS, from the combined session event source.
+
+use std::time::Duration;
+
+/// `kCGEventSourceStateCombinedSessionState`
+const COMBINED_SESSION_STATE: i32 = 0;
+/// `kCGAnyInputEventType`
+const ANY_INPUT_EVENT: u32 = u32::MAX;
+
+#[link(name = "CoreGraphics", kind = "framework")]
+extern "C" {
+    fn CGEventSourceSecondsSinceLastEventType(state: i32, event_type: u32) -> f64;
+}
+
+pub struct IdleClock;
+
+impl IdleClock {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub fn idle_time(&self) -> Option<Duration> {
+        // SAFETY: takes and returns plain values.
+        let seconds = unsafe {
+            CGEventSourceSecondsSinceLastEventType(COMBINED_SESSION_STATE, ANY_INPUT_EVENT)
+        };
+        Duration::try_from_secs_f64(seconds).ok()
+    }
+}
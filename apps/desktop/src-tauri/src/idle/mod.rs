@@ -0,0 +1,195 @@
+//! Automatic idle detection for presence.
+//!
+//! A background thread polls how long the system has gone without keyboard
+//! or mouse input. On Linux the monitor also follows the session over D-Bus:
+//! logind's `LockedHint` and the `org.freedesktop.ScreenSaver` active state
+//! for screen lock, and logind's `PrepareForSleep` for suspend and resume
+//! (see [`linux`]).
+//!
+//! The first cause to kick in emits `presence:auto-idle` with the reason;
+//! once none apply anymore `presence:active` is emitted. It's up to the
+//! frontend to leave a status the user picked by hand alone. Thresholds come
+//! from the settings and are read on every change, so edits apply right
+//! away.
+
+#[cfg(target_os = "linux")]
+mod linux;
+#[cfg(target_os = "macos")]
+mod macos;
+#[cfg(windows)]
+mod windows;
+
+#[cfg(target_os = "linux")]
+use linux as platform;
+#[cfg(target_os = "macos")]
+use macos as platform;
+#[cfg(windows)]
+use windows as platform;
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+
+use crate::settings::{AppSettings, Settings};
+
+/// How often input idle time is sampled.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Why the user was marked idle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum IdleReason {
+    /// No input for longer than the threshold.
+    Inactive,
+    /// The screen is locked or the screensaver is running.
+    Locked,
+    /// The system is going to sleep.
+    Suspended,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Transition {
+    Idle(IdleReason),
+    Active,
+}
+
+/// Something the monitor observed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    /// Time since the last input.
+    Input(Duration),
+    /// logind's idea of whether the session is locked.
+    Locked(bool),
+    ScreenSaver(bool),
+    Suspended(bool),
+}
+
+/// Thresholds, taken from [`AppSettings`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdleConfig {
+    /// Input idle time after which the user counts as away, if any.
+    pub after: Option<Duration>,
+    /// Whether locking the screen or suspending counts as away.
+    pub on_lock: bool,
+}
+
+impl From<&AppSettings> for IdleConfig {
+    fn from(settings: &AppSettings) -> Self {
+        Self {
+            after: (settings.auto_idle_after_secs > 0)
+                .then(|| Duration::from_secs(settings.auto_idle_after_secs)),
+            on_lock: settings.auto_idle_on_lock,
+        }
+    }
+}
+
+/// Current causes and whether idle was last reported.
+#[derive(Debug, Default)]
+struct Tracker {
+    idle_for: Duration,
+    locked: bool,
+    screensaver: bool,
+    suspended: bool,
+    idle: bool,
+}
+
+impl Tracker {
+    fn apply(&mut self, change: Change, config: IdleConfig) -> Option<Transition> {
+        match change {
+            Change::Input(idle_for) => self.idle_for = idle_for,
+            Change::Locked(locked) => self.locked = locked,
+            Change::ScreenSaver(active) => self.screensaver = active,
+            Change::Suspended(suspended) => self.suspended = suspended,
+        }
+        let reason = if config.on_lock && self.suspended {
+            Some(IdleReason::Suspended)
+        } else if config.on_lock && (self.locked || self.screensaver) {
+            Some(IdleReason::Locked)
+        } else if config.after.is_some_and(|after| self.idle_for >= after) {
+            Some(IdleReason::Inactive)
+        } else {
+            None
+        };
+        match (self.idle, reason) {
+            (false, Some(reason)) => {
+                self.idle = true;
+                Some(Transition::Idle(reason))
+            }
+            (true, None) => {
+                self.idle = false;
+                Some(Transition::Active)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Collects changes from every source and reports transitions.
+pub struct Monitor {
+    tracker: Mutex<Tracker>,
+    config: Box<dyn Fn() -> IdleConfig + Send + Sync>,
+    on_transition: Box<dyn Fn(Transition) + Send + Sync>,
+}
+
+impl Monitor {
+    pub fn new(
+        config: impl Fn() -> IdleConfig + Send + Sync + 'static,
+        on_transition: impl Fn(Transition) + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            tracker: Mutex::new(Tracker::default()),
+            config: Box::new(config),
+            on_transition: Box::new(on_transition),
+        }
+    }
+
+    pub fn report(&self, change: Change) {
+        let config = (self.config)();
+        let transition = self
+            .tracker
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .apply(change, config);
+        if let Some(transition) = transition {
+            (self.on_transition)(transition);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AutoIdle {
+    reason: IdleReason,
+}
+
+/// Start watching for idle, lock and suspend.
+pub fn start<R: Runtime>(app: &AppHandle<R>) {
+    let settings_app = app.clone();
+    let emit_app = app.clone();
+    let monitor = Arc::new(Monitor::new(
+        move || IdleConfig::from(&settings_app.state::<Settings>().get()),
+        move |transition| {
+            let _ = match transition {
+                Transition::Idle(reason) => {
+                    emit_app.emit("presence:auto-idle", AutoIdle { reason })
+                }
+                Transition::Active => emit_app.emit("presence:active", ()),
+            };
+        },
+    ));
+
+    #[cfg(target_os = "linux")]
+    tauri::async_runtime::spawn(linux::watch_session(monitor.clone()));
+
+    std::thread::spawn(move || {
+        let clock = platform::IdleClock::new();
+        loop {
+            if let Some(idle_for) = clock.idle_time() {
+                monitor.report(Change::Input(idle_for));
+            }
+            std::thread::sleep(POLL_INTERVAL);
+        }
+    });
+}
@@ -0,0 +1,43 @@
+//! Input idle time on Windows, from `GetLastInputInfo`.
+
+use std::time::Duration;
+
+#[repr(C)]
+struct LastInputInfo {
+    size: u32,
+    /// Tick count of the last input event.
+    time: u32,
+}
+
+#[link(name = "user32")]
+extern "system" {
+    fn GetLastInputInfo(info: *mut LastInputInfo) -> i32;
+}
+
+#[link(name = "kernel32")]
+extern "system" {
+    fn GetTickCount() -> u32;
+}
+
+pub struct IdleClock;
+
+impl IdleClock {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub fn idle_time(&self) -> Option<Duration> {
+        let mut info = LastInputInfo {
+            size: std::mem::size_of::<LastInputInfo>() as u32,
+            time: 0,
+        };
+        // SAFETY: `info` is a valid LASTINPUTINFO with its size filled in.
+        if unsafe { GetLastInputInfo(&mut info) } == 0 {
+            return None;
+        }
+        // SAFETY: no preconditions.
+        let now = unsafe { GetTickCount() };
+        // Both are 32-bit tick counts that wrap every 49.7 days.
+        Some(Duration::from_millis(now.wrapping_sub(info.time).into()))
+    }
+}
@@ -0,0 +1,90 @@
+//! On-disk storage for private-channel sender keys.
+//!
+//! Stored under `groups/` next to the keystores, one file per
+//! (identity, channel) pair and sealed to the owner's identity like the DM
+//! sessions in [`super::sessions`].
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use super::sealed;
+use crate::crypto::group::{GroupError, GroupState};
+use crate::crypto::KeyPair;
+
+const FILE_EXT: &str = "group";
+const SEAL_INFO: &[u8] = b"nodes/group-key/store";
+
+#[derive(Debug, thiserror::Error)]
+pub enum GroupStoreError {
+    #[error(transparent)]
+    Group(#[from] GroupError),
+    #[error("Group key file is corrupt")]
+    Corrupt,
+    #[error("Group key I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Sender-key store. One instance is managed by Tauri.
+pub struct GroupKeyStore {
+    dir: PathBuf,
+    // Chains advance on every message; keep read-modify-write serialized.
+    lock: Mutex<()>,
+}
+
+impl GroupKeyStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Run `f` against our state for a channel, creating it on first use,
+    /// and persist the result. Nothing is saved if `f` fails.
+    pub fn with_channel<T>(
+        &self,
+        me: &KeyPair,
+        channel_id: &str,
+        f: impl FnOnce(&mut GroupState) -> Result<T, GroupError>,
+    ) -> Result<T, GroupStoreError> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let key = sealed::identity_key(me, SEAL_INFO).map_err(GroupError::from)?;
+        let path = self.path_for(&me.pub_key, channel_id);
+        let aad = associated_data(&me.pub_key, channel_id);
+
+        let mut state: GroupState = sealed::read_json(&path, &key, &aad)
+            .map_err(corrupt)?
+            .unwrap_or_else(|| GroupState::new(channel_id));
+        let result = f(&mut state)?;
+
+        sealed::write_json(&path, &key, &aad, &state)?;
+        Ok(result)
+    }
+
+    /// Drop all sender keys for a channel, e.g. after leaving it.
+    pub fn forget(&self, my_pub: &str, channel_id: &str) -> Result<bool, GroupStoreError> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        match fs::remove_file(self.path_for(my_pub, channel_id)) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn path_for(&self, my_pub: &str, channel_id: &str) -> PathBuf {
+        let stem = sealed::file_stem(&format!("{my_pub}#{channel_id}"));
+        self.dir.join(format!("{stem}.{FILE_EXT}"))
+    }
+}
+
+fn associated_data(my_pub: &str, channel_id: &str) -> Vec<u8> {
+    format!("{my_pub}#{channel_id}").into_bytes()
+}
+
+fn corrupt(e: std::io::Error) -> GroupStoreError {
+    match e.kind() {
+        std::io::ErrorKind::InvalidData => GroupStoreError::Corrupt,
+        _ => GroupStoreError::Io(e),
+    }
+}
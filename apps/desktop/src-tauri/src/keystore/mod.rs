@@ -0,0 +1,509 @@
+//! File-backed storage for encrypted identity keystores.
+//!
+//! Each identity gets its own file in the app data directory so that clearing
+//! webview storage (or a crash-recovery reset) can no longer destroy it.
+//! Writes are atomic (temp file + rename) and the previous generation is kept
+//! as a `.bak` copy that `load` falls back to if the primary file is damaged.
+//!
+//! Passphrase handling lives in [`wrap`]; `unlock` upgrades older keystore
+//! versions to the current format after a successful decrypt. DM ratchet
+//! sessions and channel sender keys are stored alongside, see [`sessions`]
+//! and [`groups`].
+
+pub mod groups;
+pub mod sealed;
+pub mod sessions;
+pub mod wrap;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+use crate::crypto::KeyPair;
+use crate::durable;
+use wrap::{KdfParams, KdfSpec, WrapError};
+
+/// Version of the on-disk envelope wrapping each keystore.
+const FILE_FORMAT: u32 = 1;
+
+const FILE_EXT: &str = "json";
+const BACKUP_EXT: &str = "json.bak";
+const TEMP_EXT: &str = "json.tmp";
+const BACKUP_TEMP_EXT: &str = "json.bak.tmp";
+
+/// Marker written once the legacy localStorage keystore has been imported.
+const MIGRATION_MARKER: &str = ".legacy-migrated";
+
+/// Encrypted keystore. Version 1 is produced by `KeyManager.saveToLocalStore`;
+/// version 2 additionally records its KDF and nonce (see [`wrap`]).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EncryptedKeystore {
+    pub version: u32,
+    pub encrypted: String,
+    #[serde(rename = "pub")]
+    pub pub_key: String,
+    pub created_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kdf: Option<KdfSpec>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nonce: Option<String>,
+}
+
+/// On-disk envelope around a keystore.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct KeystoreFile {
+    format: u32,
+    generation: u64,
+    saved_at: u64,
+    keystore: EncryptedKeystore,
+}
+
+/// Metadata returned by `list` — never includes the encrypted payload.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeystoreSummary {
+    #[serde(rename = "pub")]
+    pub pub_key: String,
+    pub version: u32,
+    pub generation: u64,
+    pub created_at: u64,
+    pub saved_at: u64,
+}
+
+/// Outcome of importing the legacy localStorage keystore.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MigrationOutcome {
+    /// The keystore was written to the file store.
+    Migrated,
+    /// A file for this identity already existed; nothing was overwritten.
+    AlreadyPresent,
+    /// There was no legacy keystore to import.
+    NothingToMigrate,
+    /// Migration has already run on this install.
+    AlreadyMigrated,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum KeystoreError {
+    #[error("No keystore found for this identity")]
+    NotFound,
+    #[error("Keystore file is corrupt: {0}")]
+    Corrupt(String),
+    #[error("Keystore public key does not match the requested identity")]
+    Mismatch,
+    #[error(transparent)]
+    Wrap(#[from] WrapError),
+    #[error("Keystore I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Directory-backed keystore store. One instance is managed by Tauri.
+pub struct Keystore {
+    dir: PathBuf,
+    // Serializes writers so generations and backups stay consistent.
+    lock: Mutex<()>,
+}
+
+impl Keystore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Persist a keystore, rotating the current file into the backup slot.
+    pub fn save(&self, keystore: &EncryptedKeystore) -> Result<KeystoreSummary, KeystoreError> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        fs::create_dir_all(&self.dir)?;
+
+        let primary = self.path_for(&keystore.pub_key, FILE_EXT);
+        let backup = self.path_for(&keystore.pub_key, BACKUP_EXT);
+
+        let previous = read_file(&primary).ok();
+        let file = KeystoreFile {
+            format: FILE_FORMAT,
+            generation: previous.as_ref().map_or(1, |p| p.generation + 1),
+            saved_at: now_millis(),
+            keystore: keystore.clone(),
+        };
+
+        // Only rotate a readable primary into the backup slot, so a damaged
+        // file never replaces the last good generation. Both writes are
+        // atomic, so a crash between them leaves two good generations.
+        if let Some(previous) = &previous {
+            durable::write_atomic(&backup, &to_json(previous)?)?;
+        }
+        durable::write_atomic(&primary, &to_json(&file)?)?;
+
+        Ok(summarize(&file))
+    }
+
+    /// Load the keystore for `pub_key`, falling back to the backup copy.
+    pub fn load(&self, pub_key: &str) -> Result<EncryptedKeystore, KeystoreError> {
+        let primary = self.path_for(pub_key, FILE_EXT);
+        let backup = self.path_for(pub_key, BACKUP_EXT);
+
+        let file = match read_file(&primary) {
+            Ok(file) => file,
+            Err(KeystoreError::NotFound) if !backup.exists() => {
+                return Err(KeystoreError::NotFound)
+            }
+            Err(primary_err) => read_file(&backup).map_err(|_| primary_err)?,
+        };
+
+        if file.keystore.pub_key != pub_key {
+            return Err(KeystoreError::Mismatch);
+        }
+        Ok(file.keystore)
+    }
+
+    /// List every stored identity, newest save first.
+    pub fn list(&self) -> Result<Vec<KeystoreSummary>, KeystoreError> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut summaries = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(FILE_EXT) {
+                continue;
+            }
+            // Skip unreadable files rather than hiding every other identity.
+            if let Ok(file) = read_file(&path) {
+                summaries.push(summarize(&file));
+            }
+        }
+        summaries.sort_by_key(|s| std::cmp::Reverse(s.saved_at));
+        Ok(summaries)
+    }
+
+    /// Remove the keystore and its backup. Returns false if nothing existed.
+    pub fn delete(&self, pub_key: &str) -> Result<bool, KeystoreError> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut removed = false;
+        for ext in [FILE_EXT, BACKUP_EXT, TEMP_EXT, BACKUP_TEMP_EXT] {
+            match fs::remove_file(self.path_for(pub_key, ext)) {
+                Ok(()) => removed = true,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Wrap a keypair with the current format and persist it.
+    pub fn create(
+        &self,
+        keypair: &KeyPair,
+        passphrase: &str,
+        params: KdfParams,
+    ) -> Result<KeystoreSummary, KeystoreError> {
+        let keystore = wrap::wrap(keypair, passphrase, params, now_millis())?;
+        self.save(&keystore)
+    }
+
+    /// Decrypt the keystore for `pub_key`.
+    ///
+    /// Keystores older than [`wrap::CURRENT_VERSION`] are re-wrapped with
+    /// default parameters. A failed upgrade doesn't fail the unlock; the old
+    /// file stays valid and the upgrade is retried next time.
+    pub fn unlock(&self, pub_key: &str, passphrase: &str) -> Result<KeyPair, KeystoreError> {
+        let keystore = self.load(pub_key)?;
+        let keypair = wrap::unwrap(&keystore, passphrase)?;
+
+        if keystore.version < wrap::CURRENT_VERSION {
+            let upgraded = wrap::wrap(
+                &keypair,
+                passphrase,
+                KdfParams::default(),
+                keystore.created_at,
+            )
+            .map_err(KeystoreError::from)
+            .and_then(|ks| self.save(&ks));
+            if let Err(e) = upgraded {
+                eprintln!("[keystore] upgrade from v{} failed: {e}", keystore.version);
+            }
+        }
+        Ok(keypair)
+    }
+
+    /// Unlock and also return the wrapping key, for enrolling in the vault.
+    ///
+    /// Older keystores are upgraded first, since only the current format has
+    /// a wrapping key worth caching.
+    pub fn unlock_with_wrapping_key(
+        &self,
+        pub_key: &str,
+        passphrase: &str,
+    ) -> Result<(KeyPair, Zeroizing<[u8; wrap::KEY_LENGTH]>), KeystoreError> {
+        let mut keystore = self.load(pub_key)?;
+        if keystore.version < wrap::CURRENT_VERSION {
+            self.unlock(pub_key, passphrase)?;
+            keystore = self.load(pub_key)?;
+        }
+        let key = wrap::derive_wrapping_key(&keystore, passphrase)?;
+        let keypair = wrap::unwrap_with_key(&keystore, key.as_slice())?;
+        Ok((keypair, key))
+    }
+
+    /// Unlock with a wrapping key previously cached in the vault.
+    pub fn unlock_with_key(&self, pub_key: &str, key: &[u8]) -> Result<KeyPair, KeystoreError> {
+        let keystore = self.load(pub_key)?;
+        Ok(wrap::unwrap_with_key(&keystore, key)?)
+    }
+
+    /// Re-wrap under a new passphrase after verifying the current one.
+    pub fn change_passphrase(
+        &self,
+        pub_key: &str,
+        current: &str,
+        new: &str,
+        params: KdfParams,
+    ) -> Result<KeystoreSummary, KeystoreError> {
+        let keystore = self.load(pub_key)?;
+        let keypair = wrap::unwrap(&keystore, current)?;
+        let rewrapped = wrap::wrap(&keypair, new, params, keystore.created_at)?;
+        self.save(&rewrapped)
+    }
+
+    /// One-time import of the keystore previously kept in localStorage.
+    ///
+    /// Never overwrites an existing file for the same identity. Once this has
+    /// returned successfully, later calls report `AlreadyMigrated`.
+    pub fn migrate_legacy(
+        &self,
+        keystore: Option<&EncryptedKeystore>,
+    ) -> Result<MigrationOutcome, KeystoreError> {
+        let marker = self.dir.join(MIGRATION_MARKER);
+        if marker.exists() {
+            return Ok(MigrationOutcome::AlreadyMigrated);
+        }
+
+        let outcome = match keystore {
+            Some(ks) if !self.path_for(&ks.pub_key, FILE_EXT).exists() => {
+                self.save(ks)?;
+                MigrationOutcome::Migrated
+            }
+            Some(_) => MigrationOutcome::AlreadyPresent,
+            None => MigrationOutcome::NothingToMigrate,
+        };
+
+        durable::write_atomic(&marker, now_millis().to_string().as_bytes())?;
+        Ok(outcome)
+    }
+
+    /// File path for an identity. Public keys are hashed so the name is safe
+    /// on case-insensitive filesystems.
+    fn path_for(&self, pub_key: &str, ext: &str) -> PathBuf {
+        let digest = Sha256::digest(pub_key.as_bytes());
+        let stem: String = digest[..16].iter().map(|b| format!("{b:02x}")).collect();
+        self.dir.join(format!("{stem}.{ext}"))
+    }
+}
+
+fn read_file(path: &Path) -> Result<KeystoreFile, KeystoreError> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Err(KeystoreError::NotFound),
+        Err(e) => return Err(e.into()),
+    };
+    let file: KeystoreFile =
+        serde_json::from_slice(&bytes).map_err(|e| KeystoreError::Corrupt(e.to_string()))?;
+    if file.format > FILE_FORMAT {
+        return Err(KeystoreError::Corrupt(format!(
+            "unsupported file format {}",
+            file.format
+        )));
+    }
+    Ok(file)
+}
+
+fn to_json(file: &KeystoreFile) -> Result<Vec<u8>, KeystoreError> {
+    serde_json::to_vec_pretty(file).map_err(|e| KeystoreError::Corrupt(e.to_string()))
+}
+
+fn summarize(file: &KeystoreFile) -> KeystoreSummary {
+    KeystoreSummary {
+        pub_key: file.keystore.pub_key.clone(),
+        version: file.keystore.version,
+        generation: file.generation,
+        created_at: file.keystore.created_at,
+        saved_at: file.saved_at,
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keystore(pub_key: &str, encrypted: &str) -> EncryptedKeystore {
+        EncryptedKeystore {
+            version: 1,
+            encrypted: encrypted.into(),
+            pub_key: pub_key.into(),
+            created_at: 1,
+            kdf: None,
+            nonce: None,
+        }
+    }
+
+    fn temp_store() -> (tempfile::TempDir, Keystore) {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Keystore::new(dir.path().join("keystores"));
+        (dir, store)
+    }
+
+    #[test]
+    fn saves_rotate_the_previous_generation_into_the_backup() {
+        let (_dir, store) = temp_store();
+        let backup = store.path_for("alice", BACKUP_EXT);
+
+        assert_eq!(store.save(&keystore("alice", "one")).unwrap().generation, 1);
+        assert!(!backup.exists());
+        assert_eq!(store.save(&keystore("alice", "two")).unwrap().generation, 2);
+        let saved = read_file(&backup).unwrap();
+        assert_eq!(
+            (saved.generation, saved.keystore),
+            (1, keystore("alice", "one"))
+        );
+        assert_eq!(
+            store.save(&keystore("alice", "three")).unwrap().generation,
+            3
+        );
+        let saved = read_file(&backup).unwrap();
+        assert_eq!(
+            (saved.generation, saved.keystore),
+            (2, keystore("alice", "two"))
+        );
+
+        assert_eq!(store.load("alice").unwrap(), keystore("alice", "three"));
+        for ext in [TEMP_EXT, BACKUP_TEMP_EXT] {
+            assert!(!store.path_for("alice", ext).exists());
+        }
+    }
+
+    #[test]
+    fn load_falls_back_to_the_backup() {
+        let (_dir, store) = temp_store();
+        assert!(matches!(store.load("alice"), Err(KeystoreError::NotFound)));
+        store.save(&keystore("alice", "one")).unwrap();
+        store.save(&keystore("alice", "two")).unwrap();
+
+        let primary = store.path_for("alice", FILE_EXT);
+        fs::write(&primary, b"{ torn").unwrap();
+        assert_eq!(store.load("alice").unwrap(), keystore("alice", "one"));
+        fs::remove_file(&primary).unwrap();
+        assert_eq!(store.load("alice").unwrap(), keystore("alice", "one"));
+
+        // Saving over a damaged primary keeps the good backup.
+        fs::write(&primary, b"{ torn").unwrap();
+        assert_eq!(
+            store.save(&keystore("alice", "three")).unwrap().generation,
+            1
+        );
+        let backup = read_file(&store.path_for("alice", BACKUP_EXT)).unwrap();
+        assert_eq!(backup.keystore, keystore("alice", "one"));
+
+        fs::write(&primary, b"{ torn").unwrap();
+        fs::write(store.path_for("alice", BACKUP_EXT), b"").unwrap();
+        assert!(matches!(
+            store.load("alice"),
+            Err(KeystoreError::Corrupt(_))
+        ));
+
+        // A file that belongs to someone else.
+        store.save(&keystore("bob", "one")).unwrap();
+        fs::copy(store.path_for("bob", FILE_EXT), &primary).unwrap();
+        assert!(matches!(store.load("alice"), Err(KeystoreError::Mismatch)));
+    }
+
+    #[test]
+    fn list_and_delete() {
+        let (_dir, store) = temp_store();
+        assert!(store.list().unwrap().is_empty());
+        store.save(&keystore("alice", "one")).unwrap();
+        store.save(&keystore("alice", "two")).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        store.save(&keystore("bob", "one")).unwrap();
+        store.save(&keystore("carol", "one")).unwrap();
+        fs::write(store.path_for("carol", FILE_EXT), b"").unwrap();
+
+        // Backups and unreadable files are left out.
+        let listed: Vec<_> = store
+            .list()
+            .unwrap()
+            .into_iter()
+            .map(|s| (s.pub_key, s.generation))
+            .collect();
+        assert_eq!(listed, [("bob".to_owned(), 1), ("alice".to_owned(), 2)]);
+
+        assert!(store.delete("alice").unwrap());
+        assert!(!store.delete("alice").unwrap());
+        assert!(matches!(store.load("alice"), Err(KeystoreError::NotFound)));
+        for ext in [FILE_EXT, BACKUP_EXT] {
+            assert!(!store.path_for("alice", ext).exists());
+        }
+        assert_eq!(store.list().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn legacy_migration_runs_once() {
+        let (_dir, store) = temp_store();
+        assert_eq!(
+            store
+                .migrate_legacy(Some(&keystore("alice", "legacy")))
+                .unwrap(),
+            MigrationOutcome::Migrated
+        );
+        assert_eq!(store.load("alice").unwrap(), keystore("alice", "legacy"));
+        assert_eq!(
+            store
+                .migrate_legacy(Some(&keystore("bob", "legacy")))
+                .unwrap(),
+            MigrationOutcome::AlreadyMigrated
+        );
+        assert!(matches!(store.load("bob"), Err(KeystoreError::NotFound)));
+
+        let (_dir, store) = temp_store();
+        assert_eq!(
+            store.migrate_legacy(None).unwrap(),
+            MigrationOutcome::NothingToMigrate
+        );
+        assert_eq!(
+            store
+                .migrate_legacy(Some(&keystore("alice", "legacy")))
+                .unwrap(),
+            MigrationOutcome::AlreadyMigrated
+        );
+
+        // An identity already in the file store is never overwritten.
+        let (_dir, store) = temp_store();
+        store.save(&keystore("alice", "current")).unwrap();
+        assert_eq!(
+            store
+                .migrate_legacy(Some(&keystore("alice", "legacy")))
+                .unwrap(),
+            MigrationOutcome::AlreadyPresent
+        );
+        assert_eq!(store.load("alice").unwrap(), keystore("alice", "current"));
+    }
+}
@@ -0,0 +1,96 @@
+//! Files sealed to an unlocked identity.
+//!
+//! Native per-identity state (ratchet sessions, group sender keys, the local
+//! database key) holds live key material, so it is stored as
+//! `nonce ‖ AES-256-GCM(json)` under a key derived from the owner's `epriv`.
+//! Authentication failures surface as `InvalidData` I/O errors.
+
+use std::fs;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use hkdf::Hkdf;
+use rand::RngCore;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+use crate::crypto::{sea, KeyPair};
+use crate::durable;
+
+const NONCE_LENGTH: usize = 12;
+
+/// Sealing key for one kind of state, derivable only with the identity.
+pub fn identity_key(me: &KeyPair, info: &[u8]) -> Result<Zeroizing<[u8; 32]>, sea::SeaError> {
+    let secret = sea::private_key(&me.epriv)?;
+    let ikm = Zeroizing::new(secret.to_bytes());
+    let mut key = Zeroizing::new([0u8; 32]);
+    Hkdf::<Sha256>::new(None, &ikm)
+        .expand(info, key.as_mut())
+        .expect("32 bytes is a valid HKDF-SHA256 length");
+    Ok(key)
+}
+
+/// Stable file stem for a sealed entry, so names don't leak public keys.
+pub fn file_stem(id: &str) -> String {
+    let digest = Sha256::digest(id.as_bytes());
+    digest[..16].iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// Read a sealed JSON value. `Ok(None)` if the file doesn't exist.
+pub fn read_json<T: DeserializeOwned>(
+    path: &Path,
+    key: &[u8; 32],
+    aad: &[u8],
+) -> std::io::Result<Option<T>> {
+    read(path, key, aad)?
+        .map(|plain| {
+            serde_json::from_slice(&plain).map_err(|e| Error::new(ErrorKind::InvalidData, e))
+        })
+        .transpose()
+}
+
+/// Seal a value as JSON and atomically replace `path` with it.
+pub fn write_json<T: Serialize>(
+    path: &Path,
+    key: &[u8; 32],
+    aad: &[u8],
+    value: &T,
+) -> std::io::Result<()> {
+    let plain = Zeroizing::new(serde_json::to_vec(value).map_err(Error::other)?);
+    write(path, key, aad, &plain)
+}
+
+fn read(path: &Path, key: &[u8; 32], aad: &[u8]) -> std::io::Result<Option<Zeroizing<Vec<u8>>>> {
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    if bytes.len() < NONCE_LENGTH {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "sealed file is truncated",
+        ));
+    }
+    let (nonce, ct) = bytes.split_at(NONCE_LENGTH);
+    Aes256Gcm::new(key.into())
+        .decrypt(Nonce::from_slice(nonce), Payload { msg: ct, aad })
+        .map(|plain| Some(Zeroizing::new(plain)))
+        .map_err(|_| Error::new(ErrorKind::InvalidData, "sealed file failed to authenticate"))
+}
+
+fn write(path: &Path, key: &[u8; 32], aad: &[u8], plain: &[u8]) -> std::io::Result<()> {
+    let mut nonce = [0u8; NONCE_LENGTH];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    let ct = Aes256Gcm::new(key.into())
+        .encrypt(Nonce::from_slice(&nonce), Payload { msg: plain, aad })
+        .map_err(|_| Error::other("sealing failed"))?;
+
+    let mut bytes = nonce.to_vec();
+    bytes.extend_from_slice(&ct);
+    durable::write_atomic(path, &bytes)
+}
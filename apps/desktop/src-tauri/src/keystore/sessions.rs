@@ -0,0 +1,94 @@
+//! On-disk storage for DM ratchet sessions.
+//!
+//! Sessions live next to the keystores in a `sessions/` subdirectory, one
+//! file per (identity, peer) pair. Ratchet state holds live chain keys, so
+//! each file is sealed to the owner's identity (see [`super::sealed`]).
+
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use super::sealed;
+use crate::crypto::ratchet::{RatchetError, Session};
+use crate::crypto::KeyPair;
+
+const FILE_EXT: &str = "session";
+const SEAL_INFO: &[u8] = b"nodes/dm-ratchet/store";
+
+#[derive(Debug, thiserror::Error)]
+pub enum SessionError {
+    #[error(transparent)]
+    Ratchet(#[from] RatchetError),
+    #[error("Session file is corrupt")]
+    Corrupt,
+    #[error("Session I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Ratchet session store. One instance is managed by Tauri.
+pub struct SessionStore {
+    dir: PathBuf,
+    // Ratchet steps are read-modify-write; concurrent decrypts must not race.
+    lock: Mutex<()>,
+}
+
+impl SessionStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self {
+            dir,
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Run `f` against the session with a peer, creating it on first use,
+    /// and persist the result. Nothing is saved if `f` fails.
+    pub fn with_session<T>(
+        &self,
+        me: &KeyPair,
+        their_pub: &str,
+        their_epub: &str,
+        f: impl FnOnce(&mut Session) -> Result<T, RatchetError>,
+    ) -> Result<T, SessionError> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let key = sealed::identity_key(me, SEAL_INFO).map_err(RatchetError::from)?;
+        let path = self.path_for(&me.pub_key, their_pub);
+        let aad = associated_data(&me.pub_key, their_pub);
+
+        let stored: Option<Session> = sealed::read_json(&path, &key, &aad).map_err(corrupt)?;
+        let mut session = match stored {
+            Some(session) if session.matches(their_pub, their_epub) => session,
+            // Unknown peer, or the peer's encryption key changed: start over.
+            _ => Session::new(me, their_pub, their_epub)?,
+        };
+        let result = f(&mut session)?;
+
+        sealed::write_json(&path, &key, &aad, &session)?;
+        Ok(result)
+    }
+
+    /// Drop the session with a peer. Returns false if none existed.
+    pub fn reset(&self, my_pub: &str, their_pub: &str) -> Result<bool, SessionError> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        match fs::remove_file(self.path_for(my_pub, their_pub)) {
+            Ok(()) => Ok(true),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn path_for(&self, my_pub: &str, their_pub: &str) -> PathBuf {
+        let stem = sealed::file_stem(&format!("{my_pub}>{their_pub}"));
+        self.dir.join(format!("{stem}.{FILE_EXT}"))
+    }
+}
+
+fn associated_data(my_pub: &str, their_pub: &str) -> Vec<u8> {
+    format!("{my_pub}>{their_pub}").into_bytes()
+}
+
+fn corrupt(e: std::io::Error) -> SessionError {
+    match e.kind() {
+        std::io::ErrorKind::InvalidData => SessionError::Corrupt,
+        _ => SessionError::Io(e),
+    }
+}
@@ -0,0 +1,364 @@
+//! Passphrase wrapping of the identity keypair.
+//!
+//! Version 1 is what `KeyManager.saveToLocalStore` writes: `SEA.work` with the
+//! public key as salt, then `SEA.encrypt`. It is read-only here.
+//!
+//! Version 2 derives the wrapping key with Argon2id over a random salt and
+//! seals the keypair with AES-256-GCM, binding the public key as associated
+//! data so an envelope can't be swapped onto another identity.
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use zeroize::Zeroizing;
+
+use super::EncryptedKeystore;
+use crate::crypto::{sea, KeyPair};
+
+/// Keystore format written by `wrap`.
+pub const CURRENT_VERSION: u32 = 2;
+
+const SALT_LENGTH: usize = 16;
+const NONCE_LENGTH: usize = 12;
+pub const KEY_LENGTH: usize = 32;
+
+const MIN_MEMORY_KIB: u32 = 8 * 1024;
+const MAX_MEMORY_KIB: u32 = 1024 * 1024;
+const MAX_ITERATIONS: u32 = 10;
+const MAX_PARALLELISM: u32 = 8;
+
+/// Tunable Argon2id cost parameters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KdfParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for KdfParams {
+    /// 64 MiB, 3 passes, 1 lane — roughly half a second on a mid-range laptop.
+    fn default() -> Self {
+        Self {
+            memory_kib: 64 * 1024,
+            iterations: 3,
+            parallelism: 1,
+        }
+    }
+}
+
+/// KDF description stored alongside a version 2 keystore.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KdfSpec {
+    pub algorithm: String,
+    pub salt: String,
+    #[serde(flatten)]
+    pub params: KdfParams,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum WrapError {
+    #[error("Failed to decrypt keystore. Wrong passphrase?")]
+    WrongPassphrase,
+    #[error("Unsupported keystore version {0}")]
+    UnsupportedVersion(u32),
+    #[error("Malformed keystore: {0}")]
+    Malformed(String),
+    #[error("Invalid KDF parameters: {0}")]
+    InvalidParams(String),
+}
+
+impl KdfParams {
+    pub fn validate(&self) -> Result<(), WrapError> {
+        if !(MIN_MEMORY_KIB..=MAX_MEMORY_KIB).contains(&self.memory_kib) {
+            return Err(WrapError::InvalidParams(format!(
+                "memoryKib must be between {MIN_MEMORY_KIB} and {MAX_MEMORY_KIB}"
+            )));
+        }
+        if !(1..=MAX_ITERATIONS).contains(&self.iterations) {
+            return Err(WrapError::InvalidParams(format!(
+                "iterations must be between 1 and {MAX_ITERATIONS}"
+            )));
+        }
+        if !(1..=MAX_PARALLELISM).contains(&self.parallelism) {
+            return Err(WrapError::InvalidParams(format!(
+                "parallelism must be between 1 and {MAX_PARALLELISM}"
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Seal a keypair into a version 2 keystore.
+pub fn wrap(
+    keypair: &KeyPair,
+    passphrase: &str,
+    params: KdfParams,
+    created_at: u64,
+) -> Result<EncryptedKeystore, WrapError> {
+    params.validate()?;
+
+    let mut salt = [0u8; SALT_LENGTH];
+    let mut nonce = [0u8; NONCE_LENGTH];
+    rand::thread_rng().fill_bytes(&mut salt);
+    rand::thread_rng().fill_bytes(&mut nonce);
+
+    let key = derive_key(passphrase, &salt, params)?;
+    let plaintext = Zeroizing::new(
+        serde_json::to_vec(keypair).map_err(|e| WrapError::Malformed(e.to_string()))?,
+    );
+    let ciphertext = Aes256Gcm::new(key.as_slice().into())
+        .encrypt(
+            Nonce::from_slice(&nonce),
+            Payload {
+                msg: &plaintext,
+                aad: keypair.pub_key.as_bytes(),
+            },
+        )
+        .map_err(|_| WrapError::Malformed("encryption failed".into()))?;
+
+    Ok(EncryptedKeystore {
+        version: CURRENT_VERSION,
+        encrypted: BASE64.encode(ciphertext),
+        pub_key: keypair.pub_key.clone(),
+        created_at,
+        kdf: Some(KdfSpec {
+            algorithm: "argon2id".into(),
+            salt: BASE64.encode(salt),
+            params,
+        }),
+        nonce: Some(BASE64.encode(nonce)),
+    })
+}
+
+/// Open a keystore of any supported version.
+pub fn unwrap(keystore: &EncryptedKeystore, passphrase: &str) -> Result<KeyPair, WrapError> {
+    let keypair = match keystore.version {
+        1 => unwrap_v1(keystore, passphrase)?,
+        2 => unwrap_v2(keystore, passphrase)?,
+        v => return Err(WrapError::UnsupportedVersion(v)),
+    };
+    if keypair.pub_key != keystore.pub_key {
+        return Err(WrapError::Malformed(
+            "keypair does not match keystore public key".into(),
+        ));
+    }
+    Ok(keypair)
+}
+
+fn unwrap_v1(keystore: &EncryptedKeystore, passphrase: &str) -> Result<KeyPair, WrapError> {
+    let derived = Zeroizing::new(sea::work(passphrase, &keystore.pub_key));
+    let plaintext = Zeroizing::new(sea::decrypt(&keystore.encrypted, &derived).map_err(
+        |e| match e {
+            sea::SeaError::Malformed(m) => WrapError::Malformed(m),
+            _ => WrapError::WrongPassphrase,
+        },
+    )?);
+    serde_json::from_str(&plaintext).map_err(|e| WrapError::Malformed(e.to_string()))
+}
+
+fn unwrap_v2(keystore: &EncryptedKeystore, passphrase: &str) -> Result<KeyPair, WrapError> {
+    let key = derive_wrapping_key(keystore, passphrase)?;
+    unwrap_with_key(keystore, key.as_slice())
+}
+
+/// Run the KDF recorded in a version 2 keystore, yielding its wrapping key.
+///
+/// This is what the credential vault caches so later unlocks can skip both
+/// the passphrase and the Argon2 cost.
+pub fn derive_wrapping_key(
+    keystore: &EncryptedKeystore,
+    passphrase: &str,
+) -> Result<Zeroizing<[u8; KEY_LENGTH]>, WrapError> {
+    if keystore.version != CURRENT_VERSION {
+        return Err(WrapError::UnsupportedVersion(keystore.version));
+    }
+    let kdf = keystore
+        .kdf
+        .as_ref()
+        .ok_or_else(|| WrapError::Malformed("missing kdf".into()))?;
+    if kdf.algorithm != "argon2id" {
+        return Err(WrapError::Malformed(format!(
+            "unsupported kdf {}",
+            kdf.algorithm
+        )));
+    }
+    kdf.params.validate()?;
+    derive_key(passphrase, &decode(&kdf.salt)?, kdf.params)
+}
+
+/// Open a version 2 keystore with an already-derived wrapping key.
+pub fn unwrap_with_key(keystore: &EncryptedKeystore, key: &[u8]) -> Result<KeyPair, WrapError> {
+    if keystore.version != CURRENT_VERSION {
+        return Err(WrapError::UnsupportedVersion(keystore.version));
+    }
+    if key.len() != KEY_LENGTH {
+        return Err(WrapError::Malformed("bad key length".into()));
+    }
+    let nonce = decode(keystore.nonce.as_deref().unwrap_or_default())?;
+    if nonce.len() != NONCE_LENGTH {
+        return Err(WrapError::Malformed("bad nonce length".into()));
+    }
+    let ciphertext = decode(&keystore.encrypted)?;
+
+    let plaintext = Zeroizing::new(
+        Aes256Gcm::new(key.into())
+            .decrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: &ciphertext,
+                    aad: keystore.pub_key.as_bytes(),
+                },
+            )
+            .map_err(|_| WrapError::WrongPassphrase)?,
+    );
+    let keypair: KeyPair =
+        serde_json::from_slice(&plaintext).map_err(|e| WrapError::Malformed(e.to_string()))?;
+    if keypair.pub_key != keystore.pub_key {
+        return Err(WrapError::Malformed(
+            "keypair does not match keystore public key".into(),
+        ));
+    }
+    Ok(keypair)
+}
+
+fn derive_key(
+    passphrase: &str,
+    salt: &[u8],
+    params: KdfParams,
+) -> Result<Zeroizing<[u8; KEY_LENGTH]>, WrapError> {
+    let argon_params = Params::new(
+        params.memory_kib,
+        params.iterations,
+        params.parallelism,
+        Some(KEY_LENGTH),
+    )
+    .map_err(|e| WrapError::InvalidParams(e.to_string()))?;
+
+    let mut key = Zeroizing::new([0u8; KEY_LENGTH]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, argon_params)
+        .hash_password_into(passphrase.as_bytes(), salt, key.as_mut())
+        .map_err(|e| WrapError::InvalidParams(e.to_string()))?;
+    Ok(key)
+}
+
+fn decode(value: &str) -> Result<Vec<u8>, WrapError> {
+    BASE64
+        .decode(value)
+        .map_err(|e| WrapError::Malformed(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::keystore::{Keystore, KeystoreError};
+
+    const PASSPHRASE: &str = "correct horse battery staple";
+    /// What `KeyManager.saveToLocalStore` wrote for `keypair()` under
+    /// `PASSPHRASE`.
+    const V1_ENCRYPTED: &str = r#"SEA{"ct":"1ERdQuasSg+RppmA51/9Ve2yvkC701dY89RR3d7jOXdTY4Z4QeknLVrJsNuO9MH2VjkJKpxz5ysAaNcH/7QDKdtHt09kJuTwFlZwBSdmpm22kJLsl9PxvsrCMnbxJYaZ8ld2d1yrKy8tLUaktUUf+h4jt0wI1gte544XZPDBtGWBjU9eldaMxcJg+EdEV7iN/QgF/uvXZumHtNtDGO1d8sFHiF7cdBFHwG40sUo66HB+vMjRNAuGSKg8KpC0amu9D4bnf3xOHlz9se/331JUZIDoa4Ef1USVLkqr/nXDpCrvGpCZto8tJj3DL2sDQ1P1nh1mUTlI0N+y1AVeM2P9alPA047FvevS/AjkOPr99WJOMUX1Fydako8hW56Bc5DmOk4SoeBnqjeFfrtJJ2wudJxouFGULnrJsUoJ8nU=","iv":"VdfaQLrANu3NIU3oTZ4C","s":"QrZfc9B8Y1FN"}"#;
+    /// The cheapest parameters `validate` allows.
+    const FAST: KdfParams = KdfParams {
+        memory_kib: MIN_MEMORY_KIB,
+        iterations: 1,
+        parallelism: 1,
+    };
+
+    fn keypair() -> KeyPair {
+        KeyPair {
+            pub_key: "fFgfvZbDli5JUNcbGAPanfkSrZqYosmF67MLWJZPDgI.pFZ20AuDFF8O6Eq1TS6BgdGshxamlvr8Gp-ID-R-0c8".into(),
+            priv_key: "B_3Meg0eVdbrjK_4dQDraSHQpe2nTsBeGQqhvRtnjVc".into(),
+            epub: "KX0M568Mj5yyDyPUJEzJvCmj0ljPTYMpoJxuIVSSoHQ.4OdvT75Ey-pauqCX1E7oHSYDcdy1u-m19xuTOIEpvXc".into(),
+            epriv: "kXJwy6GPs4b81PGDKjvzKCzdNe2ZpxbBdL7qTQMs51A".into(),
+        }
+    }
+
+    fn v1_keystore() -> EncryptedKeystore {
+        EncryptedKeystore {
+            version: 1,
+            encrypted: V1_ENCRYPTED.into(),
+            pub_key: keypair().pub_key.clone(),
+            created_at: 1_700_000_000_000,
+            kdf: None,
+            nonce: None,
+        }
+    }
+
+    #[test]
+    fn v2_round_trip() {
+        let keystore = wrap(&keypair(), PASSPHRASE, FAST, 1).unwrap();
+        assert_eq!(keystore.version, CURRENT_VERSION);
+        assert_eq!(keystore.kdf.as_ref().unwrap().params, FAST);
+        assert!(unwrap(&keystore, PASSPHRASE).unwrap() == keypair());
+
+        let key = derive_wrapping_key(&keystore, PASSPHRASE).unwrap();
+        assert!(unwrap_with_key(&keystore, key.as_slice()).unwrap() == keypair());
+    }
+
+    #[test]
+    fn wrong_passphrase() {
+        let keystore = wrap(&keypair(), PASSPHRASE, FAST, 1).unwrap();
+        assert!(matches!(
+            unwrap(&keystore, "correct horse battery stapler"),
+            Err(WrapError::WrongPassphrase)
+        ));
+    }
+
+    #[test]
+    fn public_key_is_bound() {
+        let mut keystore = wrap(&keypair(), PASSPHRASE, FAST, 1).unwrap();
+        let key = derive_wrapping_key(&keystore, PASSPHRASE).unwrap();
+        keystore.pub_key =
+            "UxS3-yrVhUtyGA1UhvhVh3us-Zo2FDAio6qvZTFK5AU.ZjtWuM1Lw_7ws6E9PvZW7BX4Q99AeFGDs96dZ4TFA-s"
+                .into();
+        assert!(matches!(
+            unwrap_with_key(&keystore, key.as_slice()),
+            Err(WrapError::WrongPassphrase)
+        ));
+    }
+
+    #[test]
+    fn rejects_out_of_range_params() {
+        let weak = KdfParams {
+            memory_kib: MIN_MEMORY_KIB - 1,
+            ..FAST
+        };
+        assert!(matches!(
+            wrap(&keypair(), PASSPHRASE, weak, 1),
+            Err(WrapError::InvalidParams(_))
+        ));
+    }
+
+    #[test]
+    fn opens_v1_keystores() {
+        assert!(unwrap(&v1_keystore(), PASSPHRASE).unwrap() == keypair());
+        assert!(matches!(
+            unwrap(&v1_keystore(), "wrong"),
+            Err(WrapError::WrongPassphrase)
+        ));
+    }
+
+    #[test]
+    fn unlock_upgrades_v1_to_v2() {
+        let dir = tempfile::tempdir().unwrap();
+        let keystore = Keystore::new(dir.path().to_path_buf());
+        let pub_key = keypair().pub_key.clone();
+        keystore.save(&v1_keystore()).unwrap();
+
+        assert!(keystore.unlock(&pub_key, PASSPHRASE).unwrap() == keypair());
+        let upgraded = keystore.load(&pub_key).unwrap();
+        assert_eq!(upgraded.version, CURRENT_VERSION);
+        assert_eq!(upgraded.created_at, v1_keystore().created_at);
+        assert_eq!(upgraded.kdf.unwrap().params, KdfParams::default());
+        assert!(keystore.unlock(&pub_key, PASSPHRASE).unwrap() == keypair());
+        assert!(matches!(
+            keystore.unlock(&pub_key, "wrong"),
+            Err(KeystoreError::Wrap(WrapError::WrongPassphrase))
+        ));
+    }
+}
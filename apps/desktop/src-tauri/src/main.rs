@@ -1,9 +1,26 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 mod commands;
+mod crypto;
+mod db;
+mod deep_link;
+mod durable;
+mod hotkeys;
+mod idle;
+mod keystore;
+mod notifications;
+mod popout;
+mod quiet_hours;
+mod relay;
+mod search;
+mod settings;
+mod shutdown;
 mod tray;
+mod vault;
+mod window_state;
 
-use tauri::{Emitter, Manager};
+use tauri::Manager;
+use tauri_plugin_deep_link::DeepLinkExt;
 
 fn main() {
     tauri::Builder::default()
@@ -12,53 +29,209 @@ fn main() {
             // Create the system tray
             tray::create_tray(app.handle())?;
 
-            // Minimize to tray on close (production behavior)
-            // Set to false for debug mode (quit on close)
-            let minimize_to_tray = true;
+            let settings =
+                settings::Settings::load(app.path().app_config_dir()?.join("settings.json"));
+            settings.refresh_launch_at_login(app.handle());
+            let start_hidden = settings.get().start_hidden;
+            let hotkey_bindings = settings.get().hotkeys;
+            let relay_settings = settings.get().relay;
+            app.manage(settings);
+            idle::start(app.handle());
 
-            if minimize_to_tray {
-                let main_window = app.get_webview_window("main").unwrap();
-                main_window.on_window_event({
-                    let window = main_window.clone();
-                    move |event| {
-                        if let tauri::WindowEvent::CloseRequested { api, .. } = event {
-                            // Hide the window instead of closing it
-                            api.prevent_close();
+            app.manage(hotkeys::Hotkeys::default());
+            hotkeys::apply(app.handle(), &hotkey_bindings);
+
+            app.manage(notifications::Notifier::default());
+            notifications::start(app.handle());
+            app.manage(quiet_hours::QuietHours::default());
+            quiet_hours::start(app.handle());
+
+            app.manage(shutdown::Shutdown::default());
+            #[cfg(target_os = "linux")]
+            if let Err(e) = shutdown::watch_signals(app.handle()) {
+                eprintln!("[shutdown] Failed to listen for SIGTERM: {e}");
+            }
+
+            // nodes:// links. Installers register the scheme; AppImages and
+            // dev builds have to do it at runtime.
+            app.manage(deep_link::DeepLinks::default());
+            #[cfg(any(target_os = "linux", all(debug_assertions, windows)))]
+            if let Err(e) = app.deep_link().register_all() {
+                eprintln!("[deep-link] Failed to register nodes:// handler: {e}");
+            }
+            // Windows and Linux pass the link as a launch argument; macOS
+            // delivers it as an event, also on a cold start.
+            deep_link::handle_urls(app.handle(), std::env::args().skip(1));
+            #[cfg(target_os = "macos")]
+            {
+                let handle = app.handle().clone();
+                app.deep_link().on_open_url(move |event| {
+                    let urls = event.urls();
+                    deep_link::handle_urls(&handle, urls.iter().map(|url| url.as_str()));
+                });
+            }
+
+            // Native identity storage, independent of webview storage
+            let data_dir = app.path().app_data_dir()?;
+            let keystore_dir = data_dir.join("keystore");
+            app.manage(keystore::sessions::SessionStore::new(
+                keystore_dir.join("sessions"),
+            ));
+            app.manage(keystore::groups::GroupKeyStore::new(
+                keystore_dir.join("groups"),
+            ));
+            app.manage(keystore::Keystore::new(keystore_dir));
+            app.manage(vault::Vault::platform(data_dir.join("vault")));
+            // Opened once an identity unlocks; see `commands::keystore`
+            app.manage(db::Database::new(data_dir.join("db")));
+            app.manage(relay::Relay::new(data_dir.join("relay.db")));
+            relay::apply(app.handle(), &relay_settings);
+
+            app.manage(window_state::WindowStates::load(
+                app.path().app_config_dir()?.join("window-state.json"),
+            ));
+            app.manage(popout::Popouts::load(
+                app.path().app_config_dir()?.join("popouts.json"),
+            ));
+
+            // Close-to-tray and quit confirmation are read on every close, so
+            // changing them in settings applies immediately
+            let main_window = app.get_webview_window("main").unwrap();
+            window_state::restore(&main_window);
+            window_state::track(&main_window);
+            let app_handle = app.handle().clone();
+            main_window.on_window_event(move |event| {
+                if let tauri::WindowEvent::CloseRequested { api, .. } = event {
+                    api.prevent_close();
+                    if app_handle.state::<settings::Settings>().get().close_to_tray {
+                        // Hide the window instead of closing it
+                        if let Some(window) = app_handle.get_webview_window("main") {
                             let _ = window.hide();
                         }
+                    } else {
+                        shutdown::request_quit(&app_handle);
                     }
-                });
-            } else {
-                // Debug behavior: quit on close
-                let main_window = app.get_webview_window("main").unwrap();
-                let app_handle = app.handle().clone();
-                main_window.on_window_event(move |event| {
-                    if let tauri::WindowEvent::CloseRequested { api, .. } = event {
-                        // Emit event for frontend cleanup
-                        let _ = app_handle.emit("app:before-quit", ());
-                        // Allow close to proceed after brief delay for cleanup
-                        api.prevent_close();
-                        let handle = app_handle.clone();
-                        std::thread::spawn(move || {
-                            std::thread::sleep(std::time::Duration::from_millis(200));
-                            handle.exit(0);
-                        });
-                    }
-                });
+                }
+            });
+            // The window starts hidden (see tauri.conf.json) so it doesn't
+            // flash when it should stay in the tray
+            if !start_hidden {
+                let _ = main_window.show();
             }
+            popout::restore(&main_window);
 
             Ok(())
         })
-        .plugin(tauri_plugin_single_instance::init(|app, _args, _cwd| {
+        .plugin(tauri_plugin_single_instance::init(|app, args, _cwd| {
             // Focus the existing window when a second instance is launched
             if let Some(window) = app.get_webview_window("main") {
                 let _ = window.show();
                 let _ = window.set_focus();
             }
+            // A link opened while we're running starts a second instance
+            // with the URL in its argv.
+            deep_link::handle_urls(app, args.iter().skip(1));
         }))
+        .plugin(tauri_plugin_deep_link::init())
+        .plugin(tauri_plugin_autostart::init(
+            tauri_plugin_autostart::MacosLauncher::LaunchAgent,
+            None,
+        ))
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_http::init())
         .plugin(tauri_plugin_notification::init())
-        .run(tauri::generate_context!())
-        .expect("error while running Nodes");
+        .plugin(tauri_plugin_dialog::init())
+        .plugin(
+            tauri_plugin_global_shortcut::Builder::new()
+                .with_handler(hotkeys::on_shortcut)
+                .build(),
+        )
+        .invoke_handler(tauri::generate_handler![
+            commands::deep_link::deep_link_ready,
+            commands::hotkeys::hotkeys_conflicts,
+            commands::keystore::keystore_save,
+            commands::keystore::keystore_load,
+            commands::keystore::keystore_list,
+            commands::keystore::keystore_delete,
+            commands::keystore::keystore_migrate_legacy,
+            commands::keystore::keystore_create,
+            commands::keystore::keystore_unlock,
+            commands::keystore::keystore_change_passphrase,
+            commands::keystore::keystore_lock,
+            commands::vault::vault_enable,
+            commands::vault::vault_disable,
+            commands::vault::vault_unlock,
+            commands::identity::identity_export_mnemonic,
+            commands::identity::identity_import_mnemonic,
+            commands::identity::identity_split_backup,
+            commands::identity::identity_export_share_file,
+            commands::identity::identity_read_share_file,
+            commands::identity::identity_recover_backup,
+            commands::inbox::inbox_add,
+            commands::inbox::inbox_page,
+            commands::inbox::inbox_mark_read,
+            commands::inbox::inbox_mark_all_read,
+            commands::inbox::inbox_remove,
+            commands::inbox::inbox_clear,
+            commands::inbox::inbox_unread,
+            commands::sea::sea_sign,
+            commands::sea::sea_verify,
+            commands::sea::sea_secret,
+            commands::sea::sea_encrypt,
+            commands::sea::sea_decrypt,
+            commands::files::file_encrypt,
+            commands::files::file_decrypt,
+            commands::sessions::dm_session_encrypt,
+            commands::sessions::dm_session_decrypt,
+            commands::sessions::dm_session_reset,
+            commands::groups::group_rotate,
+            commands::groups::group_distribute,
+            commands::groups::group_accept,
+            commands::groups::group_encrypt,
+            commands::groups::group_decrypt,
+            commands::groups::group_forget,
+            commands::messages::message_cache_put,
+            commands::messages::message_cache_history,
+            commands::messages::message_cache_set_reactions,
+            commands::notifications::notification_show,
+            commands::notifications::notification_clear,
+            commands::popout::popout_open,
+            commands::popout::popout_focus,
+            commands::popout::popout_close,
+            commands::popout::popout_list,
+            commands::popout::popout_target,
+            commands::popout::popout_publish,
+            commands::quiet_hours::quiet_hours_status,
+            commands::relay::relay_status,
+            commands::search::search_index,
+            commands::search::search_remove,
+            commands::search::search_query,
+            commands::search::search_stats,
+            commands::search::search_clear,
+            commands::settings::settings_get,
+            commands::settings::settings_set,
+            commands::shutdown::app_quit,
+            commands::shutdown::shutdown_register,
+            commands::shutdown::shutdown_unregister,
+            commands::shutdown::shutdown_ack,
+            commands::tray::tray_update,
+        ])
+        .build(tauri::generate_context!())
+        .expect("error while building Nodes")
+        .run(|app, event| match event {
+            // The OS ending the session (or Cmd+Q on macOS) asks the event
+            // loop to exit; hold it until subscribers have cleaned up.
+            tauri::RunEvent::ExitRequested { api, .. } => {
+                if !app.state::<shutdown::Shutdown>().is_finished() {
+                    api.prevent_exit();
+                    shutdown::quit(app, shutdown::QuitReason::SessionEnd);
+                }
+            }
+            tauri::RunEvent::Exit => {
+                if let Err(e) = app.state::<window_state::WindowStates>().flush() {
+                    eprintln!("[window-state] {e}");
+                }
+            }
+            _ => {}
+        });
 }
@@ -0,0 +1,398 @@
+//! Notifications over `org.freedesktop.Notifications`.
+//!
+//! Buttons are only added if the server lists the `actions` capability
+//! (GNOME, KDE, most standalone daemons). KDE also lists `inline-reply`: its
+//! Reply button opens a text field and the text comes back in
+//! `NotificationReplied`. Without it, Reply just opens the conversation.
+
+use std::collections::HashMap;
+
+use futures_util::{stream, StreamExt};
+use zbus::zvariant::Value;
+use zbus::{proxy, Connection};
+
+use super::{ActionKind, Toast};
+
+const APP_NAME: &str = "Nodes";
+/// `NotificationClosed` reason for the user dismissing it.
+const DISMISSED: u32 = 2;
+
+#[proxy(
+    interface = "org.freedesktop.Notifications",
+    default_service = "org.freedesktop.Notifications",
+    default_path = "/org/freedesktop/Notifications"
+)]
+trait Notifications {
+    #[allow(clippy::too_many_arguments)]
+    fn notify(
+        &self,
+        app_name: &str,
+        replaces_id: u32,
+        app_icon: &str,
+        summary: &str,
+        body: &str,
+        actions: &[&str],
+        hints: &HashMap<&str, &Value<'_>>,
+        expire_timeout: i32,
+    ) -> zbus::Result<u32>;
+
+    fn close_notification(&self, id: u32) -> zbus::Result<()>;
+
+    fn get_capabilities(&self) -> zbus::Result<Vec<String>>;
+
+    #[zbus(signal)]
+    fn action_invoked(&self, id: u32, action_key: &str) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn notification_replied(&self, id: u32, text: &str) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    fn notification_closed(&self, id: u32, reason: u32) -> zbus::Result<()>;
+}
+
+/// Something the user did to one of our notifications.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    Action { id: u32, action: ActionKind },
+    Replied { id: u32, text: String },
+    Dismissed { id: u32 },
+}
+
+pub struct Server {
+    proxy: NotificationsProxy<'static>,
+    actions: bool,
+    inline_reply: bool,
+    markup: bool,
+}
+
+impl Server {
+    /// Connect over the session bus and check what the server supports.
+    pub async fn connect() -> zbus::Result<Self> {
+        Self::with_connection(&Connection::session().await?).await
+    }
+
+    pub async fn with_connection(connection: &Connection) -> zbus::Result<Self> {
+        let proxy = NotificationsProxy::new(connection).await?;
+        let capabilities = proxy.get_capabilities().await?;
+        let has = |capability: &str| capabilities.iter().any(|c| c == capability);
+        Ok(Self {
+            actions: has("actions"),
+            inline_reply: has("inline-reply"),
+            markup: has("body-markup"),
+            proxy,
+        })
+    }
+
+    /// Show `toast`, replacing notification `replaces` if it's still up, and
+    /// return its ID.
+    pub async fn show(&self, replaces: Option<u32>, toast: &Toast) -> zbus::Result<u32> {
+        let mut actions = Vec::new();
+        if self.actions && toast.interactive {
+            actions.extend(["default", "Open"]);
+            if self.inline_reply {
+                actions.extend(["inline-reply", "Reply"]);
+            } else {
+                actions.extend(["reply", "Reply"]);
+            }
+            actions.extend(["mark-read", "Mark as read"]);
+            if toast.can_mute {
+                actions.extend(["mute", "Mute channel"]);
+            }
+        }
+        let category = Value::from("im.received");
+        let placeholder = Value::from(toast.reply_placeholder.as_str());
+        let mut hints = HashMap::from([("category", &category)]);
+        if self.inline_reply && toast.interactive {
+            hints.insert("x-kde-reply-placeholder-text", &placeholder);
+        }
+        let body = if self.markup {
+            escape(&toast.body)
+        } else {
+            toast.body.clone()
+        };
+        self.proxy
+            .notify(
+                APP_NAME,
+                replaces.unwrap_or(0),
+                "",
+                &toast.summary,
+                &body,
+                &actions,
+                &hints,
+                -1,
+            )
+            .await
+    }
+
+    pub async fn close(&self, id: u32) -> zbus::Result<()> {
+        self.proxy.close_notification(id).await
+    }
+
+    /// Call `on_event` for every activation, reply and dismissal until the
+    /// connection drops. Signals for other apps' notifications are passed
+    /// along too; their IDs just won't match anything. Malformed signals are
+    /// logged and skipped.
+    pub async fn listen(&self, on_event: impl Fn(Event)) -> zbus::Result<()> {
+        let invoked = self.proxy.receive_action_invoked().await?.map(|signal| {
+            let args = signal.args()?;
+            let action = match args.action_key {
+                "default" => ActionKind::Open,
+                "reply" => ActionKind::Reply,
+                "mark-read" => ActionKind::MarkRead,
+                "mute" => ActionKind::Mute,
+                // The inline reply arrives as `NotificationReplied`.
+                _ => return Ok(None),
+            };
+            Ok(Some(Event::Action {
+                id: args.id,
+                action,
+            }))
+        });
+        let replied = self
+            .proxy
+            .receive_notification_replied()
+            .await?
+            .map(|signal| {
+                let args = signal.args()?;
+                Ok(Some(Event::Replied {
+                    id: args.id,
+                    text: args.text.to_string(),
+                }))
+            });
+        let closed = self
+            .proxy
+            .receive_notification_closed()
+            .await?
+            .map(|signal| {
+                let args = signal.args()?;
+                Ok((args.reason == DISMISSED).then_some(Event::Dismissed { id: args.id }))
+            });
+
+        let mut events = stream::select(invoked, stream::select(replied, closed));
+        while let Some(event) = events.next().await {
+            let event: zbus::Result<Option<Event>> = event;
+            match event {
+                Ok(Some(event)) => on_event(event),
+                Ok(None) => {}
+                Err(e) => eprintln!("[notifications] Ignoring a malformed signal: {e}"),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Escape text for servers that read the body as markup.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use tokio::net::UnixStream;
+    use tokio::sync::mpsc;
+    use zbus::connection::Builder;
+    use zbus::zvariant::OwnedValue;
+    use zbus::Guid;
+
+    use super::*;
+
+    const PATH: &str = "/org/freedesktop/Notifications";
+    const INTERFACE: &str = "org.freedesktop.Notifications";
+
+    /// A notification server that records the actions it's asked to show.
+    struct MockServer {
+        capabilities: Vec<String>,
+        shown: Arc<Mutex<Vec<Vec<String>>>>,
+    }
+
+    #[zbus::interface(name = "org.freedesktop.Notifications")]
+    impl MockServer {
+        fn get_capabilities(&self) -> Vec<String> {
+            self.capabilities.clone()
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        fn notify(
+            &self,
+            _app_name: &str,
+            _replaces_id: u32,
+            _app_icon: &str,
+            _summary: &str,
+            _body: &str,
+            actions: Vec<String>,
+            _hints: HashMap<String, OwnedValue>,
+            _expire_timeout: i32,
+        ) -> u32 {
+            let mut shown = self.shown.lock().unwrap();
+            shown.push(actions);
+            shown.len() as u32
+        }
+
+        fn close_notification(&self, _id: u32) {}
+    }
+
+    /// The mock's end of a private connection, and a `Server` on the other.
+    async fn connect(capabilities: &[&str]) -> (Connection, Server, Arc<Mutex<Vec<Vec<String>>>>) {
+        let shown = Arc::new(Mutex::new(Vec::new()));
+        let mock = MockServer {
+            capabilities: capabilities.iter().map(|c| c.to_string()).collect(),
+            shown: shown.clone(),
+        };
+        let (ours, theirs) = UnixStream::pair().unwrap();
+        let (mock, client) = futures_util::join!(
+            async {
+                Builder::unix_stream(theirs)
+                    .server(Guid::generate())
+                    .unwrap()
+                    .p2p()
+                    .serve_at(PATH, mock)
+                    .unwrap()
+                    .build()
+                    .await
+                    .unwrap()
+            },
+            async { Builder::unix_stream(ours).p2p().build().await.unwrap() },
+        );
+        let server = Server::with_connection(&client).await.unwrap();
+        (mock, server, shown)
+    }
+
+    async fn emit<B>(mock: &Connection, signal: &str, body: &B)
+    where
+        B: serde::Serialize + zbus::zvariant::DynamicType,
+    {
+        mock.emit_signal(None::<()>, PATH, INTERFACE, signal, body)
+            .await
+            .unwrap();
+    }
+
+    /// Run `listen` while `script` emits signals, and return the events seen.
+    /// A dismissal of notification 0 is emitted until it arrives, so the
+    /// script only starts once the listener is subscribed.
+    async fn listen<F: std::future::Future<Output = ()>>(
+        mock: &Connection,
+        server: &Server,
+        count: usize,
+        script: impl FnOnce() -> F,
+    ) -> Vec<Event> {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let listening = server.listen(move |event| {
+            let _ = tx.send(event);
+        });
+        let scripted = async {
+            let probe = Event::Dismissed { id: 0 };
+            loop {
+                emit(mock, "NotificationClosed", &(0u32, DISMISSED)).await;
+                let next = tokio::time::timeout(Duration::from_millis(20), rx.recv()).await;
+                if next.is_ok_and(|event| event == Some(probe.clone())) {
+                    break;
+                }
+            }
+            script().await;
+            let mut events = Vec::new();
+            while events.len() < count {
+                let event = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+                    .await
+                    .expect("timed out waiting for an event")
+                    .unwrap();
+                if event != probe {
+                    events.push(event);
+                }
+            }
+            events
+        };
+        tokio::select! {
+            result = listening => panic!("listener stopped: {result:?}"),
+            events = scripted => events,
+        }
+    }
+
+    fn action(id: u32, action: ActionKind) -> Event {
+        Event::Action { id, action }
+    }
+
+    fn toast(can_mute: bool) -> Toast {
+        Toast {
+            summary: "#general".into(),
+            body: "alice: hi".into(),
+            can_mute,
+            reply_placeholder: "Message #general".into(),
+            interactive: true,
+        }
+    }
+
+    #[test]
+    fn buttons_follow_the_server_capabilities() {
+        tauri::async_runtime::block_on(async {
+            let (_mock, server, shown) = connect(&["actions", "inline-reply"]).await;
+            assert_eq!(server.show(None, &toast(true)).await.unwrap(), 1);
+            let (_mock, server, plain) = connect(&["actions"]).await;
+            server.show(None, &toast(false)).await.unwrap();
+            let (_mock, server, none) = connect(&["body"]).await;
+            server.show(None, &toast(true)).await.unwrap();
+
+            let keys = |shown: &Mutex<Vec<Vec<String>>>| -> Vec<String> {
+                shown.lock().unwrap()[0]
+                    .iter()
+                    .step_by(2)
+                    .cloned()
+                    .collect()
+            };
+            assert_eq!(
+                keys(&shown),
+                ["default", "inline-reply", "mark-read", "mute"]
+            );
+            assert_eq!(keys(&plain), ["default", "reply", "mark-read"]);
+            assert!(keys(&none).is_empty());
+        });
+    }
+
+    #[test]
+    fn actions_replies_and_dismissals_become_events() {
+        tauri::async_runtime::block_on(async {
+            let (mock, server, _) = connect(&["actions", "inline-reply"]).await;
+            let events = listen(&mock, &server, 6, || async {
+                for key in ["default", "reply", "inline-reply", "mark-read", "mute"] {
+                    emit(&mock, "ActionInvoked", &(7u32, key)).await;
+                }
+                emit(&mock, "NotificationReplied", &(7u32, "on my way")).await;
+                // Expired (1) and closed by us (3) aren't the user's doing.
+                emit(&mock, "NotificationClosed", &(7u32, 1u32)).await;
+                emit(&mock, "NotificationClosed", &(7u32, 3u32)).await;
+                emit(&mock, "NotificationClosed", &(7u32, DISMISSED)).await;
+            })
+            .await;
+            // Each signal has its own stream, so order is only kept within one.
+            let (actions, others): (Vec<_>, Vec<_>) = events
+                .into_iter()
+                .partition(|event| matches!(event, Event::Action { .. }));
+            use ActionKind::*;
+            assert_eq!(actions, [Open, Reply, MarkRead, Mute].map(|a| action(7, a)));
+            assert_eq!(others.len(), 2);
+            assert!(others.contains(&Event::Replied {
+                id: 7,
+                text: "on my way".into()
+            }));
+            assert!(others.contains(&Event::Dismissed { id: 7 }));
+        });
+    }
+
+    #[test]
+    fn malformed_signals_are_skipped() {
+        tauri::async_runtime::block_on(async {
+            let (mock, server, _) = connect(&["actions"]).await;
+            let events = listen(&mock, &server, 1, || async {
+                emit(&mock, "ActionInvoked", &("seven", 7u32)).await;
+                emit(&mock, "NotificationReplied", &(7u32,)).await;
+                emit(&mock, "ActionInvoked", &(8u32, "default")).await;
+            })
+            .await;
+            assert_eq!(events, [action(8, ActionKind::Open)]);
+        });
+    }
+}
@@ -0,0 +1,376 @@
+//! Native message notifications, grouped per conversation, with actions.
+//!
+//! The frontend decides what's worth a notification and passes it to
+//! `notification_show`. Messages in the same conversation share one
+//! notification, which is replaced with a running count and the latest few
+//! lines; `notification_clear` removes it once the conversation is read.
+//!
+//! On Linux notifications go straight to `org.freedesktop.Notifications`
+//! (see [`linux`]) with Reply, Mark as read and Mute channel buttons where
+//! the server supports actions, and KDE's inline reply where it supports
+//! that. What the user picks comes back as a `notification:action` event
+//! carrying the node, channel and message IDs. Elsewhere, and on Linux
+//! without a notification server, they're plain toasts through the
+//! notification plugin, which can't report activations.
+//!
+//! During quiet hours messages are held back unless they're exceptions; see
+//! [`crate::quiet_hours`].
+
+#[cfg(target_os = "linux")]
+mod linux;
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, MutexGuard};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+use tauri_plugin_notification::NotificationExt;
+
+use crate::quiet_hours;
+
+/// Lines of recent messages kept in a grouped notification.
+const MAX_LINES: usize = 3;
+/// Longest message preview per line, in characters.
+const MAX_LINE_LENGTH: usize = 120;
+
+/// A channel in a node, or a DM conversation.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Conversation {
+    /// `None` for DMs.
+    pub node_id: Option<String>,
+    /// The channel, or the DM conversation ID.
+    pub channel_id: String,
+}
+
+/// Why a message notifies, as in the frontend's `NotificationType`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationKind {
+    Mention,
+    Dm,
+    Reply,
+    RoleMention,
+    Everyone,
+    Here,
+}
+
+impl NotificationKind {
+    const ALL: [NotificationKind; 6] = [
+        Self::Mention,
+        Self::Dm,
+        Self::Reply,
+        Self::RoleMention,
+        Self::Everyone,
+        Self::Here,
+    ];
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Mention => "mention",
+            Self::Dm => "dm",
+            Self::Reply => "reply",
+            Self::RoleMention => "role_mention",
+            Self::Everyone => "everyone",
+            Self::Here => "here",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.as_str() == name)
+    }
+}
+
+/// A message to notify about.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageNotification {
+    #[serde(flatten)]
+    pub conversation: Conversation,
+    pub message_id: String,
+    #[serde(rename = "type")]
+    pub kind: NotificationKind,
+    /// Public key of the message's author.
+    pub author_key: String,
+    /// The conversation's name, e.g. `#general` or the other person's name.
+    pub title: String,
+    pub author: String,
+    pub body: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ActionKind {
+    /// The notification itself was clicked.
+    Open,
+    /// Reply, with `text` if it was typed into the notification.
+    Reply,
+    MarkRead,
+    Mute,
+}
+
+/// Payload of `notification:action`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationAction {
+    pub action: ActionKind,
+    #[serde(flatten)]
+    pub conversation: Conversation,
+    /// The latest message in the notification.
+    pub message_id: String,
+    pub text: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum NotificationError {
+    #[cfg(target_os = "linux")]
+    #[error("Failed to show notification: {0}")]
+    DBus(#[from] zbus::Error),
+    #[error("Failed to show notification: {0}")]
+    Plugin(#[from] tauri_plugin_notification::Error),
+}
+
+/// What to display for a conversation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Toast {
+    pub summary: String,
+    pub body: String,
+    /// Offer Mute channel; DMs can't be muted from a notification.
+    pub can_mute: bool,
+    /// Hint for an inline reply field.
+    pub reply_placeholder: String,
+    /// Offer actions at all; `false` for notices that aren't a conversation.
+    pub interactive: bool,
+}
+
+#[derive(Default)]
+struct Group {
+    title: String,
+    lines: VecDeque<String>,
+    count: u32,
+    message_id: String,
+    /// The notification currently showing, if the platform gives IDs.
+    id: Option<u32>,
+}
+
+impl Group {
+    fn toast(&self, conversation: &Conversation) -> Toast {
+        let summary = match self.count {
+            1 => self.title.clone(),
+            n => format!("{} ({n} new messages)", self.title),
+        };
+        Toast {
+            summary,
+            body: Vec::from(self.lines.clone()).join("\n"),
+            can_mute: conversation.node_id.is_some(),
+            reply_placeholder: format!("Reply to {}", self.title),
+            interactive: true,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Groups {
+    by_conversation: HashMap<Conversation, Group>,
+    by_id: HashMap<u32, Conversation>,
+}
+
+impl Groups {
+    fn remove(&mut self, conversation: &Conversation) -> Option<Group> {
+        let group = self.by_conversation.remove(conversation)?;
+        if let Some(id) = group.id {
+            self.by_id.remove(&id);
+        }
+        Some(group)
+    }
+}
+
+/// Notifications currently showing. One instance is managed by Tauri.
+#[derive(Default)]
+pub struct Notifier {
+    groups: Mutex<Groups>,
+    #[cfg(target_os = "linux")]
+    server: std::sync::OnceLock<linux::Server>,
+}
+
+impl Notifier {
+    /// Add `message` to its conversation's notification and show it, unless
+    /// quiet hours hold it back.
+    pub async fn show<R: Runtime>(
+        &self,
+        app: &AppHandle<R>,
+        message: MessageNotification,
+    ) -> Result<(), NotificationError> {
+        if !quiet_hours::admit(app, &message) {
+            return Ok(());
+        }
+        let conversation = message.conversation;
+        let (toast, replaces) = {
+            let mut groups = self.lock();
+            let group = groups
+                .by_conversation
+                .entry(conversation.clone())
+                .or_default();
+            group.title = message.title;
+            group.count += 1;
+            group.message_id = message.message_id;
+            group.lines.push_back(line(&message.author, &message.body));
+            if group.lines.len() > MAX_LINES {
+                group.lines.pop_front();
+            }
+            (group.toast(&conversation), group.id)
+        };
+
+        #[cfg(target_os = "linux")]
+        if let Some(server) = self.server.get() {
+            let id = server.show(replaces, &toast).await?;
+            let mut groups = self.lock();
+            if let Some(group) = groups.by_conversation.get_mut(&conversation) {
+                group.id = Some(id);
+                groups.by_id.insert(id, conversation);
+            }
+            return Ok(());
+        }
+        let _ = replaces;
+
+        app.notification()
+            .builder()
+            .title(toast.summary)
+            .body(toast.body)
+            .show()?;
+        Ok(())
+    }
+
+    /// Show a one-off notice without actions, such as the quiet hours
+    /// summary.
+    pub async fn show_plain<R: Runtime>(
+        &self,
+        app: &AppHandle<R>,
+        summary: &str,
+        body: &str,
+    ) -> Result<(), NotificationError> {
+        let toast = Toast {
+            summary: summary.to_string(),
+            body: body.to_string(),
+            can_mute: false,
+            reply_placeholder: String::new(),
+            interactive: false,
+        };
+        #[cfg(target_os = "linux")]
+        if let Some(server) = self.server.get() {
+            server.show(None, &toast).await?;
+            return Ok(());
+        }
+        app.notification()
+            .builder()
+            .title(toast.summary)
+            .body(toast.body)
+            .show()?;
+        Ok(())
+    }
+
+    /// Remove the conversation's notification, e.g. once it has been read.
+    pub async fn clear(&self, conversation: &Conversation) -> Result<(), NotificationError> {
+        let group = self.lock().remove(conversation);
+        #[cfg(target_os = "linux")]
+        if let (Some(server), Some(id)) = (self.server.get(), group.and_then(|g| g.id)) {
+            server.close(id).await?;
+        }
+        #[cfg(not(target_os = "linux"))]
+        let _ = group;
+        Ok(())
+    }
+
+    /// Report what the user picked on notification `id` to the frontend.
+    fn activated<R: Runtime>(
+        &self,
+        app: &AppHandle<R>,
+        id: u32,
+        action: ActionKind,
+        text: Option<String>,
+    ) {
+        let (conversation, group) = {
+            let mut groups = self.lock();
+            let Some(conversation) = groups.by_id.get(&id).cloned() else {
+                return;
+            };
+            let Some(group) = groups.remove(&conversation) else {
+                return;
+            };
+            (conversation, group)
+        };
+        if action == ActionKind::Open || (action == ActionKind::Reply && text.is_none()) {
+            if let Some(window) = app.get_webview_window("main") {
+                let _ = window.show();
+                let _ = window.unminimize();
+                let _ = window.set_focus();
+            }
+        }
+        let _ = app.emit(
+            "notification:action",
+            NotificationAction {
+                action,
+                conversation,
+                message_id: group.message_id,
+                text,
+            },
+        );
+    }
+
+    /// Forget notification `id` after the user dismissed it, so the next
+    /// message starts a new count.
+    fn dismissed(&self, id: u32) {
+        let mut groups = self.lock();
+        if let Some(conversation) = groups.by_id.get(&id).cloned() {
+            groups.remove(&conversation);
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Groups> {
+        self.groups.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn line(author: &str, body: &str) -> String {
+    let body = body.split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut preview: String = body.chars().take(MAX_LINE_LENGTH).collect();
+    if preview.len() < body.len() {
+        preview.push('…');
+    }
+    format!("{author}: {preview}")
+}
+
+/// Connect to the platform's notification server, if it has one we talk to
+/// directly, and listen for activations.
+pub fn start<R: Runtime>(app: &AppHandle<R>) {
+    #[cfg(target_os = "linux")]
+    {
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            let notifier = app.state::<Notifier>();
+            let server = match linux::Server::connect().await {
+                Ok(server) => notifier.server.get_or_init(|| server),
+                Err(e) => {
+                    eprintln!("[notifications] No notification server, using plain toasts: {e}");
+                    return;
+                }
+            };
+            let result = server
+                .listen(|event| match event {
+                    linux::Event::Action { id, action } => {
+                        notifier.activated(&app, id, action, None)
+                    }
+                    linux::Event::Replied { id, text } => {
+                        notifier.activated(&app, id, ActionKind::Reply, Some(text))
+                    }
+                    linux::Event::Dismissed { id } => notifier.dismissed(id),
+                })
+                .await;
+            if let Err(e) = result {
+                eprintln!("[notifications] Stopped listening for notification actions: {e}");
+            }
+        });
+    }
+    #[cfg(not(target_os = "linux"))]
+    let _ = app;
+}
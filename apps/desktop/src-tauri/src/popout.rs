@@ -0,0 +1,311 @@
+//! Pop-out windows: a channel or DM in its own window, and the voice
+//! overlay.
+//!
+//! A pop-out loads the same frontend as the main window and asks
+//! `popout_target` what to show. Labels are derived from the target
+//! (`channel-…`, `dm-…`, `voice-overlay`), so opening something that's
+//! already popped out focuses its window instead, and each class of window
+//! gets its own capability in `capabilities/`, matched by label. Pop-outs
+//! may only call the few commands their capability lists.
+//!
+//! State stays in the main window. It publishes updates to pop-outs, and
+//! pop-outs send actions back, through `popout_publish`: an update scoped to
+//! a target reaches the main window and that target's window, an unscoped
+//! one reaches every window, and neither goes back to the sender.
+//!
+//! The open pop-outs are saved to `popouts.json` and reopened on the next
+//! launch. Windows still open when the app quits count as open; only
+//! closing one removes it.
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tauri::{
+    AppHandle, Emitter, Manager, Runtime, WebviewUrl, WebviewWindow, WebviewWindowBuilder,
+    WindowEvent,
+};
+
+use crate::durable;
+use crate::shutdown::Shutdown;
+use crate::window_state;
+
+pub const MAIN_LABEL: &str = "main";
+const VOICE_OVERLAY_LABEL: &str = "voice-overlay";
+
+/// What a pop-out shows, serialized as `{ "kind": "channel", ... }`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(
+    tag = "kind",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum Popout {
+    Channel {
+        node_id: String,
+        channel_id: String,
+    },
+    Dm {
+        conversation_id: String,
+        recipient_key: String,
+    },
+    VoiceOverlay,
+}
+
+impl Popout {
+    /// The window label. IDs are hashed, since labels only allow a few
+    /// characters.
+    pub fn label(&self) -> String {
+        match self {
+            Popout::Channel {
+                node_id,
+                channel_id,
+            } => format!("channel-{}", digest(&[node_id, channel_id])),
+            Popout::Dm {
+                conversation_id, ..
+            } => format!("dm-{}", digest(&[conversation_id])),
+            Popout::VoiceOverlay => VOICE_OVERLAY_LABEL.to_string(),
+        }
+    }
+}
+
+fn digest(parts: &[&str]) -> String {
+    let digest = Sha256::digest(parts.join("\0").as_bytes());
+    digest[..8].iter().map(|b| format!("{b:02x}")).collect()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenPopout {
+    pub label: String,
+    pub target: Popout,
+    pub title: String,
+}
+
+/// State to pass between windows.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StateUpdate {
+    /// The pop-out this concerns; `None` for every window.
+    pub scope: Option<Popout>,
+    pub topic: String,
+    pub payload: serde_json::Value,
+}
+
+/// Payload of `popout:state`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct RoutedUpdate<'a> {
+    /// Label of the window that published it.
+    source: &'a str,
+    scope: &'a Option<Popout>,
+    topic: &'a str,
+    payload: &'a serde_json::Value,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum PopoutError {
+    #[error("Failed to open window: {0}")]
+    Window(#[from] tauri::Error),
+    #[error("Failed to save open windows: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to save open windows: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// The open pop-outs. One instance is managed by Tauri.
+pub struct Popouts {
+    path: PathBuf,
+    open: Mutex<Vec<OpenPopout>>,
+}
+
+impl Popouts {
+    /// Load the pop-outs that were open last time from `path`, starting
+    /// with none if the file is missing or unreadable.
+    pub fn load(path: PathBuf) -> Self {
+        let open = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+                eprintln!("[popout] Ignoring unreadable list of open windows: {e}");
+                Vec::new()
+            }),
+            Err(e) if e.kind() == ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                eprintln!("[popout] Failed to read open windows: {e}");
+                Vec::new()
+            }
+        };
+        Self {
+            path,
+            open: Mutex::new(open),
+        }
+    }
+
+    pub fn list(&self) -> Vec<OpenPopout> {
+        self.lock().clone()
+    }
+
+    /// The target of the pop-out labeled `label`.
+    pub fn target(&self, label: &str) -> Option<Popout> {
+        self.lock()
+            .iter()
+            .find(|popout| popout.label == label)
+            .map(|popout| popout.target.clone())
+    }
+
+    fn insert(&self, popout: OpenPopout) -> Result<(), PopoutError> {
+        let mut open = self.lock();
+        open.retain(|p| p.label != popout.label);
+        open.push(popout);
+        self.save(&open)
+    }
+
+    fn remove(&self, label: &str) -> Result<(), PopoutError> {
+        let mut open = self.lock();
+        open.retain(|p| p.label != label);
+        self.save(&open)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<OpenPopout>> {
+        self.open.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn save(&self, open: &[OpenPopout]) -> Result<(), PopoutError> {
+        durable::write_atomic(&self.path, &serde_json::to_vec_pretty(open)?)?;
+        Ok(())
+    }
+}
+
+/// Open `target` in its own window, or focus the window already showing it.
+///
+/// On Windows, creating a window from the main thread while it's handling
+/// an event deadlocks, so call this from an async command or a spawned task.
+pub fn open<R: Runtime>(
+    app: &AppHandle<R>,
+    target: Popout,
+    title: Option<String>,
+) -> Result<WebviewWindow<R>, PopoutError> {
+    if let Some(window) = focus(app, &target) {
+        return Ok(window);
+    }
+    let label = target.label();
+    let title = title.unwrap_or_else(|| "Nodes".to_string());
+    let builder = WebviewWindowBuilder::new(app, &label, WebviewUrl::default())
+        .title(&title)
+        .visible(false);
+    let builder = match target {
+        Popout::VoiceOverlay => builder
+            .inner_size(320.0, 200.0)
+            .resizable(false)
+            .decorations(false)
+            .always_on_top(true)
+            .skip_taskbar(true),
+        Popout::Channel { .. } | Popout::Dm { .. } => builder
+            .inner_size(900.0, 640.0)
+            .min_inner_size(480.0, 360.0)
+            .center(),
+    };
+    // Listed before the window exists, so `popout_target` has an answer as
+    // soon as the page loads.
+    let popouts = app.state::<Popouts>();
+    popouts.insert(OpenPopout {
+        label: label.clone(),
+        target,
+        title,
+    })?;
+    let window = match builder.build() {
+        Ok(window) => window,
+        Err(e) => {
+            let _ = popouts.remove(&label);
+            return Err(e.into());
+        }
+    };
+    window_state::restore(&window);
+    window_state::track(&window);
+
+    let app_handle = app.clone();
+    let closed = label.clone();
+    window.on_window_event(move |event| {
+        // Windows torn down by quitting stay in the list to be reopened.
+        if matches!(event, WindowEvent::Destroyed) && !app_handle.state::<Shutdown>().is_finished()
+        {
+            if let Err(e) = app_handle.state::<Popouts>().remove(&closed) {
+                eprintln!("[popout] {e}");
+            }
+            changed(&app_handle);
+        }
+    });
+    window.show()?;
+    window.set_focus()?;
+    changed(app);
+    Ok(window)
+}
+
+/// Focus the pop-out showing `target`, if there is one.
+pub fn focus<R: Runtime>(app: &AppHandle<R>, target: &Popout) -> Option<WebviewWindow<R>> {
+    let window = app.get_webview_window(&target.label())?;
+    let _ = window.show();
+    let _ = window.unminimize();
+    let _ = window.set_focus();
+    Some(window)
+}
+
+pub fn close<R: Runtime>(app: &AppHandle<R>, target: &Popout) -> tauri::Result<()> {
+    match app.get_webview_window(&target.label()) {
+        Some(window) => window.close(),
+        None => Ok(()),
+    }
+}
+
+/// Deliver `update` from the window labeled `source` as `popout:state`.
+pub fn publish<R: Runtime>(app: &AppHandle<R>, source: &str, update: &StateUpdate) {
+    let labels: Vec<String> = match &update.scope {
+        Some(scope) => vec![MAIN_LABEL.to_string(), scope.label()],
+        None => app.webview_windows().into_keys().collect(),
+    };
+    let routed = RoutedUpdate {
+        source,
+        scope: &update.scope,
+        topic: &update.topic,
+        payload: &update.payload,
+    };
+    for label in labels.iter().filter(|label| *label != source) {
+        let _ = app.emit_to(label.as_str(), "popout:state", &routed);
+    }
+}
+
+/// Reopen the pop-outs from last time once the main window is visible, so
+/// starting hidden in the tray keeps them hidden too.
+pub fn restore<R: Runtime>(main_window: &WebviewWindow<R>) {
+    let app = main_window.app_handle().clone();
+    if main_window.is_visible().unwrap_or(false) {
+        reopen(&app);
+        return;
+    }
+    let restored = AtomicBool::new(false);
+    main_window.on_window_event(move |event| {
+        if matches!(event, WindowEvent::Focused(true)) && !restored.swap(true, Ordering::Relaxed) {
+            // Not from the event handler; see `open`.
+            let app = app.clone();
+            tauri::async_runtime::spawn(async move { reopen(&app) });
+        }
+    });
+}
+
+fn reopen<R: Runtime>(app: &AppHandle<R>) {
+    let popouts = app.state::<Popouts>();
+    for popout in popouts.list() {
+        if let Err(e) = open(app, popout.target, Some(popout.title)) {
+            eprintln!("[popout] Failed to reopen {}: {e}", popout.label);
+            let _ = popouts.remove(&popout.label);
+        }
+    }
+}
+
+/// Tell every window which pop-outs are open, as `popout:changed`.
+fn changed<R: Runtime>(app: &AppHandle<R>) {
+    let _ = app.emit("popout:changed", app.state::<Popouts>().list());
+}
@@ -0,0 +1,596 @@
+//! Quiet hours: weekly windows during which notifications are held back.
+//!
+//! The schedule is part of the settings: windows given as days of the week
+//! and local start and end times, in an IANA time zone (the system's by
+//! default). A window whose end isn't after its start runs past midnight.
+//! Times are resolved in the zone on each day, so a window keeps its wall
+//! clock times across DST changes; a time skipped by a spring-forward gap
+//! moves to just after the gap, and a repeated time uses its first
+//! occurrence.
+//!
+//! While a window is active, notifications that aren't exceptions (DMs from
+//! favorites, and direct mentions if allowed; never @everyone or @here) are
+//! queued instead of shown. When it ends, the queue is delivered as one
+//! summary notification and a `quiet-hours:summary` event. Starting and
+//! ending emit `quiet-hours:changed`, and if the schedule asks for it,
+//! `presence:auto-dnd` and `presence:auto-dnd-end` so the frontend can
+//! switch the user's status.
+//!
+//! The schedule is checked every [`TICK`] and right after the settings
+//! change, which also covers clock and time zone changes.
+
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+use jiff::civil::{Date, Time, Weekday};
+use jiff::tz::TimeZone;
+use jiff::Timestamp;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+
+use crate::notifications::{Conversation, MessageNotification, NotificationKind, Notifier};
+use crate::settings::Settings;
+
+/// How often the schedule is checked.
+const TICK: Duration = Duration::from_secs(30);
+/// Conversation names listed in the summary before "and N more".
+const SUMMARY_NAMES: usize = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum Day {
+    Monday,
+    Tuesday,
+    Wednesday,
+    Thursday,
+    Friday,
+    Saturday,
+    Sunday,
+}
+
+impl From<Weekday> for Day {
+    fn from(weekday: Weekday) -> Self {
+        match weekday {
+            Weekday::Monday => Day::Monday,
+            Weekday::Tuesday => Day::Tuesday,
+            Weekday::Wednesday => Day::Wednesday,
+            Weekday::Thursday => Day::Thursday,
+            Weekday::Friday => Day::Friday,
+            Weekday::Saturday => Day::Saturday,
+            Weekday::Sunday => Day::Sunday,
+        }
+    }
+}
+
+/// A recurring window, e.g. 22:00 to 07:00 starting Sunday to Thursday.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuietWindow {
+    /// Days the window starts on.
+    pub days: Vec<Day>,
+    pub start: Time,
+    /// On the next day if it isn't after `start`.
+    pub end: Time,
+}
+
+impl QuietWindow {
+    /// When the window starting on `date` begins and ends, or `None` if it
+    /// doesn't start that day.
+    fn on(&self, date: Date, tz: &TimeZone) -> Option<(Timestamp, Timestamp)> {
+        if !self.days.contains(&date.weekday().into()) {
+            return None;
+        }
+        let end_date = if self.end <= self.start {
+            date.tomorrow().ok()?
+        } else {
+            date
+        };
+        let start = tz.to_zoned(date.to_datetime(self.start)).ok()?.timestamp();
+        let end = tz
+            .to_zoned(end_date.to_datetime(self.end))
+            .ok()?
+            .timestamp();
+        (start < end).then_some((start, end))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct QuietSchedule {
+    pub enabled: bool,
+    /// IANA name such as `Europe/Berlin`; `None` follows the system.
+    pub time_zone: Option<String>,
+    pub windows: Vec<QuietWindow>,
+    /// Public keys whose DMs still notify.
+    pub favorites: Vec<String>,
+    /// Let direct mentions and replies through.
+    pub allow_mentions: bool,
+    /// Ask the frontend to switch to Do Not Disturb for the duration.
+    pub set_dnd: bool,
+}
+
+impl Default for QuietSchedule {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            time_zone: None,
+            windows: Vec::new(),
+            favorites: Vec::new(),
+            allow_mentions: false,
+            set_dnd: true,
+        }
+    }
+}
+
+impl QuietSchedule {
+    /// The zone windows are in, falling back to the system's if the name
+    /// is unknown.
+    pub fn time_zone(&self) -> TimeZone {
+        match &self.time_zone {
+            Some(name) => TimeZone::get(name).unwrap_or_else(|e| {
+                eprintln!("[quiet-hours] Using the system time zone: {e}");
+                TimeZone::system()
+            }),
+            None => TimeZone::system(),
+        }
+    }
+
+    /// If quiet hours are on at `now`, when they end. Windows that overlap
+    /// or touch count as one.
+    pub fn quiet_until(&self, tz: &TimeZone, now: Timestamp) -> Option<Timestamp> {
+        if !self.enabled {
+            return None;
+        }
+        let mut until: Option<Timestamp> = None;
+        // Each round extends past the end found so far; a week of back to
+        // back windows is as far as it can go.
+        for _ in 0..8 {
+            let at = until.unwrap_or(now);
+            let today = tz.to_datetime(at).date();
+            let end = [today.yesterday().ok(), Some(today)]
+                .into_iter()
+                .flatten()
+                .flat_map(|date| self.windows.iter().filter_map(move |w| w.on(date, tz)))
+                .filter(|&(start, end)| start <= at && at < end)
+                .map(|(_, end)| end)
+                .max();
+            match end {
+                Some(end) if until.is_none_or(|until| end > until) => until = Some(end),
+                _ => break,
+            }
+        }
+        until
+    }
+
+    /// Whether `message` gets through while quiet hours are on.
+    pub fn is_exception(&self, message: &MessageNotification) -> bool {
+        match message.kind {
+            NotificationKind::Dm => self.favorites.contains(&message.author_key),
+            NotificationKind::Mention | NotificationKind::Reply | NotificationKind::RoleMention => {
+                self.allow_mentions
+            }
+            NotificationKind::Everyone | NotificationKind::Here => false,
+        }
+    }
+}
+
+/// A conversation with notifications held back.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Held {
+    #[serde(flatten)]
+    pub conversation: Conversation,
+    pub title: String,
+    pub count: u32,
+    /// The latest message held back.
+    pub message_id: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuietStatus {
+    pub active: bool,
+    /// Unix milliseconds.
+    pub ends_at: Option<i64>,
+    /// Notifications held back so far.
+    pub held: u32,
+}
+
+/// Whether quiet hours are on, and what they held back. One instance is
+/// managed by Tauri.
+#[derive(Default)]
+pub struct QuietHours {
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    until: Option<Timestamp>,
+    held: Vec<Held>,
+}
+
+impl QuietHours {
+    pub fn status(&self) -> QuietStatus {
+        let state = self.lock();
+        QuietStatus {
+            active: state.until.is_some(),
+            ends_at: state.until.map(|until| until.as_millisecond()),
+            held: state.held.iter().map(|held| held.count).sum(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Whether to show `message` now. If not, it's held for the summary.
+pub fn admit<R: Runtime>(app: &AppHandle<R>, message: &MessageNotification) -> bool {
+    let quiet_hours = app.state::<QuietHours>();
+    let mut state = quiet_hours.lock();
+    if state.until.is_none()
+        || app
+            .state::<Settings>()
+            .get()
+            .quiet_hours
+            .is_exception(message)
+    {
+        return true;
+    }
+    match state
+        .held
+        .iter_mut()
+        .find(|held| held.conversation == message.conversation)
+    {
+        Some(held) => {
+            held.count += 1;
+            held.title.clone_from(&message.title);
+            held.message_id.clone_from(&message.message_id);
+        }
+        None => state.held.push(Held {
+            conversation: message.conversation.clone(),
+            title: message.title.clone(),
+            count: 1,
+            message_id: message.message_id.clone(),
+        }),
+    }
+    false
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct AutoDnd {
+    ends_at: i64,
+}
+
+/// Check the schedule now and act on a change.
+pub fn refresh<R: Runtime>(app: &AppHandle<R>) {
+    let schedule = app.state::<Settings>().get().quiet_hours;
+    let until = schedule.quiet_until(&schedule.time_zone(), Timestamp::now());
+    let quiet_hours = app.state::<QuietHours>();
+    let (was, held) = {
+        let mut state = quiet_hours.lock();
+        if state.until == until {
+            return;
+        }
+        let was = std::mem::replace(&mut state.until, until);
+        let held = match until {
+            Some(_) => Vec::new(),
+            None => std::mem::take(&mut state.held),
+        };
+        (was, held)
+    };
+
+    let _ = app.emit("quiet-hours:changed", quiet_hours.status());
+    match (was, until) {
+        (None, Some(until)) if schedule.set_dnd => {
+            let _ = app.emit(
+                "presence:auto-dnd",
+                AutoDnd {
+                    ends_at: until.as_millisecond(),
+                },
+            );
+        }
+        (Some(_), None) => {
+            if schedule.set_dnd {
+                let _ = app.emit("presence:auto-dnd-end", ());
+            }
+            deliver_summary(app, held);
+        }
+        _ => {}
+    }
+}
+
+fn deliver_summary<R: Runtime>(app: &AppHandle<R>, held: Vec<Held>) {
+    if held.is_empty() {
+        return;
+    }
+    let _ = app.emit("quiet-hours:summary", &held);
+    let body = summary(&held);
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let notifier = app.state::<Notifier>();
+        if let Err(e) = notifier.show_plain(&app, "Quiet hours ended", &body).await {
+            eprintln!("[quiet-hours] {e}");
+        }
+    });
+}
+
+/// E.g. "7 messages in #general, Alice, #random and 2 more".
+fn summary(held: &[Held]) -> String {
+    let total: u32 = held.iter().map(|held| held.count).sum();
+    let names: Vec<&str> = held
+        .iter()
+        .take(SUMMARY_NAMES)
+        .map(|held| held.title.as_str())
+        .collect();
+    let mut body = format!(
+        "{total} {} in {}",
+        if total == 1 { "message" } else { "messages" },
+        names.join(", ")
+    );
+    if held.len() > SUMMARY_NAMES {
+        body.push_str(&format!(" and {} more", held.len() - SUMMARY_NAMES));
+    }
+    body
+}
+
+/// Check the schedule every [`TICK`] from now on.
+pub fn start<R: Runtime>(app: &AppHandle<R>) {
+    let app = app.clone();
+    std::thread::spawn(move || loop {
+        refresh(&app);
+        std::thread::sleep(TICK);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use jiff::civil::{date, time};
+
+    use super::*;
+
+    fn new_york() -> TimeZone {
+        TimeZone::get("America/New_York").unwrap()
+    }
+
+    fn at(instant: &str) -> Timestamp {
+        instant.parse().unwrap()
+    }
+
+    fn window(days: &[Day], start: Time, end: Time) -> QuietWindow {
+        QuietWindow {
+            days: days.to_vec(),
+            start,
+            end,
+        }
+    }
+
+    fn schedule(windows: Vec<QuietWindow>) -> QuietSchedule {
+        QuietSchedule {
+            enabled: true,
+            time_zone: Some("America/New_York".into()),
+            windows,
+            ..Default::default()
+        }
+    }
+
+    fn message(kind: NotificationKind, author_key: &str) -> MessageNotification {
+        MessageNotification {
+            conversation: Conversation {
+                node_id: None,
+                channel_id: "conversation".into(),
+            },
+            message_id: "message".into(),
+            kind,
+            author_key: author_key.into(),
+            title: "Alice".into(),
+            author: "Alice".into(),
+            body: "hi".into(),
+        }
+    }
+
+    // Clocks in New York skip 02:00-03:00 on 2026-03-08 and repeat
+    // 01:00-02:00 on 2026-11-01, both Sundays.
+
+    #[test]
+    fn start_in_the_gap_moves_past_it() {
+        let tz = new_york();
+        let quiet = window(&[Day::Sunday], time(2, 30, 0, 0), time(4, 0, 0, 0));
+        assert_eq!(
+            quiet.on(date(2026, 3, 8), &tz),
+            Some((at("2026-03-08T03:30-04:00"), at("2026-03-08T04:00-04:00")))
+        );
+        // Skipped entirely: 02:30 becomes 03:30, after the end.
+        let skipped = window(&[Day::Sunday], time(2, 30, 0, 0), time(3, 0, 0, 0));
+        assert_eq!(skipped.on(date(2026, 3, 8), &tz), None);
+        // A week later the same window is back to normal.
+        assert_eq!(
+            quiet.on(date(2026, 3, 15), &tz),
+            Some((at("2026-03-15T02:30-04:00"), at("2026-03-15T04:00-04:00")))
+        );
+    }
+
+    #[test]
+    fn repeated_times_use_the_first_occurrence() {
+        let tz = new_york();
+        let quiet = window(&[Day::Sunday], time(1, 0, 0, 0), time(1, 30, 0, 0));
+        assert_eq!(
+            quiet.on(date(2026, 11, 1), &tz),
+            Some((at("2026-11-01T01:00-04:00"), at("2026-11-01T01:30-04:00")))
+        );
+        let schedule = schedule(vec![quiet]);
+        assert_eq!(
+            schedule.quiet_until(&tz, at("2026-11-01T01:15-04:00")),
+            Some(at("2026-11-01T01:30-04:00"))
+        );
+        // The second 01:15 isn't quiet.
+        assert_eq!(
+            schedule.quiet_until(&tz, at("2026-11-01T01:15-05:00")),
+            None
+        );
+    }
+
+    #[test]
+    fn window_spanning_the_fold_ends_on_the_second_pass() {
+        let tz = new_york();
+        let schedule = schedule(vec![window(
+            &[Day::Sunday],
+            time(1, 30, 0, 0),
+            time(2, 30, 0, 0),
+        )]);
+        // Two hours long: 01:30 EDT to 02:30 EST.
+        for now in [
+            "2026-11-01T01:30-04:00",
+            "2026-11-01T01:45-05:00",
+            "2026-11-01T02:29-05:00",
+        ] {
+            assert_eq!(
+                schedule.quiet_until(&tz, at(now)),
+                Some(at("2026-11-01T02:30-05:00")),
+                "{now}"
+            );
+        }
+        assert_eq!(
+            schedule.quiet_until(&tz, at("2026-11-01T01:29-04:00")),
+            None
+        );
+    }
+
+    #[test]
+    fn overnight_windows() {
+        let tz = new_york();
+        let schedule = schedule(vec![window(
+            &[Day::Friday],
+            time(22, 0, 0, 0),
+            time(7, 0, 0, 0),
+        )]);
+        let until = Some(at("2026-10-17T07:00-04:00"));
+        assert_eq!(
+            schedule.quiet_until(&tz, at("2026-10-16T21:59-04:00")),
+            None
+        );
+        assert_eq!(
+            schedule.quiet_until(&tz, at("2026-10-16T22:00-04:00")),
+            until
+        );
+        // After midnight the window is found from the day before.
+        assert_eq!(
+            schedule.quiet_until(&tz, at("2026-10-17T03:00-04:00")),
+            until
+        );
+        assert_eq!(
+            schedule.quiet_until(&tz, at("2026-10-17T07:00-04:00")),
+            None
+        );
+        // Saturday night isn't in it.
+        assert_eq!(
+            schedule.quiet_until(&tz, at("2026-10-17T23:00-04:00")),
+            None
+        );
+    }
+
+    #[test]
+    fn overnight_window_across_spring_forward() {
+        let tz = new_york();
+        let schedule = schedule(vec![window(
+            &[Day::Saturday],
+            time(22, 0, 0, 0),
+            time(7, 0, 0, 0),
+        )]);
+        // Eight hours rather than nine, ending at 07:00 on the wall clock.
+        let until = Some(at("2026-03-08T07:00-04:00"));
+        assert_eq!(
+            schedule.quiet_until(&tz, at("2026-03-07T22:00-05:00")),
+            until
+        );
+        assert_eq!(
+            schedule.quiet_until(&tz, at("2026-03-08T01:59-05:00")),
+            until
+        );
+        assert_eq!(
+            schedule.quiet_until(&tz, at("2026-03-08T03:00-04:00")),
+            until
+        );
+        assert_eq!(
+            schedule.quiet_until(&tz, at("2026-03-08T07:00-04:00")),
+            None
+        );
+    }
+
+    #[test]
+    fn touching_windows_count_as_one() {
+        let tz = new_york();
+        let schedule = schedule(vec![
+            window(&[Day::Monday], time(9, 0, 0, 0), time(12, 0, 0, 0)),
+            window(&[Day::Monday], time(12, 0, 0, 0), time(17, 0, 0, 0)),
+            // Runs into Tuesday morning and on into a Tuesday window.
+            window(&[Day::Monday], time(17, 0, 0, 0), time(8, 0, 0, 0)),
+            window(&[Day::Tuesday], time(8, 0, 0, 0), time(9, 0, 0, 0)),
+        ]);
+        let until = Some(at("2026-10-20T09:00-04:00"));
+        assert_eq!(
+            schedule.quiet_until(&tz, at("2026-10-19T10:00-04:00")),
+            until
+        );
+        assert_eq!(
+            schedule.quiet_until(&tz, at("2026-10-19T12:00-04:00")),
+            until
+        );
+        assert_eq!(
+            schedule.quiet_until(&tz, at("2026-10-20T08:30-04:00")),
+            until
+        );
+        assert_eq!(
+            schedule.quiet_until(&tz, at("2026-10-20T09:00-04:00")),
+            None
+        );
+    }
+
+    #[test]
+    fn disabled_schedule_is_never_quiet() {
+        let tz = new_york();
+        let mut schedule = schedule(vec![window(
+            &[Day::Friday],
+            time(0, 0, 0, 0),
+            time(0, 0, 0, 0),
+        )]);
+        let now = at("2026-10-16T12:00-04:00");
+        assert_eq!(
+            schedule.quiet_until(&tz, now),
+            Some(at("2026-10-17T00:00-04:00"))
+        );
+        schedule.enabled = false;
+        assert_eq!(schedule.quiet_until(&tz, now), None);
+    }
+
+    #[test]
+    fn exceptions() {
+        let mut schedule = QuietSchedule {
+            favorites: vec!["favorite".into()],
+            ..Default::default()
+        };
+        assert!(schedule.is_exception(&message(NotificationKind::Dm, "favorite")));
+        assert!(!schedule.is_exception(&message(NotificationKind::Dm, "stranger")));
+        assert!(!schedule.is_exception(&message(NotificationKind::Mention, "favorite")));
+
+        schedule.allow_mentions = true;
+        for kind in [
+            NotificationKind::Mention,
+            NotificationKind::Reply,
+            NotificationKind::RoleMention,
+        ] {
+            assert!(
+                schedule.is_exception(&message(kind, "stranger")),
+                "{kind:?}"
+            );
+        }
+        // Not even from a favorite.
+        for kind in [NotificationKind::Everyone, NotificationKind::Here] {
+            assert!(
+                !schedule.is_exception(&message(kind, "favorite")),
+                "{kind:?}"
+            );
+        }
+    }
+}
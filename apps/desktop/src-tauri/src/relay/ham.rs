@@ -0,0 +1,146 @@
+//! Gun's conflict resolution (HAM) and what counts as a valid value.
+//!
+//! Every field carries a state, the writer's clock in milliseconds. The
+//! higher state wins; on a tie the value that sorts higher as JSON wins, so
+//! every peer converges on the same value whatever order updates arrive in.
+//! States ahead of our clock are deferred until then rather than applied.
+
+use std::cmp::Ordering;
+
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Ham {
+    /// Newer than what we have: store it.
+    Incoming,
+    /// Older, or a tie that loses: keep ours.
+    Current,
+    /// Same state and value: nothing to do.
+    Same,
+    /// From the future: apply once our clock gets there.
+    Defer,
+}
+
+/// Resolve an incoming field against the current one, if any, with
+/// `machine` as our clock.
+pub fn resolve(
+    machine: f64,
+    incoming_state: f64,
+    incoming: &Value,
+    current: Option<(f64, &Value)>,
+) -> Ham {
+    if machine < incoming_state {
+        return Ham::Defer;
+    }
+    let Some((current_state, current)) = current else {
+        return Ham::Incoming;
+    };
+    if incoming_state < current_state {
+        return Ham::Current;
+    }
+    if current_state < incoming_state {
+        return Ham::Incoming;
+    }
+    match lexical(incoming, current) {
+        Ordering::Equal => Ham::Same,
+        Ordering::Less => Ham::Current,
+        Ordering::Greater => Ham::Incoming,
+    }
+}
+
+/// Compare like Gun does, as JSON strings in JavaScript's UTF-16 order.
+fn lexical(a: &Value, b: &Value) -> Ordering {
+    let (a, b) = (a.to_string(), b.to_string());
+    a.encode_utf16().cmp(b.encode_utf16())
+}
+
+/// Gun stores `null`, booleans, finite numbers, strings, and links to other
+/// nodes as `{ "#": soul }`. Anything else is rejected.
+pub fn is_value(value: &Value) -> bool {
+    match value {
+        Value::Null | Value::Bool(_) | Value::String(_) => true,
+        Value::Number(n) => n.as_f64().is_some_and(f64::is_finite),
+        Value::Object(link) => link.len() == 1 && link.get("#").is_some_and(Value::is_string),
+        Value::Array(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    const NOW: f64 = 1_700_000_000_000.0;
+
+    #[test]
+    fn higher_state_wins() {
+        let ours = json!("ours");
+        assert_eq!(resolve(NOW, 10.0, &json!("new"), None), Ham::Incoming);
+        assert_eq!(
+            resolve(NOW, 20.0, &json!("new"), Some((10.0, &ours))),
+            Ham::Incoming
+        );
+        assert_eq!(
+            resolve(NOW, 5.0, &json!("old"), Some((10.0, &ours))),
+            Ham::Current
+        );
+    }
+
+    #[test]
+    fn ties_go_to_the_higher_json() {
+        let a = json!("a");
+        let b = json!("b");
+        assert_eq!(resolve(NOW, 10.0, &a, Some((10.0, &a))), Ham::Same);
+        assert_eq!(resolve(NOW, 10.0, &b, Some((10.0, &a))), Ham::Incoming);
+        assert_eq!(resolve(NOW, 10.0, &a, Some((10.0, &b))), Ham::Current);
+        // Compared as JSON: `"1"` starts with a quote, which sorts below `2`.
+        assert_eq!(
+            resolve(NOW, 10.0, &json!(2), Some((10.0, &json!("1")))),
+            Ham::Incoming
+        );
+        // In UTF-16 order, as in JavaScript: an emoji's high surrogate
+        // (0xD83D) sorts below U+FF61 although its code point is higher.
+        assert_eq!(
+            resolve(
+                NOW,
+                10.0,
+                &json!("\u{1F600}"),
+                Some((10.0, &json!("\u{FF61}")))
+            ),
+            Ham::Current
+        );
+    }
+
+    #[test]
+    fn future_states_are_deferred() {
+        let ours = json!("ours");
+        assert_eq!(resolve(NOW, NOW + 1.0, &json!("x"), None), Ham::Defer);
+        assert_eq!(
+            resolve(NOW, NOW + 1.0, &json!("x"), Some((10.0, &ours))),
+            Ham::Defer
+        );
+        assert_eq!(resolve(NOW, NOW, &json!("x"), None), Ham::Incoming);
+    }
+
+    #[test]
+    fn values() {
+        for value in [
+            json!(null),
+            json!(true),
+            json!(1.5),
+            json!("text"),
+            json!({ "#": "user/alice" }),
+        ] {
+            assert!(is_value(&value), "{value}");
+        }
+        for value in [
+            json!([1]),
+            json!({ "a": 1 }),
+            json!({ "#": 1 }),
+            json!({ "#": "user/alice", "name": "Alice" }),
+        ] {
+            assert!(!is_value(&value), "{value}");
+        }
+    }
+}
@@ -0,0 +1,401 @@
+//! What the relay does with each message, independent of the sockets.
+//!
+//! Gun messages are JSON objects, sent alone or batched in an array. Each
+//! has a random ID under `#`, and replies name the message they answer
+//! under `@`. Messages are deduplicated by ID, since in a mesh the same one
+//! arrives over several paths.
+//!
+//! - `put` is merged into the store, acknowledged to the sender, and, if it
+//!   changed anything, passed on to every other peer so their subscriptions
+//!   see it.
+//! - `get` is answered from the store and passed on to the other peers, whose
+//!   answers are routed back to the asker by their `@`.
+//! - `dam: "?"` is the peer ID handshake, answered with our own ID.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use rand::distributions::{Alphanumeric, DistString};
+use serde_json::{json, Value};
+
+use super::store::{self, Store, Update};
+
+/// How long message IDs are remembered, for dedupe and for routing replies.
+const MEMORY: Duration = Duration::from_secs(9);
+/// Forget expired IDs once this many are remembered.
+const PRUNE_AT: usize = 10_000;
+/// Updates stamped further ahead of our clock than this are dropped rather
+/// than deferred.
+const MAX_DEFER_MS: f64 = 24.0 * 60.0 * 60.0 * 1000.0;
+
+pub type PeerId = u64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum To {
+    Peer(PeerId),
+    AllBut(PeerId),
+    All,
+}
+
+#[derive(Debug, Default)]
+pub struct Received {
+    pub outgoing: Vec<(To, Value)>,
+    /// Updates to merge again with [`Mesh::apply_deferred`] once the latest
+    /// of their states has passed.
+    pub deferred: Vec<Update>,
+}
+
+pub struct Mesh {
+    /// Our peer ID for the `dam` handshake.
+    pid: String,
+    store: Store,
+    seen: Mutex<Recent<()>>,
+    /// Who asked, for messages we passed on.
+    routes: Mutex<Recent<PeerId>>,
+}
+
+impl Mesh {
+    pub fn new(store: Store) -> Self {
+        Self {
+            pid: random_id(),
+            store,
+            seen: Mutex::default(),
+            routes: Mutex::default(),
+        }
+    }
+
+    /// Handle a frame from peer `from`, with `peers` connected in total.
+    pub fn receive(&self, from: PeerId, text: &str, peers: usize) -> Received {
+        let mut received = Received::default();
+        match serde_json::from_str(text) {
+            Ok(Value::Array(batch)) => {
+                for message in batch {
+                    self.handle(from, message, peers, &mut received);
+                }
+            }
+            Ok(message) => self.handle(from, message, peers, &mut received),
+            // Gun sends the odd empty keepalive.
+            Err(_) => {}
+        }
+        received
+    }
+
+    /// Merge updates that were ahead of our clock, and pass on the ones
+    /// that won to every peer.
+    pub fn apply_deferred(&self, updates: Vec<Update>) -> Received {
+        let mut received = Received::default();
+        match self.store.merge(updates, now()) {
+            Ok(merge) => {
+                if !merge.applied.is_empty() {
+                    received.outgoing.push((
+                        To::All,
+                        json!({ "#": random_id(), "put": store::graph(&merge.applied) }),
+                    ));
+                }
+                received.deferred = merge.deferred;
+            }
+            Err(e) => eprintln!("[relay] Failed to store deferred updates: {e}"),
+        }
+        received
+    }
+
+    fn handle(&self, from: PeerId, message: Value, peers: usize, received: &mut Received) {
+        let Some(fields) = message.as_object() else {
+            return;
+        };
+        let id = match fields.get("#").and_then(Value::as_str) {
+            Some(id) => id.to_string(),
+            None => random_id(),
+        };
+        if !lock(&self.seen).insert(id.clone(), ()) {
+            return;
+        }
+        let reply_to = fields.get("@").and_then(Value::as_str).map(str::to_string);
+
+        if fields.get("dam").and_then(Value::as_str) == Some("?") {
+            if reply_to.is_none() {
+                received.outgoing.push((
+                    To::Peer(from),
+                    json!({ "#": random_id(), "@": id, "dam": "?", "pid": self.pid }),
+                ));
+            }
+            return;
+        }
+
+        if let Some(put) = fields.get("put").filter(|put| !put.is_null()) {
+            let merged = match store::updates(put) {
+                Some(updates) => self.store.merge(updates, now()).map_err(|e| {
+                    eprintln!("[relay] Failed to store update: {e}");
+                    "Failed to store update"
+                }),
+                None => Err("Invalid graph"),
+            };
+            let (changed, ack) = match merged {
+                Ok(merge) => {
+                    let limit = now() + MAX_DEFER_MS;
+                    received
+                        .deferred
+                        .extend(merge.deferred.into_iter().filter(|u| u.state <= limit));
+                    (
+                        !merge.applied.is_empty(),
+                        json!({ "#": random_id(), "@": id, "ok": 1 }),
+                    )
+                }
+                Err(err) => (false, json!({ "#": random_id(), "@": id, "err": err })),
+            };
+            // An answer to a `get` we passed on.
+            if let Some(reply_to) = reply_to {
+                self.route(&reply_to, message, received);
+                return;
+            }
+            received.outgoing.push((To::Peer(from), ack));
+            if changed {
+                received.outgoing.push((To::AllBut(from), message));
+            }
+            return;
+        }
+
+        if let Some(get) = fields.get("get") {
+            let found = match get.get("#").and_then(Value::as_str) {
+                Some(soul) => self
+                    .store
+                    .get(soul, get.get(".").and_then(Value::as_str))
+                    .unwrap_or_else(|e| {
+                        eprintln!("[relay] Failed to read {soul}: {e}");
+                        None
+                    }),
+                None => None,
+            };
+            let answered = found.is_some();
+            if let Some(graph) = found {
+                received.outgoing.push((
+                    To::Peer(from),
+                    json!({ "#": random_id(), "@": id, "put": graph }),
+                ));
+            }
+            if peers > 1 {
+                lock(&self.routes).insert(id, from);
+                received.outgoing.push((To::AllBut(from), message));
+            } else if !answered {
+                // Nobody else to ask: tell the peer we have nothing.
+                received
+                    .outgoing
+                    .push((To::Peer(from), json!({ "#": random_id(), "@": id })));
+            }
+            return;
+        }
+
+        if let Some(reply_to) = reply_to {
+            self.route(&reply_to, message, received);
+        }
+    }
+
+    /// Send a reply back to the peer whose message it answers.
+    fn route(&self, reply_to: &str, message: Value, received: &mut Received) {
+        if let Some(peer) = lock(&self.routes).get(reply_to) {
+            received.outgoing.push((To::Peer(peer), message));
+        }
+    }
+}
+
+/// Recently seen message IDs.
+struct Recent<T> {
+    entries: HashMap<String, (T, Instant)>,
+}
+
+impl<T> Default for Recent<T> {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+        }
+    }
+}
+
+impl<T: Copy> Recent<T> {
+    /// Remember `id`, returning false if it was already remembered.
+    fn insert(&mut self, id: String, value: T) -> bool {
+        if self.entries.len() >= PRUNE_AT {
+            self.entries.retain(|_, (_, seen)| seen.elapsed() < MEMORY);
+        }
+        match self.entries.get(&id) {
+            Some((_, seen)) if seen.elapsed() < MEMORY => false,
+            _ => {
+                self.entries.insert(id, (value, Instant::now()));
+                true
+            }
+        }
+    }
+
+    fn get(&self, id: &str) -> Option<T> {
+        self.entries
+            .get(id)
+            .filter(|(_, seen)| seen.elapsed() < MEMORY)
+            .map(|(value, _)| *value)
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Our clock as a Gun state: milliseconds since the epoch.
+pub fn now() -> f64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs_f64() * 1000.0)
+        .unwrap_or(0.0)
+}
+
+/// A message ID like Gun's own.
+fn random_id() -> String {
+    Alphanumeric.sample_string(&mut rand::thread_rng(), 9)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALICE: PeerId = 1;
+    const BOB: PeerId = 2;
+
+    fn mesh() -> (tempfile::TempDir, Mesh) {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::open(&dir.path().join("relay.db")).unwrap();
+        (dir, Mesh::new(store))
+    }
+
+    fn put(id: &str, name: &str, state: f64) -> Value {
+        json!({
+            "#": id,
+            "put": {
+                "user/alice": {
+                    "_": { "#": "user/alice", ">": { "name": state } },
+                    "name": name,
+                },
+            },
+        })
+    }
+
+    fn receive(mesh: &Mesh, from: PeerId, message: &Value, peers: usize) -> Received {
+        mesh.receive(from, &message.to_string(), peers)
+    }
+
+    #[test]
+    fn put_is_acked_and_forwarded() {
+        let (_dir, mesh) = mesh();
+        let message = put("p1", "Alice", now() - 10.0);
+        let received = receive(&mesh, ALICE, &message, 2);
+        let [(to, ack), (forward_to, forwarded)] = &received.outgoing[..] else {
+            panic!("{:?}", received.outgoing);
+        };
+        assert_eq!(*to, To::Peer(ALICE));
+        assert_eq!((&ack["@"], &ack["ok"]), (&json!("p1"), &json!(1)));
+        assert_eq!(*forward_to, To::AllBut(ALICE));
+        assert_eq!(forwarded, &message);
+
+        // Nothing changed, so only the ack.
+        let received = receive(&mesh, BOB, &put("p2", "Alice", now() - 20.0), 2);
+        assert_eq!(received.outgoing.len(), 1);
+        assert_eq!(received.outgoing[0].0, To::Peer(BOB));
+    }
+
+    #[test]
+    fn invalid_put_is_refused() {
+        let (_dir, mesh) = mesh();
+        let message = json!({ "#": "p1", "put": { "user/alice": { "name": "Alice" } } });
+        let received = receive(&mesh, ALICE, &message, 2);
+        let [(to, ack)] = &received.outgoing[..] else {
+            panic!("{:?}", received.outgoing);
+        };
+        assert_eq!(*to, To::Peer(ALICE));
+        assert_eq!(
+            (&ack["@"], &ack["err"]),
+            (&json!("p1"), &json!("Invalid graph"))
+        );
+    }
+
+    #[test]
+    fn future_puts_are_deferred() {
+        let (_dir, mesh) = mesh();
+        let soon = now() + 60_000.0;
+        let received = receive(&mesh, ALICE, &put("p1", "Alice", soon), 2);
+        assert_eq!(received.outgoing.len(), 1, "only the ack");
+        assert_eq!(received.deferred.len(), 1);
+        assert_eq!(received.deferred[0].state, soon);
+
+        let far = now() + MAX_DEFER_MS * 2.0;
+        let received = receive(&mesh, ALICE, &put("p2", "Alice", far), 2);
+        assert!(received.deferred.is_empty());
+    }
+
+    #[test]
+    fn duplicates_are_dropped() {
+        let (_dir, mesh) = mesh();
+        let message = put("p1", "Alice", now() - 10.0);
+        assert!(!receive(&mesh, ALICE, &message, 2).outgoing.is_empty());
+        // Over another path, or changed: still the same message.
+        assert!(receive(&mesh, BOB, &message, 2).outgoing.is_empty());
+        let changed = put("p1", "Mallory", now());
+        assert!(receive(&mesh, BOB, &changed, 2).outgoing.is_empty());
+    }
+
+    #[test]
+    fn get_is_answered_from_the_store() {
+        let (_dir, mesh) = mesh();
+        let state = now() - 10.0;
+        receive(&mesh, ALICE, &put("p1", "Alice", state), 1);
+
+        let get = json!({ "#": "g1", "get": { "#": "user/alice", ".": "name" } });
+        let received = receive(&mesh, BOB, &get, 1);
+        let [(to, answer)] = &received.outgoing[..] else {
+            panic!("{:?}", received.outgoing);
+        };
+        assert_eq!(*to, To::Peer(BOB));
+        assert_eq!(answer["@"], "g1");
+        assert_eq!(answer["put"]["user/alice"]["name"], "Alice");
+        assert_eq!(answer["put"]["user/alice"]["_"][">"]["name"], state);
+
+        // Alone with nothing to say, the relay says so.
+        let get = json!({ "#": "g2", "get": { "#": "user/bob" } });
+        let received = receive(&mesh, BOB, &get, 1);
+        let [(to, empty)] = &received.outgoing[..] else {
+            panic!("{:?}", received.outgoing);
+        };
+        assert_eq!(*to, To::Peer(BOB));
+        assert_eq!(empty, &json!({ "#": empty["#"], "@": "g2" }));
+    }
+
+    #[test]
+    fn get_is_passed_on_and_answers_routed_back() {
+        let (_dir, mesh) = mesh();
+        let get = json!({ "#": "g1", "get": { "#": "user/bob" } });
+        let received = receive(&mesh, BOB, &get, 2);
+        assert_eq!(received.outgoing, [(To::AllBut(BOB), get)]);
+
+        let mut answer = put("r1", "Bob", now() - 10.0);
+        answer["@"] = json!("g1");
+        let received = receive(&mesh, ALICE, &answer, 2);
+        assert_eq!(received.outgoing, [(To::Peer(BOB), answer)]);
+    }
+
+    #[test]
+    fn handshake_and_batches() {
+        let (_dir, mesh) = mesh();
+        let batch = json!([
+            { "#": "d1", "dam": "?", "pid": "alice" },
+            { "#": "g1", "get": { "#": "user/bob" } },
+        ]);
+        let received = receive(&mesh, ALICE, &batch, 1);
+        let [(to, hi), (_, empty)] = &received.outgoing[..] else {
+            panic!("{:?}", received.outgoing);
+        };
+        assert_eq!(*to, To::Peer(ALICE));
+        assert_eq!((&hi["@"], &hi["dam"]), (&json!("d1"), &json!("?")));
+        assert_eq!(hi["pid"], mesh.pid);
+        assert_eq!(empty["@"], "g1");
+
+        assert!(mesh.receive(ALICE, "", 1).outgoing.is_empty());
+        assert!(mesh.receive(ALICE, "42", 1).outgoing.is_empty());
+    }
+}
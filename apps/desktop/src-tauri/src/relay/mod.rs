@@ -0,0 +1,391 @@
+//! An optional Gun relay built into the app, so any desktop can stand in for
+//! `scripts/gun-relay.mjs` or a hosted relay, for itself or the whole LAN.
+//!
+//! It speaks Gun's wire protocol over a WebSocket at `/gun`, on port 8765
+//! by default like the development relay, so `gun-instance.ts` finds it at
+//! its usual local address. Messages are handled by [`mesh`], conflicts are
+//! resolved by HAM (see [`ham`]), and the graph is kept in `relay.db` in the
+//! app data directory (see [`store`]). As with the Node relay, SEA
+//! signatures aren't checked here; clients verify what they read.
+//! `scripts/relay-interop.mjs` checks a running relay with real Gun clients.
+//!
+//! The relay is off by default and listens on loopback only unless the
+//! `lan` setting is on. Changing the settings restarts it, and every change
+//! in whether it runs is broadcast as `relay:changed`.
+
+mod ham;
+mod mesh;
+mod store;
+
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, UdpSocket};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use futures_util::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use tauri::async_runtime::{self, JoinHandle};
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, watch};
+use tokio_tungstenite::tungstenite::handshake::server::{ErrorResponse, Request, Response};
+use tokio_tungstenite::tungstenite::http::StatusCode;
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
+use tokio_tungstenite::tungstenite::Message;
+
+use mesh::{Mesh, PeerId, Received, To};
+use store::{Store, Update};
+
+const PATH: &str = "/gun";
+/// Largest message accepted; Gun batches can get big on first sync.
+const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RelaySettings {
+    pub enabled: bool,
+    pub port: u16,
+    /// Accept peers from other machines, not just this one.
+    pub lan: bool,
+}
+
+impl Default for RelaySettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            port: 8765,
+            lan: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelayStatus {
+    pub running: bool,
+    /// Peer URLs to give Gun clients, this machine's first.
+    pub urls: Vec<String>,
+    /// Connected peers.
+    pub peers: usize,
+    /// Why the relay isn't running although it's enabled.
+    pub error: Option<String>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum RelayError {
+    #[error("Failed to start relay: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Relay storage error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("Relay storage is corrupt: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// The relay, if it's running. One instance is managed by Tauri.
+pub struct Relay {
+    path: PathBuf,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    running: Option<Running>,
+    error: Option<String>,
+}
+
+struct Running {
+    addr: SocketAddr,
+    server: Arc<Server>,
+    stop: watch::Sender<bool>,
+    task: JoinHandle<()>,
+}
+
+/// What connections share.
+struct Server {
+    mesh: Mesh,
+    peers: Mutex<HashMap<PeerId, mpsc::UnboundedSender<Message>>>,
+    next_peer: AtomicU64,
+    stopped: watch::Receiver<bool>,
+}
+
+impl Relay {
+    /// A stopped relay that keeps its graph at `path`.
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            state: Mutex::default(),
+        }
+    }
+
+    pub fn status(&self) -> RelayStatus {
+        let state = self.lock();
+        let Some(running) = &state.running else {
+            return RelayStatus {
+                error: state.error.clone(),
+                ..RelayStatus::default()
+            };
+        };
+        let port = running.addr.port();
+        let mut urls = vec![url(IpAddr::V4(Ipv4Addr::LOCALHOST), port)];
+        if running.addr.ip().is_unspecified() {
+            urls.extend(lan_ip().map(|ip| url(ip, port)));
+        }
+        let peers = lock(&running.server.peers).len();
+        RelayStatus {
+            running: true,
+            urls,
+            peers,
+            error: None,
+        }
+    }
+
+    /// Stop the relay if it's running, and start it again with `settings`
+    /// if they enable it.
+    fn restart(&self, settings: &RelaySettings) -> Result<(), RelayError> {
+        let mut state = self.lock();
+        if let Some(running) = state.running.take() {
+            let _ = running.stop.send(true);
+            // Wait for the port to be released before binding it again.
+            let _ = async_runtime::block_on(running.task);
+        }
+        state.error = None;
+        if !settings.enabled {
+            return Ok(());
+        }
+        match start(&self.path, settings) {
+            Ok(running) => {
+                state.running = Some(running);
+                Ok(())
+            }
+            Err(e) => {
+                state.error = Some(e.to_string());
+                Err(e)
+            }
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        lock(&self.state)
+    }
+}
+
+/// Start, stop or restart the relay to match `settings`.
+pub fn apply<R: Runtime>(app: &AppHandle<R>, settings: &RelaySettings) {
+    let relay = app.state::<Relay>();
+    if let Err(e) = relay.restart(settings) {
+        eprintln!("[relay] {e}");
+    }
+    let _ = app.emit("relay:changed", relay.status());
+}
+
+fn start(path: &std::path::Path, settings: &RelaySettings) -> Result<Running, RelayError> {
+    let ip = if settings.lan {
+        Ipv4Addr::UNSPECIFIED
+    } else {
+        Ipv4Addr::LOCALHOST
+    };
+    // Bound here rather than in the task, so a taken port is reported.
+    let listener = std::net::TcpListener::bind((ip, settings.port))?;
+    listener.set_nonblocking(true)?;
+    let addr = listener.local_addr()?;
+    let (stop, stopped) = watch::channel(false);
+    let server = Arc::new(Server {
+        mesh: Mesh::new(Store::open(path)?),
+        peers: Mutex::default(),
+        next_peer: AtomicU64::new(0),
+        stopped,
+    });
+    let task = async_runtime::spawn(serve(listener, server.clone()));
+    Ok(Running {
+        addr,
+        server,
+        stop,
+        task,
+    })
+}
+
+async fn serve(listener: std::net::TcpListener, server: Arc<Server>) {
+    let listener = match TcpListener::from_std(listener) {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("[relay] Failed to listen: {e}");
+            return;
+        }
+    };
+    let mut stopped = server.stopped.clone();
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((stream, _)) => {
+                    async_runtime::spawn(connection(stream, server.clone()));
+                }
+                Err(e) => eprintln!("[relay] Failed to accept a peer: {e}"),
+            },
+            _ = stopped.changed() => break,
+        }
+    }
+}
+
+async fn connection(stream: TcpStream, server: Arc<Server>) {
+    let config = WebSocketConfig::default().max_message_size(Some(MAX_MESSAGE_SIZE));
+    let ws = match tokio_tungstenite::accept_hdr_async_with_config(stream, check_path, Some(config))
+        .await
+    {
+        Ok(ws) => ws,
+        // Not a WebSocket, or not for us.
+        Err(_) => return,
+    };
+    let (mut sink, mut frames) = ws.split();
+    let (outbox, mut outgoing) = mpsc::unbounded_channel();
+    let peer = server.next_peer.fetch_add(1, Ordering::Relaxed);
+    lock(&server.peers).insert(peer, outbox);
+
+    let writer = async_runtime::spawn(async move {
+        while let Some(message) = outgoing.recv().await {
+            if sink.send(message).await.is_err() {
+                break;
+            }
+        }
+        let _ = sink.close().await;
+    });
+
+    let mut stopped = server.stopped.clone();
+    loop {
+        tokio::select! {
+            frame = frames.next() => match frame {
+                Some(Ok(Message::Text(text))) => receive(&server, peer, text.to_string()).await,
+                Some(Ok(Message::Close(_)) | Err(_)) | None => break,
+                // Pings are answered by tungstenite.
+                Some(Ok(_)) => {}
+            },
+            _ = stopped.changed() => break,
+        }
+    }
+    // Dropping the outbox ends the writer, which closes the socket.
+    lock(&server.peers).remove(&peer);
+    let _ = writer.await;
+}
+
+/// Only accept WebSockets at `/gun`, where Gun clients connect. The
+/// signature is tungstenite's.
+#[allow(clippy::result_large_err)]
+fn check_path(request: &Request, response: Response) -> Result<Response, ErrorResponse> {
+    if request.uri().path() == PATH {
+        return Ok(response);
+    }
+    let mut error = ErrorResponse::new(Some("Not found".to_string()));
+    *error.status_mut() = StatusCode::NOT_FOUND;
+    Err(error)
+}
+
+async fn receive(server: &Arc<Server>, peer: PeerId, text: String) {
+    let handler = server.clone();
+    // The store is SQLite; keep it off the async threads.
+    let received = async_runtime::spawn_blocking(move || {
+        let peers = lock(&handler.peers).len();
+        handler.mesh.receive(peer, &text, peers)
+    })
+    .await;
+    if let Ok(received) = received {
+        deliver(server, received);
+    }
+}
+
+fn deliver(server: &Arc<Server>, received: Received) {
+    {
+        let peers = lock(&server.peers);
+        for (to, message) in received.outgoing {
+            let message = Message::Text(message.to_string().into());
+            for (&id, outbox) in peers.iter() {
+                let wanted = match to {
+                    To::Peer(peer) => id == peer,
+                    To::AllBut(peer) => id != peer,
+                    To::All => true,
+                };
+                if wanted {
+                    let _ = outbox.send(message.clone());
+                }
+            }
+        }
+    }
+    defer(server, received.deferred);
+}
+
+/// Merge `updates` again once the latest of their states has passed.
+fn defer(server: &Arc<Server>, updates: Vec<Update>) {
+    let Some(latest) = updates.iter().map(|u| u.state).reduce(f64::max) else {
+        return;
+    };
+    let delay = Duration::from_secs_f64(((latest - mesh::now()) / 1000.0).max(0.0));
+    let server = server.clone();
+    async_runtime::spawn(async move {
+        let mut stopped = server.stopped.clone();
+        tokio::select! {
+            _ = tokio::time::sleep(delay) => {}
+            _ = stopped.changed() => return,
+        }
+        let handler = server.clone();
+        let received =
+            async_runtime::spawn_blocking(move || handler.mesh.apply_deferred(updates)).await;
+        if let Ok(received) = received {
+            deliver(&server, received);
+        }
+    });
+}
+
+fn url(ip: IpAddr, port: u16) -> String {
+    format!("http://{}{PATH}", SocketAddr::new(ip, port))
+}
+
+/// The address other machines on the LAN reach us at: the one the OS would
+/// route outgoing traffic from. Connecting a UDP socket sends nothing.
+fn lan_ip() -> Option<IpAddr> {
+    let socket = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).ok()?;
+    socket.connect((Ipv4Addr::new(192, 0, 2, 1), 80)).ok()?;
+    let ip = socket.local_addr().ok()?.ip();
+    (!ip.is_unspecified() && !ip.is_loopback()).then_some(ip)
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::process::Command;
+
+    use super::*;
+
+    /// `scripts/relay-interop.mjs` against a relay on a free port. Run with
+    /// `pnpm --filter @nodes/desktop test:relay` once dependencies are installed.
+    #[test]
+    #[ignore = "needs Node.js and the workspace's node_modules"]
+    fn gun_clients_interoperate() {
+        let dir = tempfile::tempdir().unwrap();
+        let relay = Relay::new(dir.path().join("relay.db"));
+        let settings = RelaySettings {
+            enabled: true,
+            port: 0,
+            lan: false,
+        };
+        relay.restart(&settings).unwrap();
+        let port = lock(&relay.state).running.as_ref().unwrap().addr.port();
+
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../..");
+        let output = Command::new("node")
+            .arg("scripts/relay-interop.mjs")
+            .arg(port.to_string())
+            .current_dir(root)
+            .output()
+            .expect("node should be installed");
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(output.status.success(), "{stdout}{stderr}");
+        assert_eq!(stdout.lines().filter(|l| l.starts_with("ok ")).count(), 5);
+
+        relay.restart(&RelaySettings::default()).unwrap();
+    }
+}
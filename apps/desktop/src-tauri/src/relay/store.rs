@@ -0,0 +1,294 @@
+//! The relay's copy of the graph: one row per node field, with its value as
+//! JSON and its HAM state, in a plain SQLite file. Relayed data is the same
+//! public graph every peer holds, so unlike the local database it isn't
+//! encrypted or tied to an identity.
+
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+
+use rusqlite::{params, Connection, OptionalExtension};
+use serde_json::{json, Map, Value};
+
+use super::ham::{self, Ham};
+use super::RelayError;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS graph (
+        soul TEXT NOT NULL,
+        key TEXT NOT NULL,
+        value TEXT NOT NULL,
+        state REAL NOT NULL,
+        PRIMARY KEY (soul, key)
+    ) WITHOUT ROWID;";
+
+/// One field of a `put`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Update {
+    pub soul: String,
+    pub key: String,
+    pub value: Value,
+    pub state: f64,
+}
+
+/// Split a `put` graph into field updates, or `None` if any node is
+/// malformed: its metadata must name the node's own soul and give every
+/// field a state.
+pub fn updates(graph: &Value) -> Option<Vec<Update>> {
+    let mut updates = Vec::new();
+    for (soul, node) in graph.as_object()? {
+        let node = node.as_object()?;
+        let meta = node.get("_")?.as_object()?;
+        if meta.get("#")?.as_str()? != soul {
+            return None;
+        }
+        let states = meta.get(">")?.as_object()?;
+        for (key, value) in node.iter().filter(|(key, _)| *key != "_") {
+            let state = states.get(key)?.as_f64()?;
+            if !ham::is_value(value) || !state.is_finite() {
+                return None;
+            }
+            updates.push(Update {
+                soul: soul.clone(),
+                key: key.clone(),
+                value: value.clone(),
+                state,
+            });
+        }
+    }
+    Some(updates)
+}
+
+/// Build a `put` graph from field updates.
+pub fn graph(updates: &[Update]) -> Value {
+    let mut graph = Map::new();
+    for update in updates {
+        let node = graph
+            .entry(update.soul.clone())
+            .or_insert_with(|| json!({ "_": { "#": update.soul, ">": {} } }));
+        node["_"][">"][&update.key] = json!(update.state);
+        node[&update.key] = update.value.clone();
+    }
+    Value::Object(graph)
+}
+
+#[derive(Debug, Default)]
+pub struct Merge {
+    /// Fields stored because they won.
+    pub applied: Vec<Update>,
+    /// Fields from the future, to merge again once their state has passed.
+    pub deferred: Vec<Update>,
+}
+
+pub struct Store {
+    conn: Mutex<Connection>,
+}
+
+impl Store {
+    pub fn open(path: &Path) -> Result<Self, RelayError> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let conn = Connection::open(path)?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.pragma_update(None, "synchronous", "NORMAL")?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    /// Merge `updates` by HAM with `machine` as the current time.
+    pub fn merge(&self, updates: Vec<Update>, machine: f64) -> Result<Merge, RelayError> {
+        let mut conn = self.lock();
+        let tx = conn.transaction()?;
+        let mut merge = Merge::default();
+        {
+            let mut select =
+                tx.prepare_cached("SELECT value, state FROM graph WHERE soul = ?1 AND key = ?2")?;
+            let mut upsert = tx.prepare_cached(
+                "INSERT OR REPLACE INTO graph (soul, key, value, state) VALUES (?1, ?2, ?3, ?4)",
+            )?;
+            for update in updates {
+                let current = select
+                    .query_row(params![update.soul, update.key], |row| {
+                        Ok((row.get::<_, String>(0)?, row.get::<_, f64>(1)?))
+                    })
+                    .optional()?
+                    .map(|(value, state)| {
+                        Ok::<_, RelayError>((serde_json::from_str(&value)?, state))
+                    })
+                    .transpose()?;
+                let current = current.as_ref().map(|(value, state)| (*state, value));
+                match ham::resolve(machine, update.state, &update.value, current) {
+                    Ham::Incoming => {
+                        upsert.execute(params![
+                            update.soul,
+                            update.key,
+                            update.value.to_string(),
+                            update.state
+                        ])?;
+                        merge.applied.push(update);
+                    }
+                    Ham::Defer => merge.deferred.push(update),
+                    Ham::Current | Ham::Same => {}
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(merge)
+    }
+
+    /// The node `soul` as Gun sends it, with only field `key` if given.
+    /// `None` if we have nothing for it.
+    pub fn get(&self, soul: &str, key: Option<&str>) -> Result<Option<Value>, RelayError> {
+        let conn = self.lock();
+        let mut stmt = conn.prepare_cached(
+            "SELECT key, value, state FROM graph
+             WHERE soul = ?1 AND (?2 IS NULL OR key = ?2)",
+        )?;
+        let rows = stmt.query_map(params![soul, key], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, f64>(2)?,
+            ))
+        })?;
+        let mut updates = Vec::new();
+        for row in rows {
+            let (key, value, state) = row?;
+            updates.push(Update {
+                soul: soul.to_string(),
+                key,
+                value: serde_json::from_str(&value)?,
+                state,
+            });
+        }
+        Ok((!updates.is_empty()).then(|| graph(&updates)))
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update(soul: &str, key: &str, value: Value, state: f64) -> Update {
+        Update {
+            soul: soul.into(),
+            key: key.into(),
+            value,
+            state,
+        }
+    }
+
+    fn open() -> (tempfile::TempDir, Store) {
+        let dir = tempfile::tempdir().unwrap();
+        let store = Store::open(&dir.path().join("relay.db")).unwrap();
+        (dir, store)
+    }
+
+    #[test]
+    fn splits_graphs_into_updates() {
+        let put = json!({
+            "user/alice": {
+                "_": { "#": "user/alice", ">": { "name": 1.0, "friend": 2.0 } },
+                "name": "Alice",
+                "friend": { "#": "user/bob" },
+            },
+        });
+        let mut updates = updates(&put).unwrap();
+        updates.sort_by(|a, b| a.key.cmp(&b.key));
+        assert_eq!(
+            updates,
+            [
+                update("user/alice", "friend", json!({ "#": "user/bob" }), 2.0),
+                update("user/alice", "name", json!("Alice"), 1.0),
+            ]
+        );
+        assert_eq!(graph(&updates), put);
+    }
+
+    #[test]
+    fn states_keep_every_digit() {
+        // Gun's states are fractional milliseconds. One that moved by a bit
+        // on the way through could break a tie differently elsewhere.
+        let text = r##"{"a":{"_":{"#":"a",">":{"x":1792163885854.0781}},"x":1}}"##;
+        let updates = updates(&serde_json::from_str(text).unwrap()).unwrap();
+        assert_eq!(updates[0].state, 1792163885854.0781);
+        assert_eq!(graph(&updates).to_string(), text);
+    }
+
+    #[test]
+    fn rejects_malformed_graphs() {
+        /// `user/alice` with metadata naming `soul` and `states`.
+        fn alice(soul: &str, states: Value, key: &str, value: Value) -> Value {
+            json!({ "user/alice": { "_": { "#": soul, ">": states }, key: value } })
+        }
+        for put in [
+            json!("user/alice"),
+            json!({ "user/alice": "Alice" }),
+            // No metadata.
+            json!({ "user/alice": { "name": "Alice" } }),
+            // Metadata for another node.
+            alice("user/bob", json!({ "name": 1 }), "name", json!("Alice")),
+            // A field without a state.
+            alice("user/alice", json!({}), "name", json!("Alice")),
+            alice("user/alice", json!({ "name": "1" }), "name", json!("Alice")),
+            // Values Gun doesn't store.
+            alice("user/alice", json!({ "tags": 1 }), "tags", json!([])),
+            alice(
+                "user/alice",
+                json!({ "bio": 1 }),
+                "bio",
+                json!({ "text": "hi" }),
+            ),
+        ] {
+            assert_eq!(updates(&put), None, "{put}");
+        }
+    }
+
+    #[test]
+    fn merges_by_ham() {
+        let (_dir, store) = open();
+        let now = 1_000.0;
+        let merge = store
+            .merge(
+                vec![
+                    update("user/alice", "name", json!("Alice"), 10.0),
+                    update("user/alice", "bio", json!("later"), now + 1.0),
+                ],
+                now,
+            )
+            .unwrap();
+        assert_eq!(merge.applied.len(), 1);
+        assert_eq!(
+            merge.deferred,
+            [update("user/alice", "bio", json!("later"), now + 1.0)]
+        );
+
+        let merge = store
+            .merge(
+                vec![
+                    update("user/alice", "name", json!("Old"), 5.0),
+                    update("user/alice", "name", json!("Alice"), 10.0),
+                ],
+                now,
+            )
+            .unwrap();
+        assert!(merge.applied.is_empty());
+        let merge = store
+            .merge(vec![update("user/alice", "name", json!("Al"), 11.0)], now)
+            .unwrap();
+        assert_eq!(merge.applied.len(), 1);
+
+        assert_eq!(
+            store.get("user/alice", None).unwrap(),
+            Some(graph(&[update("user/alice", "name", json!("Al"), 11.0)]))
+        );
+        assert_eq!(store.get("user/alice", Some("bio")).unwrap(), None);
+        assert_eq!(store.get("user/bob", None).unwrap(), None);
+    }
+}
@@ -0,0 +1,894 @@
+//! Full-text search over messages and DMs.
+//!
+//! Documents live in the local database: metadata in `search_docs`, text in
+//! an FTS5 table sharing its rowid, and every indexed word in `search_terms`
+//! for fuzzy lookups. The FTS table also indexes author, channel, node, type
+//! and attachment tokens in a `meta` column, so those filters are posting
+//! list intersections instead of row lookups. The index is updated a
+//! document at a time as messages arrive, so nothing has to be loaded or
+//! serialized up front the way the MiniSearch index was.
+//!
+//! Queries follow `search-store.ts`: free-text terms are OR-ed together, each
+//! matching as a prefix or with a small typo, and ranked by BM25. The
+//! `from:`, `in:`, `before:`, `after:` and `has:` filters narrow the set; a
+//! query with only filters returns the newest matching documents. Ranking
+//! covers the most recently indexed matches only (see [`RANK_WINDOW`]),
+//! which keeps common words fast on large indexes. An older message that
+//! matches a common word can therefore be missed unless the query is
+//! narrowed; the [`SearchPage`] says when that happened so the UI can
+//! suggest it.
+//!
+//! This module only needs a [`Connection`], so the benchmarks can build it
+//! without the rest of the app.
+
+mod query;
+
+use rusqlite::types::Value as SqlValue;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+
+/// Tables and indexes, applied as a database migration.
+pub const SCHEMA: &str = "
+    CREATE TABLE search_docs (
+        doc INTEGER PRIMARY KEY,
+        id TEXT NOT NULL UNIQUE,
+        kind TEXT NOT NULL,
+        author_key TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        channel_id TEXT,
+        node_id TEXT,
+        conversation_id TEXT,
+        has_file INTEGER NOT NULL DEFAULT 0,
+        has_image INTEGER NOT NULL DEFAULT 0,
+        has_link INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE INDEX search_docs_time ON search_docs (timestamp);
+    CREATE INDEX search_docs_author ON search_docs (author_key, timestamp);
+    CREATE INDEX search_docs_channel ON search_docs (channel_id, timestamp);
+    CREATE VIRTUAL TABLE search_fts USING fts5 (
+        content,
+        meta,
+        tokenize = 'unicode61 remove_diacritics 2'
+    );
+    INSERT INTO search_fts (search_fts, rank) VALUES ('rank', 'bm25(1.0, 0.0)');
+    CREATE TABLE search_terms (term TEXT PRIMARY KEY) WITHOUT ROWID;";
+
+/// Only this many of the most recently indexed matches are ranked, so a
+/// common word doesn't mean scoring a large part of the index. Ranking every
+/// match of a common word takes 0.5-1s on a million documents, against
+/// 10-50ms with the window.
+const RANK_WINDOW: usize = 2000;
+/// Same default as `searchIndex.search`.
+const DEFAULT_LIMIT: u32 = 50;
+const MAX_LIMIT: u32 = 500;
+/// Tokens of context in a snippet, roughly the 150 characters the frontend
+/// used to cut.
+const SNIPPET_TOKENS: u32 = 24;
+const SNIPPET_CHARS: usize = 150;
+/// Private-use characters marking matches inside `snippet()` output.
+const MARK_START: char = '\u{E000}';
+const MARK_END: char = '\u{E001}';
+const ELLIPSIS: &str = "…";
+const HAS_FILE: &str = "zfile";
+const HAS_IMAGE: &str = "zimage";
+const HAS_LINK: &str = "zlink";
+
+/// A message or DM as handed to the index.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchDocument {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: DocumentKind,
+    pub content: String,
+    pub author_key: String,
+    pub timestamp: i64,
+    #[serde(default)]
+    pub channel_id: Option<String>,
+    #[serde(default)]
+    pub node_id: Option<String>,
+    #[serde(default)]
+    pub conversation_id: Option<String>,
+    /// The message's `attachments` JSON, if any.
+    #[serde(default)]
+    pub attachments: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DocumentKind {
+    Message,
+    Dm,
+}
+
+/// `SearchFilters` from `@nodes/core`, with dates as Unix milliseconds, plus
+/// the node and type restrictions the search scopes apply.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchFilters {
+    pub from: Option<String>,
+    #[serde(rename = "in")]
+    pub channel: Option<String>,
+    pub before: Option<i64>,
+    pub after: Option<i64>,
+    pub has: Option<ContentFilter>,
+    pub node_id: Option<String>,
+    #[serde(rename = "type")]
+    pub kind: Option<DocumentKind>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentFilter {
+    File,
+    Image,
+    Link,
+}
+
+/// `SearchResult` from `@nodes/core`.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchResult {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub kind: DocumentKind,
+    pub content: String,
+    /// Plain text around the best match.
+    pub content_snippet: String,
+    pub author_key: String,
+    pub timestamp: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub node_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub conversation_id: Option<String>,
+    pub score: f64,
+    /// Distinct words that matched, lowercased.
+    pub matches: Vec<String>,
+    /// `[start, end)` ranges of matches in `content_snippet`, in UTF-16 code
+    /// units so they index JavaScript strings directly.
+    pub highlights: Vec<[usize; 2]>,
+}
+
+/// Results of one query.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchPage {
+    pub results: Vec<SearchResult>,
+    /// Older matches were left out of ranking; narrowing the query with
+    /// more words or filters brings them in.
+    pub truncated: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchStats {
+    pub documents: u64,
+    /// How many of the newest matches a query ranks; older ones are left
+    /// out.
+    pub rank_window: usize,
+}
+
+/// Add or update documents. Text is only re-indexed when it changed.
+/// Returns how many documents were added or changed.
+///
+/// Run batches inside a transaction; each document is several writes.
+pub fn index(conn: &Connection, documents: &[SearchDocument]) -> rusqlite::Result<usize> {
+    let mut changed = 0;
+    for document in documents {
+        let flags = ContentFlags::of(document);
+        let meta = meta_tokens(document, &flags);
+        let existing: Option<(i64, String, String)> = conn
+            .prepare_cached(
+                "SELECT d.doc, f.content, f.meta FROM search_docs d
+                 JOIN search_fts f ON f.rowid = d.doc WHERE d.id = ?1",
+            )?
+            .query_row([&document.id], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })
+            .optional()?;
+
+        let (updated, content_changed) = match &existing {
+            Some((doc, content, old_meta)) => {
+                let updated = conn
+                    .prepare_cached(
+                        "UPDATE search_docs SET kind = ?2, author_key = ?3, timestamp = ?4,
+                             channel_id = ?5, node_id = ?6, conversation_id = ?7,
+                             has_file = ?8, has_image = ?9, has_link = ?10
+                         WHERE doc = ?1 AND NOT (kind IS ?2 AND author_key IS ?3
+                             AND timestamp IS ?4 AND channel_id IS ?5 AND node_id IS ?6
+                             AND conversation_id IS ?7 AND has_file IS ?8
+                             AND has_image IS ?9 AND has_link IS ?10)",
+                    )?
+                    .execute(params![
+                        doc,
+                        kind_str(document.kind),
+                        document.author_key,
+                        document.timestamp,
+                        document.channel_id,
+                        document.node_id,
+                        document.conversation_id,
+                        flags.file,
+                        flags.image,
+                        flags.link,
+                    ])?;
+                let content_changed = *content != document.content;
+                if content_changed || *old_meta != meta {
+                    conn.prepare_cached(
+                        "UPDATE search_fts SET content = ?2, meta = ?3 WHERE rowid = ?1",
+                    )?
+                    .execute(params![doc, document.content, meta])?;
+                }
+                (updated > 0, content_changed)
+            }
+            None => {
+                conn.prepare_cached(
+                    "INSERT INTO search_docs (id, kind, author_key, timestamp, channel_id,
+                         node_id, conversation_id, has_file, has_image, has_link)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+                )?
+                .execute(params![
+                    document.id,
+                    kind_str(document.kind),
+                    document.author_key,
+                    document.timestamp,
+                    document.channel_id,
+                    document.node_id,
+                    document.conversation_id,
+                    flags.file,
+                    flags.image,
+                    flags.link,
+                ])?;
+                conn.prepare_cached(
+                    "INSERT INTO search_fts (rowid, content, meta) VALUES (?1, ?2, ?3)",
+                )?
+                .execute(params![
+                    conn.last_insert_rowid(),
+                    document.content,
+                    meta
+                ])?;
+                (true, true)
+            }
+        };
+
+        if content_changed {
+            let mut add_term =
+                conn.prepare_cached("INSERT OR IGNORE INTO search_terms (term) VALUES (?1)")?;
+            for term in query::tokenize(&document.content) {
+                add_term.execute([term])?;
+            }
+        }
+        if updated || content_changed {
+            changed += 1;
+        }
+    }
+    Ok(changed)
+}
+
+/// Drop documents by id. Returns how many were indexed.
+///
+/// Their words stay in `search_terms`; a stale term only costs a fuzzy
+/// variant that matches nothing.
+pub fn remove(conn: &Connection, ids: &[String]) -> rusqlite::Result<usize> {
+    let mut removed = 0;
+    for id in ids {
+        let doc: Option<i64> = conn
+            .prepare_cached("DELETE FROM search_docs WHERE id = ?1 RETURNING doc")?
+            .query_row([id], |row| row.get(0))
+            .optional()?;
+        if let Some(doc) = doc {
+            conn.prepare_cached("DELETE FROM search_fts WHERE rowid = ?1")?
+                .execute([doc])?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+/// Ranked matches for `terms`, restricted by `filters`.
+pub fn search(
+    conn: &Connection,
+    terms: &[String],
+    filters: &SearchFilters,
+    limit: Option<u32>,
+) -> rusqlite::Result<SearchPage> {
+    let limit = limit
+        .filter(|&l| l > 0)
+        .unwrap_or(DEFAULT_LIMIT)
+        .min(MAX_LIMIT);
+
+    let Some(text) = query::build(conn, terms)? else {
+        // Terms that tokenize to nothing (e.g. only punctuation) match nothing.
+        if terms.iter().any(|t| !t.trim().is_empty()) {
+            return Ok(SearchPage::default());
+        }
+        return Ok(SearchPage {
+            results: recent(conn, filters, limit)?,
+            truncated: false,
+        });
+    };
+
+    let mut expression = format!("content : ({text})");
+    let meta = filter_tokens(filters);
+    if !meta.is_empty() {
+        expression.push_str(&format!(" AND meta : ({})", meta.join(" AND ")));
+    }
+    let mut conditions = vec!["search_fts MATCH ?".to_owned()];
+    let mut values = vec![SqlValue::Text(expression)];
+    if let Some(before) = filters.before {
+        conditions.push("d.timestamp < ?".to_owned());
+        values.push(SqlValue::Integer(before));
+    }
+    if let Some(after) = filters.after {
+        conditions.push("d.timestamp > ?".to_owned());
+        values.push(SqlValue::Integer(after));
+    }
+
+    // Walking matches in rowid order is cheap; scoring them isn't. The
+    // oldest match in the window, and whether there is one before it.
+    let cutoff_sql = if conditions.len() == 1 {
+        format!(
+            "SELECT rowid FROM search_fts WHERE search_fts MATCH ?
+             ORDER BY rowid DESC LIMIT 2 OFFSET {}",
+            RANK_WINDOW - 1
+        )
+    } else {
+        format!(
+            "SELECT f.rowid FROM search_fts f JOIN search_docs d ON d.doc = f.rowid
+             WHERE {}
+             ORDER BY f.rowid DESC LIMIT 2 OFFSET {}",
+            conditions.join(" AND "),
+            RANK_WINDOW - 1
+        )
+    };
+    let beyond: Vec<i64> = conn
+        .prepare_cached(&cutoff_sql)?
+        .query_map(params_from_iter(&values), |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+    let truncated = beyond.len() > 1;
+    if truncated {
+        conditions.push("f.rowid >= ?".to_owned());
+        values.push(SqlValue::Integer(beyond[0]));
+    }
+
+    let sql = format!(
+        "SELECT d.id, d.kind, f.content, d.author_key, d.timestamp, d.channel_id,
+             d.node_id, d.conversation_id, -f.rank,
+             snippet(search_fts, 0, char({}), char({}), '{ELLIPSIS}', {SNIPPET_TOKENS})
+         FROM search_fts f JOIN search_docs d ON d.doc = f.rowid
+         WHERE {}
+         ORDER BY f.rank, d.timestamp DESC
+         LIMIT {limit}",
+        MARK_START as u32,
+        MARK_END as u32,
+        conditions.join(" AND "),
+    );
+    let mut stmt = conn.prepare_cached(&sql)?;
+    let results = stmt
+        .query_map(params_from_iter(values), from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(SearchPage { results, truncated })
+}
+
+/// The newest documents matching `filters`, for queries without text.
+fn recent(
+    conn: &Connection,
+    filters: &SearchFilters,
+    limit: u32,
+) -> rusqlite::Result<Vec<SearchResult>> {
+    let (conditions, values) = filter_clause(filters);
+    if conditions.is_empty() {
+        return Ok(Vec::new());
+    }
+    let sql = format!(
+        "SELECT d.id, d.kind, f.content, d.author_key, d.timestamp, d.channel_id,
+             d.node_id, d.conversation_id, 0.0, NULL
+         FROM search_docs d JOIN search_fts f ON f.rowid = d.doc
+         WHERE {}
+         ORDER BY d.timestamp DESC
+         LIMIT {limit}",
+        conditions.join(" AND "),
+    );
+    let mut stmt = conn.prepare_cached(&sql)?;
+    let results = stmt
+        .query_map(params_from_iter(values), from_row)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(results)
+}
+
+pub fn stats(conn: &Connection) -> rusqlite::Result<SearchStats> {
+    let documents = conn.query_row("SELECT count(*) FROM search_docs", [], |row| row.get(0))?;
+    Ok(SearchStats {
+        documents,
+        rank_window: RANK_WINDOW,
+    })
+}
+
+/// Empty the index, e.g. before a full rebuild.
+pub fn clear(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(
+        "DELETE FROM search_docs;
+         DELETE FROM search_fts;
+         DELETE FROM search_terms;",
+    )
+}
+
+struct ContentFlags {
+    file: bool,
+    image: bool,
+    link: bool,
+}
+
+impl ContentFlags {
+    fn of(document: &SearchDocument) -> Self {
+        let attachments: Vec<serde_json::Value> = document
+            .attachments
+            .as_deref()
+            .and_then(|json| serde_json::from_str(json).ok())
+            .unwrap_or_default();
+        let lower = document.content.to_lowercase();
+        Self {
+            file: !attachments.is_empty(),
+            image: attachments.iter().any(|a| {
+                a.get("mimeType")
+                    .and_then(|m| m.as_str())
+                    .is_some_and(|m| m.starts_with("image/"))
+            }),
+            link: lower.contains("http://") || lower.contains("https://"),
+        }
+    }
+}
+
+/// Filterable attributes as single index tokens. IDs are hex-encoded so the
+/// tokenizer keeps each one whole.
+fn meta_tokens(document: &SearchDocument, flags: &ContentFlags) -> String {
+    let mut tokens = vec![
+        meta_token('a', &document.author_key),
+        meta_token('k', kind_str(document.kind)),
+    ];
+    if let Some(channel_id) = &document.channel_id {
+        tokens.push(meta_token('c', channel_id));
+    }
+    if let Some(node_id) = &document.node_id {
+        tokens.push(meta_token('n', node_id));
+    }
+    for (flag, token) in [
+        (flags.file, HAS_FILE),
+        (flags.image, HAS_IMAGE),
+        (flags.link, HAS_LINK),
+    ] {
+        if flag {
+            tokens.push(token.to_owned());
+        }
+    }
+    tokens.join(" ")
+}
+
+/// The `meta` tokens every result must carry.
+fn filter_tokens(filters: &SearchFilters) -> Vec<String> {
+    let mut tokens = Vec::new();
+    if let Some(from) = &filters.from {
+        tokens.push(meta_token('a', from));
+    }
+    if let Some(channel) = &filters.channel {
+        tokens.push(meta_token('c', channel));
+    }
+    if let Some(node_id) = &filters.node_id {
+        tokens.push(meta_token('n', node_id));
+    }
+    if let Some(kind) = filters.kind {
+        tokens.push(meta_token('k', kind_str(kind)));
+    }
+    if let Some(has) = filters.has {
+        tokens.push(
+            match has {
+                ContentFilter::File => HAS_FILE,
+                ContentFilter::Image => HAS_IMAGE,
+                ContentFilter::Link => HAS_LINK,
+            }
+            .to_owned(),
+        );
+    }
+    tokens
+}
+
+fn meta_token(prefix: char, value: &str) -> String {
+    let mut token = String::with_capacity(1 + value.len() * 2);
+    token.push(prefix);
+    for byte in value.bytes() {
+        token.push_str(&format!("{byte:02x}"));
+    }
+    token
+}
+
+fn kind_str(kind: DocumentKind) -> &'static str {
+    match kind {
+        DocumentKind::Message => "message",
+        DocumentKind::Dm => "dm",
+    }
+}
+
+/// SQL conditions and their bound values for queries without text.
+fn filter_clause(filters: &SearchFilters) -> (Vec<String>, Vec<SqlValue>) {
+    let mut conditions = Vec::new();
+    let mut values = Vec::new();
+    let mut push = |condition: &str, value: SqlValue| {
+        conditions.push(condition.to_owned());
+        values.push(value);
+    };
+    if let Some(from) = &filters.from {
+        push("d.author_key = ?", SqlValue::Text(from.clone()));
+    }
+    if let Some(channel) = &filters.channel {
+        push("d.channel_id = ?", SqlValue::Text(channel.clone()));
+    }
+    if let Some(before) = filters.before {
+        push("d.timestamp < ?", SqlValue::Integer(before));
+    }
+    if let Some(after) = filters.after {
+        push("d.timestamp > ?", SqlValue::Integer(after));
+    }
+    if let Some(node_id) = &filters.node_id {
+        push("d.node_id = ?", SqlValue::Text(node_id.clone()));
+    }
+    if let Some(kind) = filters.kind {
+        push("d.kind = ?", SqlValue::Text(kind_str(kind).to_owned()));
+    }
+    if let Some(has) = filters.has {
+        conditions.push(
+            match has {
+                ContentFilter::File => "d.has_file",
+                ContentFilter::Image => "d.has_image",
+                ContentFilter::Link => "d.has_link",
+            }
+            .to_owned(),
+        );
+    }
+    (conditions, values)
+}
+
+fn from_row(row: &Row<'_>) -> rusqlite::Result<SearchResult> {
+    let kind: String = row.get(1)?;
+    let content: String = row.get(2)?;
+    let marked: Option<String> = row.get(9)?;
+    let (content_snippet, matches, highlights) = match marked {
+        Some(marked) => parse_snippet(&marked),
+        None => (truncate(&content), Vec::new(), Vec::new()),
+    };
+    Ok(SearchResult {
+        id: row.get(0)?,
+        kind: if kind == "dm" {
+            DocumentKind::Dm
+        } else {
+            DocumentKind::Message
+        },
+        content,
+        content_snippet,
+        author_key: row.get(3)?,
+        timestamp: row.get(4)?,
+        channel_id: row.get(5)?,
+        node_id: row.get(6)?,
+        conversation_id: row.get(7)?,
+        score: row.get(8)?,
+        matches,
+        highlights,
+    })
+}
+
+/// Strip match markers from `snippet()` output, recording where they were.
+fn parse_snippet(marked: &str) -> (String, Vec<String>, Vec<[usize; 2]>) {
+    let mut text = String::with_capacity(marked.len());
+    let mut matches: Vec<String> = Vec::new();
+    let mut highlights = Vec::new();
+    let mut offset = 0;
+    let mut open: Option<(usize, usize)> = None;
+    for c in marked.chars() {
+        match c {
+            MARK_START => open = Some((offset, text.len())),
+            MARK_END => {
+                if let Some((start, byte_start)) = open.take() {
+                    highlights.push([start, offset]);
+                    let word = text[byte_start..].to_lowercase();
+                    if !matches.contains(&word) {
+                        matches.push(word);
+                    }
+                }
+            }
+            c => {
+                text.push(c);
+                offset += c.len_utf16();
+            }
+        }
+    }
+    (text, matches, highlights)
+}
+
+fn truncate(content: &str) -> String {
+    match content.char_indices().nth(SNIPPET_CHARS) {
+        Some((end, _)) => format!("{}{ELLIPSIS}", content[..end].trim_end()),
+        None => content.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(SCHEMA).unwrap();
+        conn
+    }
+
+    fn doc(id: &str, content: &str, timestamp: i64) -> SearchDocument {
+        SearchDocument {
+            id: id.into(),
+            kind: DocumentKind::Message,
+            content: content.into(),
+            author_key: "alice".into(),
+            timestamp,
+            channel_id: Some("general".into()),
+            node_id: Some("node".into()),
+            conversation_id: None,
+            attachments: None,
+        }
+    }
+
+    fn ids(conn: &Connection, terms: &[&str], filters: &SearchFilters) -> Vec<String> {
+        let terms: Vec<String> = terms.iter().map(|t| t.to_string()).collect();
+        let page = search(conn, &terms, filters, None).unwrap();
+        let mut ids: Vec<String> = page.results.into_iter().map(|r| r.id).collect();
+        ids.sort();
+        ids
+    }
+
+    fn find(conn: &Connection, terms: &[&str]) -> Vec<String> {
+        ids(conn, terms, &SearchFilters::default())
+    }
+
+    #[test]
+    fn terms_match_as_prefixes_or_with_typos() {
+        let conn = open();
+        index(
+            &conn,
+            &[
+                doc("1", "hello world", 1),
+                doc("2", "helicopter rides", 2),
+                doc("3", "yellow submarine", 3),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(find(&conn, &["hel"]), ["1", "2"]);
+        assert_eq!(find(&conn, &["HELLO"]), ["1"]);
+        assert_eq!(find(&conn, &["wirld"]), ["1"]);
+        assert_eq!(find(&conn, &["submarnie"]), ["3"]);
+        // Short terms get no typo allowance.
+        assert!(find(&conn, &["yo"]).is_empty());
+        // Terms are OR-ed together.
+        assert_eq!(find(&conn, &["rides", "yellow"]), ["2", "3"]);
+        assert!(find(&conn, &["?!"]).is_empty());
+        assert!(find(&conn, &[]).is_empty());
+    }
+
+    #[test]
+    fn filters_narrow_the_results() {
+        let conn = open();
+        let mut bob = doc("bob", "lunch plans", 20);
+        bob.author_key = "bob".into();
+        let mut random = doc("random", "lunch at noon", 30);
+        random.channel_id = Some("random".into());
+        let mut file = doc("file", "lunch menu", 40);
+        file.attachments = Some(r#"[{"mimeType":"application/pdf"}]"#.into());
+        let mut image = doc("image", "lunch photo", 50);
+        image.attachments = Some(r#"[{"mimeType":"image/png"}]"#.into());
+        let link = doc("link", "lunch at https://example.com", 60);
+        let mut dm = doc("dm", "lunch tomorrow?", 70);
+        dm.kind = DocumentKind::Dm;
+        dm.channel_id = None;
+        dm.node_id = None;
+        dm.conversation_id = Some("alice-bob".into());
+        let plain = doc("plain", "lunch", 10);
+        index(&conn, &[plain, bob, random, file, image, link, dm]).unwrap();
+
+        let filtered = |filters: SearchFilters| ids(&conn, &["lunch"], &filters);
+        assert_eq!(
+            filtered(SearchFilters {
+                from: Some("bob".into()),
+                ..Default::default()
+            }),
+            ["bob"]
+        );
+        assert_eq!(
+            filtered(SearchFilters {
+                channel: Some("random".into()),
+                ..Default::default()
+            }),
+            ["random"]
+        );
+        assert_eq!(
+            filtered(SearchFilters {
+                before: Some(30),
+                ..Default::default()
+            }),
+            ["bob", "plain"]
+        );
+        assert_eq!(
+            filtered(SearchFilters {
+                after: Some(50),
+                ..Default::default()
+            }),
+            ["dm", "link"]
+        );
+        assert_eq!(
+            filtered(SearchFilters {
+                has: Some(ContentFilter::File),
+                ..Default::default()
+            }),
+            ["file", "image"]
+        );
+        assert_eq!(
+            filtered(SearchFilters {
+                has: Some(ContentFilter::Image),
+                ..Default::default()
+            }),
+            ["image"]
+        );
+        assert_eq!(
+            filtered(SearchFilters {
+                has: Some(ContentFilter::Link),
+                ..Default::default()
+            }),
+            ["link"]
+        );
+        assert_eq!(
+            filtered(SearchFilters {
+                kind: Some(DocumentKind::Dm),
+                ..Default::default()
+            }),
+            ["dm"]
+        );
+        assert_eq!(
+            filtered(SearchFilters {
+                node_id: Some("node".into()),
+                after: Some(40),
+                ..Default::default()
+            }),
+            ["image", "link"]
+        );
+
+        // Without text, the newest matches come first.
+        let page = search(
+            &conn,
+            &[],
+            &SearchFilters {
+                has: Some(ContentFilter::File),
+                ..Default::default()
+            },
+            None,
+        )
+        .unwrap();
+        let newest: Vec<_> = page.results.iter().map(|r| r.id.as_str()).collect();
+        assert_eq!(newest, ["image", "file"]);
+        assert!(page.results.iter().all(|r| r.highlights.is_empty()));
+    }
+
+    #[test]
+    fn snippets_mark_matches() {
+        let conn = open();
+        index(
+            &conn,
+            &[
+                doc("1", "Say hello to the World, hello!", 1),
+                doc("2", "😀 hello", 2),
+                doc("3", &format!("{} tail", "lorem ".repeat(100)), 3),
+            ],
+        )
+        .unwrap();
+
+        let results = |terms: &[&str]| {
+            let terms: Vec<String> = terms.iter().map(|t| t.to_string()).collect();
+            search(&conn, &terms, &SearchFilters::default(), None)
+                .unwrap()
+                .results
+        };
+        let result = &results(&["hello", "world"])[0];
+        assert_eq!(result.id, "1");
+        assert_eq!(result.content_snippet, "Say hello to the World, hello!");
+        assert_eq!(result.highlights, [[4, 9], [17, 22], [24, 29]]);
+        assert_eq!(result.matches, ["hello", "world"]);
+        assert!(result.score > 0.0);
+
+        // Offsets are UTF-16 code units.
+        let emoji = results(&["hello"])
+            .into_iter()
+            .find(|r| r.id == "2")
+            .unwrap();
+        assert_eq!(emoji.highlights, [[3, 8]]);
+
+        let long = &results(&["tail"])[0];
+        assert!(long.content_snippet.starts_with(ELLIPSIS));
+        assert!(long.content_snippet.ends_with("tail"));
+        assert_eq!(long.matches, ["tail"]);
+
+        // Without text there's nothing to mark; long content is cut instead.
+        let page = search(
+            &conn,
+            &[],
+            &SearchFilters {
+                after: Some(2),
+                ..Default::default()
+            },
+            None,
+        )
+        .unwrap();
+        let snippet = &page.results[0].content_snippet;
+        assert_eq!(snippet.chars().count(), SNIPPET_CHARS);
+        assert!(snippet.ends_with(ELLIPSIS));
+    }
+
+    #[test]
+    fn index_updates_incrementally() {
+        let conn = open();
+        assert_eq!(
+            index(&conn, &[doc("1", "first draft", 1), doc("2", "other", 2)]).unwrap(),
+            2
+        );
+        assert_eq!(index(&conn, &[doc("1", "first draft", 1)]).unwrap(), 0);
+        assert_eq!(find(&conn, &["draft"]), ["1"]);
+
+        // An edit replaces the text.
+        assert_eq!(index(&conn, &[doc("1", "final version", 1)]).unwrap(), 1);
+        assert!(find(&conn, &["draft"]).is_empty());
+        assert_eq!(find(&conn, &["final"]), ["1"]);
+
+        // So does a metadata-only change.
+        let mut moved = doc("1", "final version", 1);
+        moved.channel_id = Some("random".into());
+        assert_eq!(index(&conn, &[moved]).unwrap(), 1);
+        let in_random = SearchFilters {
+            channel: Some("random".into()),
+            ..Default::default()
+        };
+        assert_eq!(ids(&conn, &["final"], &in_random), ["1"]);
+
+        assert_eq!(remove(&conn, &["1".into(), "missing".into()]).unwrap(), 1);
+        assert_eq!(remove(&conn, &["1".into()]).unwrap(), 0);
+        assert!(find(&conn, &["final"]).is_empty());
+        assert_eq!(stats(&conn).unwrap().documents, 1);
+
+        clear(&conn).unwrap();
+        assert_eq!(stats(&conn).unwrap().documents, 0);
+        assert!(find(&conn, &["other"]).is_empty());
+    }
+
+    #[test]
+    fn pages_say_when_older_matches_were_not_ranked() {
+        let conn = open();
+        // The oldest match would rank first if it were ranked at all.
+        let mut documents = vec![doc("old", "common common common", 0)];
+        documents
+            .extend((1..RANK_WINDOW).map(|i| doc(&i.to_string(), "common and more", i as i64)));
+        index(&conn, &documents).unwrap();
+
+        let terms = ["common".to_owned()];
+        let page = search(&conn, &terms, &SearchFilters::default(), None).unwrap();
+        assert!(!page.truncated);
+        assert_eq!(page.results[0].id, "old");
+
+        index(&conn, &[doc("new", "common and more", RANK_WINDOW as i64)]).unwrap();
+        let page = search(&conn, &terms, &SearchFilters::default(), None).unwrap();
+        assert!(page.truncated);
+        assert!(page.results.iter().all(|r| r.id != "old"));
+
+        // Narrowing the query brings it back.
+        let older = SearchFilters {
+            before: Some(10),
+            ..Default::default()
+        };
+        let page = search(&conn, &terms, &older, None).unwrap();
+        assert!(!page.truncated);
+        assert_eq!(page.results[0].id, "old");
+    }
+}
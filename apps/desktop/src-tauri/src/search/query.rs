@@ -0,0 +1,116 @@
+//! Turning search terms into an FTS5 `MATCH` expression.
+//!
+//! Each term matches as a prefix, plus any indexed word within a small edit
+//! distance (MiniSearch's `fuzzy: 0.2`). Candidates for fuzzy matching come
+//! from `search_terms` and must share the term's first character, which keeps
+//! the scan to a small slice of the vocabulary.
+
+use rusqlite::Connection;
+
+/// Fraction of the term length allowed as edit distance.
+const FUZZY: f64 = 0.2;
+/// Upper bound on edit distance regardless of term length.
+const MAX_DISTANCE: usize = 2;
+/// Most fuzzy variants added per term, closest first.
+const MAX_VARIANTS: usize = 16;
+
+/// Split user input into index terms the same way `unicode61` does:
+/// lowercase runs of letters and digits.
+pub fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Build the `MATCH` expression for `terms`, or `None` if there are none.
+pub fn build(conn: &Connection, terms: &[String]) -> rusqlite::Result<Option<String>> {
+    let mut groups = Vec::new();
+    for term in terms.iter().flat_map(|t| tokenize(t)) {
+        let mut alternatives = vec![format!("{} *", quote(&term))];
+        for variant in fuzzy_variants(conn, &term)? {
+            alternatives.push(quote(&variant));
+        }
+        groups.push(format!("({})", alternatives.join(" OR ")));
+    }
+    if groups.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(groups.join(" OR ")))
+}
+
+/// Indexed words within the allowed edit distance of `term`.
+fn fuzzy_variants(conn: &Connection, term: &str) -> rusqlite::Result<Vec<String>> {
+    let max = max_distance(term);
+    if max == 0 {
+        return Ok(Vec::new());
+    }
+    let Some(first) = term.chars().next() else {
+        return Ok(Vec::new());
+    };
+    let Some(upper) = char::from_u32(first as u32 + 1) else {
+        return Ok(Vec::new());
+    };
+    let len = term.chars().count();
+
+    let mut stmt = conn.prepare_cached(
+        "SELECT term FROM search_terms
+         WHERE term >= ?1 AND term < ?2 AND length(term) BETWEEN ?3 AND ?4",
+    )?;
+    let rows = stmt.query_map(
+        rusqlite::params![
+            first.to_string(),
+            upper.to_string(),
+            len.saturating_sub(max),
+            len + max
+        ],
+        |row| row.get::<_, String>(0),
+    )?;
+
+    let mut variants = Vec::new();
+    for candidate in rows {
+        let candidate = candidate?;
+        // Longer words starting with the term are already prefix matches.
+        if candidate.starts_with(term) {
+            continue;
+        }
+        if let Some(distance) = levenshtein(term, &candidate, max) {
+            variants.push((distance, candidate));
+        }
+    }
+    variants.sort();
+    variants.truncate(MAX_VARIANTS);
+    Ok(variants.into_iter().map(|(_, term)| term).collect())
+}
+
+fn max_distance(term: &str) -> usize {
+    let len = term.chars().count() as f64;
+    ((len * FUZZY).round() as usize).min(MAX_DISTANCE)
+}
+
+/// Edit distance if it is at most `max`.
+fn levenshtein(a: &str, b: &str, max: usize) -> Option<usize> {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+    for (i, ca) in a.iter().enumerate() {
+        current[0] = i + 1;
+        let mut row_min = current[0];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+            row_min = row_min.min(current[j + 1]);
+        }
+        if row_min > max {
+            return None;
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    Some(previous[b.len()]).filter(|&d| d <= max)
+}
+
+/// FTS5 string literal.
+fn quote(term: &str) -> String {
+    format!("\"{}\"", term.replace('"', "\"\""))
+}
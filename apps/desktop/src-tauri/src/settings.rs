@@ -0,0 +1,222 @@
+//! App behavior settings: closing to the tray, starting hidden, launching at
+//! login, confirming before quitting, how long quitting waits for cleanup,
+//! when to mark the user idle automatically, global hotkey bindings, the
+//! quiet hours schedule, how long the notification inbox keeps entries, and
+//! the built-in Gun relay.
+//!
+//! Settings live in `settings.json` in the app config directory and are
+//! cached in memory. Changes apply live: the close handler and quit path read
+//! the current values each time, launch-at-login is applied to the OS as
+//! part of the update, and `settings_set` registers changed hotkeys,
+//! rechecks quiet hours and restarts the relay. The OS login item wins over
+//! the saved value on startup, since the user can remove it outside the app.
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::sync::RwLock;
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Runtime};
+use tauri_plugin_autostart::ManagerExt;
+
+use crate::db::inbox::InboxRetention;
+use crate::durable;
+use crate::hotkeys::HotkeyBindings;
+use crate::quiet_hours::QuietSchedule;
+use crate::relay::RelaySettings;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AppSettings {
+    /// Closing the main window hides it to the tray instead of quitting.
+    pub close_to_tray: bool,
+    /// Keep the main window hidden in the tray on startup.
+    pub start_hidden: bool,
+    pub launch_at_login: bool,
+    /// Ask the frontend to confirm before quitting.
+    pub confirm_quit: bool,
+    /// How long quitting waits for shutdown subscribers to acknowledge.
+    pub shutdown_timeout_ms: u64,
+    /// Mark the user idle after this long without input; 0 turns it off.
+    pub auto_idle_after_secs: u64,
+    /// Mark the user idle while the screen is locked or the system sleeps.
+    pub auto_idle_on_lock: bool,
+    pub hotkeys: HotkeyBindings,
+    pub quiet_hours: QuietSchedule,
+    pub inbox_retention: InboxRetention,
+    pub relay: RelaySettings,
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            close_to_tray: true,
+            start_hidden: false,
+            launch_at_login: false,
+            confirm_quit: false,
+            shutdown_timeout_ms: 5_000,
+            auto_idle_after_secs: 5 * 60,
+            auto_idle_on_lock: true,
+            hotkeys: HotkeyBindings::default(),
+            quiet_hours: QuietSchedule::default(),
+            inbox_retention: InboxRetention::default(),
+            relay: RelaySettings::default(),
+        }
+    }
+}
+
+/// A partial update; unset fields keep their current value.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SettingsPatch {
+    pub close_to_tray: Option<bool>,
+    pub start_hidden: Option<bool>,
+    pub launch_at_login: Option<bool>,
+    pub confirm_quit: Option<bool>,
+    pub shutdown_timeout_ms: Option<u64>,
+    pub auto_idle_after_secs: Option<u64>,
+    pub auto_idle_on_lock: Option<bool>,
+    /// Replaces all bindings.
+    pub hotkeys: Option<HotkeyBindings>,
+    /// Replaces the whole schedule.
+    pub quiet_hours: Option<QuietSchedule>,
+    pub inbox_retention: Option<InboxRetention>,
+    pub relay: Option<RelaySettings>,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SettingsError {
+    #[error("Failed to save settings: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to save settings: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Failed to change launch at login: {0}")]
+    Autostart(#[from] tauri_plugin_autostart::Error),
+    #[error("Unknown time zone: {0}")]
+    TimeZone(String),
+}
+
+/// The settings store. One instance is managed by Tauri.
+pub struct Settings {
+    path: PathBuf,
+    current: RwLock<AppSettings>,
+}
+
+impl Settings {
+    /// Load from `path`, falling back to defaults if the file is missing or
+    /// unreadable.
+    pub fn load(path: PathBuf) -> Self {
+        let current = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+                eprintln!("[settings] Ignoring unreadable settings file: {e}");
+                AppSettings::default()
+            }),
+            Err(e) if e.kind() == ErrorKind::NotFound => AppSettings::default(),
+            Err(e) => {
+                eprintln!("[settings] Failed to read settings: {e}");
+                AppSettings::default()
+            }
+        };
+        Self {
+            path,
+            current: RwLock::new(current),
+        }
+    }
+
+    pub fn get(&self) -> AppSettings {
+        self.current
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// Apply `patch`, sync launch-at-login with the OS if it changed, and
+    /// persist. Nothing is saved if the OS change fails or the quiet hours
+    /// time zone is unknown.
+    pub fn update<R: Runtime>(
+        &self,
+        app: &AppHandle<R>,
+        patch: &SettingsPatch,
+    ) -> Result<AppSettings, SettingsError> {
+        let mut current = self.current.write().unwrap_or_else(|e| e.into_inner());
+        let next = AppSettings {
+            close_to_tray: patch.close_to_tray.unwrap_or(current.close_to_tray),
+            start_hidden: patch.start_hidden.unwrap_or(current.start_hidden),
+            launch_at_login: patch.launch_at_login.unwrap_or(current.launch_at_login),
+            confirm_quit: patch.confirm_quit.unwrap_or(current.confirm_quit),
+            shutdown_timeout_ms: patch
+                .shutdown_timeout_ms
+                .unwrap_or(current.shutdown_timeout_ms),
+            auto_idle_after_secs: patch
+                .auto_idle_after_secs
+                .unwrap_or(current.auto_idle_after_secs),
+            auto_idle_on_lock: patch.auto_idle_on_lock.unwrap_or(current.auto_idle_on_lock),
+            hotkeys: patch
+                .hotkeys
+                .clone()
+                .unwrap_or_else(|| current.hotkeys.clone()),
+            quiet_hours: patch
+                .quiet_hours
+                .clone()
+                .unwrap_or_else(|| current.quiet_hours.clone()),
+            inbox_retention: patch
+                .inbox_retention
+                .clone()
+                .unwrap_or_else(|| current.inbox_retention.clone()),
+            relay: patch.relay.clone().unwrap_or_else(|| current.relay.clone()),
+        };
+        if let Some(name) = &next.quiet_hours.time_zone {
+            if jiff::tz::TimeZone::get(name).is_err() {
+                return Err(SettingsError::TimeZone(name.clone()));
+            }
+        }
+        if next.launch_at_login != current.launch_at_login {
+            set_launch_at_login(app, next.launch_at_login)?;
+        }
+        if next != *current {
+            self.save(&next)?;
+            *current = next.clone();
+        }
+        Ok(next)
+    }
+
+    /// Adopt the OS login item state, in case the user changed it outside
+    /// the app.
+    pub fn refresh_launch_at_login<R: Runtime>(&self, app: &AppHandle<R>) {
+        let enabled = match app.autolaunch().is_enabled() {
+            Ok(enabled) => enabled,
+            Err(e) => {
+                eprintln!("[settings] Failed to read launch at login: {e}");
+                return;
+            }
+        };
+        let mut current = self.current.write().unwrap_or_else(|e| e.into_inner());
+        if current.launch_at_login != enabled {
+            let next = AppSettings {
+                launch_at_login: enabled,
+                ..current.clone()
+            };
+            match self.save(&next) {
+                Ok(()) => *current = next,
+                Err(e) => eprintln!("[settings] {e}"),
+            }
+        }
+    }
+
+    fn save(&self, settings: &AppSettings) -> Result<(), SettingsError> {
+        durable::write_atomic(&self.path, &serde_json::to_vec_pretty(settings)?)?;
+        Ok(())
+    }
+}
+
+fn set_launch_at_login<R: Runtime>(
+    app: &AppHandle<R>,
+    enabled: bool,
+) -> Result<(), tauri_plugin_autostart::Error> {
+    if enabled {
+        app.autolaunch().enable()
+    } else {
+        app.autolaunch().disable()
+    }
+}
@@ -0,0 +1,326 @@
+//! Quitting the app.
+//!
+//! User-initiated quits (the tray item, or closing the main window with
+//! close-to-tray turned off) go through [`request_quit`], which asks for
+//! confirmation in a native dialog first if the user enabled that setting.
+//! The OS ending the session and SIGTERM on Linux skip the prompt and call
+//! [`quit`] directly.
+//!
+//! Quitting is a handshake rather than a fixed delay. Anything that needs to
+//! clean up (e.g. setting presence offline) registers as a subscriber with
+//! `shutdown_register`. [`quit`] emits `app:before-quit` with a deadline, then
+//! exits as soon as every subscriber has called `shutdown_ack`, or when the
+//! deadline passes, logging the subscribers that didn't answer.
+
+use std::collections::BTreeSet;
+use std::sync::{Condvar, Mutex};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager, Runtime};
+use tauri_plugin_dialog::{DialogExt, MessageDialogButtons};
+
+use crate::settings::Settings;
+
+/// Bounds for the configurable shutdown deadline.
+const MIN_TIMEOUT_MS: u64 = 500;
+const MAX_TIMEOUT_MS: u64 = 30_000;
+
+/// Why the app is quitting, passed along with `app:before-quit`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum QuitReason {
+    /// The tray item, closing the window, or the frontend.
+    User,
+    /// The OS asked the app to exit, e.g. on logout.
+    SessionEnd,
+    /// SIGTERM.
+    Signal,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct BeforeQuit {
+    reason: QuitReason,
+    deadline_ms: u64,
+}
+
+/// Shutdown subscribers and progress. One instance is managed by Tauri.
+#[derive(Default)]
+pub struct Shutdown {
+    state: Mutex<State>,
+    acked: Condvar,
+}
+
+#[derive(Default)]
+struct State {
+    subscribers: BTreeSet<String>,
+    /// Subscribers that haven't acknowledged the current shutdown.
+    pending: BTreeSet<String>,
+    started: bool,
+    /// The quit confirmation is showing.
+    confirming: bool,
+    /// Set right before exiting, so the exit itself isn't intercepted.
+    finished: bool,
+}
+
+impl Shutdown {
+    /// Wait for `name` to acknowledge before exiting.
+    pub fn register(&self, name: String) {
+        self.lock().subscribers.insert(name);
+    }
+
+    /// Stop waiting for `name`, including for a shutdown already under way.
+    pub fn unregister(&self, name: &str) {
+        let mut state = self.lock();
+        state.subscribers.remove(name);
+        state.pending.remove(name);
+        self.acked.notify_all();
+    }
+
+    /// `name` has finished cleaning up.
+    pub fn acknowledge(&self, name: &str) {
+        let mut state = self.lock();
+        if state.pending.remove(name) {
+            self.acked.notify_all();
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.lock().finished
+    }
+
+    /// Start asking to quit. False if the question is already showing.
+    fn start_confirming(&self) -> bool {
+        !std::mem::replace(&mut self.lock().confirming, true)
+    }
+
+    fn stop_confirming(&self) {
+        self.lock().confirming = false;
+    }
+
+    /// Start a shutdown. False if one is already under way.
+    fn start(&self) -> bool {
+        let mut state = self.lock();
+        if state.started {
+            return false;
+        }
+        state.started = true;
+        state.pending = state.subscribers.clone();
+        true
+    }
+
+    /// Block until every subscriber acknowledged or `timeout` passed, then
+    /// mark the shutdown finished. Returns the subscribers still pending.
+    fn wait(&self, timeout: Duration) -> Vec<String> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.lock();
+        while !state.pending.is_empty() {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+            state = self
+                .acked
+                .wait_timeout(state, deadline - now)
+                .unwrap_or_else(|e| e.into_inner())
+                .0;
+        }
+        state.finished = true;
+        std::mem::take(&mut state.pending).into_iter().collect()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Quit, asking first if the user wants to be asked.
+pub fn request_quit<R: Runtime>(app: &AppHandle<R>) {
+    if !app.state::<Settings>().get().confirm_quit {
+        quit(app, QuitReason::User);
+        return;
+    }
+    // One question at a time, however often Quit is clicked.
+    if !app.state::<Shutdown>().start_confirming() {
+        return;
+    }
+    let mut dialog = app
+        .dialog()
+        .message("Are you sure you want to quit Nodes?")
+        .title("Quit Nodes")
+        .buttons(MessageDialogButtons::OkCancelCustom(
+            "Quit".into(),
+            "Cancel".into(),
+        ));
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.show();
+        let _ = window.set_focus();
+        dialog = dialog.parent(&window);
+    }
+    let app = app.clone();
+    dialog.show(move |confirmed| {
+        app.state::<Shutdown>().stop_confirming();
+        if confirmed {
+            quit(&app, QuitReason::User);
+        }
+    });
+}
+
+/// Let subscribers clean up, then exit. Does nothing if a shutdown is
+/// already under way.
+pub fn quit<R: Runtime>(app: &AppHandle<R>, reason: QuitReason) {
+    if !app.state::<Shutdown>().start() {
+        return;
+    }
+    let timeout_ms = app
+        .state::<Settings>()
+        .get()
+        .shutdown_timeout_ms
+        .clamp(MIN_TIMEOUT_MS, MAX_TIMEOUT_MS);
+    let _ = app.emit(
+        "app:before-quit",
+        BeforeQuit {
+            reason,
+            deadline_ms: timeout_ms,
+        },
+    );
+
+    let app = app.clone();
+    std::thread::spawn(move || {
+        let stragglers = app
+            .state::<Shutdown>()
+            .wait(Duration::from_millis(timeout_ms));
+        if !stragglers.is_empty() {
+            eprintln!(
+                "[shutdown] Exiting after {timeout_ms} ms without an acknowledgement from: {}",
+                stragglers.join(", ")
+            );
+        }
+        app.exit(0);
+    });
+}
+
+/// Quit on SIGTERM, e.g. from systemd or a session manager.
+#[cfg(target_os = "linux")]
+pub fn watch_signals<R: Runtime>(app: &AppHandle<R>) -> std::io::Result<()> {
+    use signal_hook::consts::SIGTERM;
+    use signal_hook::iterator::Signals;
+
+    let mut signals = Signals::new([SIGTERM])?;
+    let app = app.clone();
+    std::thread::spawn(move || {
+        for _ in signals.forever() {
+            quit(&app, QuitReason::Signal);
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::thread;
+
+    const LONG: Duration = Duration::from_secs(10);
+
+    fn subscribed(names: &[&str]) -> Arc<Shutdown> {
+        let shutdown = Arc::new(Shutdown::default());
+        for name in names {
+            shutdown.register(name.to_string());
+        }
+        shutdown
+    }
+
+    /// Run `wait` on another thread, returning its stragglers and how long
+    /// it blocked.
+    fn wait_in_background(
+        shutdown: &Arc<Shutdown>,
+        timeout: Duration,
+    ) -> thread::JoinHandle<(Vec<String>, Duration)> {
+        let shutdown = shutdown.clone();
+        thread::spawn(move || {
+            let started = Instant::now();
+            let stragglers = shutdown.wait(timeout);
+            (stragglers, started.elapsed())
+        })
+    }
+
+    #[test]
+    fn exits_as_soon_as_everyone_acknowledges() {
+        let shutdown = subscribed(&["presence", "sync"]);
+        assert!(shutdown.start());
+        let waiter = wait_in_background(&shutdown, LONG);
+
+        shutdown.acknowledge("presence");
+        thread::sleep(Duration::from_millis(50));
+        assert!(!shutdown.is_finished());
+        shutdown.acknowledge("sync");
+
+        let (stragglers, waited) = waiter.join().unwrap();
+        assert!(stragglers.is_empty());
+        assert!(waited < LONG / 2);
+        assert!(shutdown.is_finished());
+    }
+
+    #[test]
+    fn reports_stragglers_at_the_deadline() {
+        let shutdown = subscribed(&["presence", "sync", "uploads"]);
+        assert!(shutdown.start());
+        shutdown.acknowledge("sync");
+        // Unknown and repeated acknowledgements are ignored.
+        shutdown.acknowledge("nobody");
+        shutdown.acknowledge("sync");
+
+        let timeout = Duration::from_millis(100);
+        let (stragglers, waited) = wait_in_background(&shutdown, timeout).join().unwrap();
+        assert_eq!(stragglers, ["presence", "uploads"]);
+        assert!(waited >= timeout);
+        assert!(shutdown.is_finished());
+    }
+
+    #[test]
+    fn unregistering_during_a_shutdown_unblocks_wait() {
+        let shutdown = subscribed(&["presence", "popout:chat"]);
+        assert!(shutdown.start());
+        shutdown.acknowledge("presence");
+        let waiter = wait_in_background(&shutdown, LONG);
+
+        // A pop-out closing mid-shutdown will never acknowledge.
+        thread::sleep(Duration::from_millis(50));
+        shutdown.unregister("popout:chat");
+
+        let (stragglers, waited) = waiter.join().unwrap();
+        assert!(stragglers.is_empty());
+        assert!(waited < LONG / 2);
+    }
+
+    #[test]
+    fn only_the_first_start_begins_a_shutdown() {
+        let shutdown = subscribed(&["presence"]);
+        assert!(shutdown.start());
+        // A late subscriber isn't waited for by the shutdown under way.
+        shutdown.register("late".into());
+        assert!(!shutdown.start());
+        shutdown.acknowledge("presence");
+        assert!(shutdown.wait(LONG).is_empty());
+    }
+
+    #[test]
+    fn with_no_subscribers_wait_returns_immediately() {
+        let shutdown = Shutdown::default();
+        assert!(shutdown.start());
+        assert!(shutdown.wait(LONG).is_empty());
+        assert!(shutdown.is_finished());
+    }
+
+    #[test]
+    fn only_one_confirmation_shows_at_a_time() {
+        let shutdown = Shutdown::default();
+        assert!(shutdown.start_confirming());
+        assert!(!shutdown.start_confirming());
+        shutdown.stop_confirming();
+        assert!(shutdown.start_confirming());
+    }
+}
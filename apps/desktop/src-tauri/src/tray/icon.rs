@@ -0,0 +1,408 @@
+//! Tray icon variants: the app icon with a presence dot and an unread badge.
+//!
+//! Variants are drawn in software from the bundled 64px icon at the size the
+//! platform's tray uses, encoded as PNG and cached, so flipping between
+//! states only renders each combination once. Drawing is deterministic, so
+//! a variant's PNG bytes are stable and can be compared directly.
+
+use std::collections::HashMap;
+
+use super::UserStatus;
+
+const BASE_ICON: &[u8] = include_bytes!("../../icons/64x64.png");
+
+/// Tray icon edge in pixels: the 22pt menu bar at 2x on macOS, 16px at up
+/// to 200% scaling elsewhere.
+#[cfg(target_os = "macos")]
+const SIZE: u32 = 44;
+#[cfg(not(target_os = "macos"))]
+const SIZE: u32 = 32;
+
+/// Counts above this show as "9+".
+const MAX_BADGE_COUNT: u32 = 9;
+
+/// Tailwind 500 shades, as used by `StatusSelector`.
+const ONLINE: [f32; 3] = rgb(0x22c55e);
+const IDLE: [f32; 3] = rgb(0xeab308);
+const DND: [f32; 3] = rgb(0xef4444);
+const INVISIBLE: [f32; 3] = rgb(0x6b7280);
+const BADGE: [f32; 3] = rgb(0xef4444);
+const BADGE_TEXT: [f32; 3] = rgb(0xffffff);
+
+/// 3×5 glyphs for the badge, one row per entry, high bit on the left.
+const GLYPH_WIDTH: usize = 3;
+const GLYPH_HEIGHT: usize = 5;
+const DIGITS: [[u8; GLYPH_HEIGHT]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b001, 0b001, 0b001],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+const PLUS: [u8; GLYPH_HEIGHT] = [0b000, 0b010, 0b111, 0b010, 0b000];
+
+/// What to draw over the base icon.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Overlay {
+    pub status: UserStatus,
+    /// Unread count for the badge; none is drawn at zero.
+    pub unread: u32,
+}
+
+/// Renders and caches icon variants.
+pub struct IconRenderer {
+    base: Canvas,
+    cache: HashMap<Overlay, Vec<u8>>,
+}
+
+impl IconRenderer {
+    pub fn new() -> Self {
+        Self {
+            base: Canvas::decode(BASE_ICON).resample(SIZE),
+            cache: HashMap::new(),
+        }
+    }
+
+    /// The icon for `overlay` as PNG.
+    pub fn png(&mut self, overlay: Overlay) -> &[u8] {
+        let overlay = Overlay {
+            unread: overlay.unread.min(MAX_BADGE_COUNT + 1),
+            ..overlay
+        };
+        self.cache
+            .entry(overlay)
+            .or_insert_with(|| render(&self.base, overlay).encode())
+    }
+}
+
+fn render(base: &Canvas, overlay: Overlay) -> Canvas {
+    let mut canvas = base.clone();
+    let size = canvas.size as f32;
+    // Each mark is cut out of the icon with a transparent ring around it,
+    // so it stays legible on any tray background.
+    let gap = (size / 16.0).max(1.0);
+
+    let radius = size * 0.2;
+    let center = size - radius - 0.5;
+    let dot = |x: f32, y: f32, grow: f32| circle(x, y, center, center, radius + grow);
+    canvas.clear(|x, y| dot(x, y, gap));
+    canvas.fill(status_color(overlay.status), |x, y| dot(x, y, 0.0));
+
+    if overlay.unread > 0 {
+        let text = badge_glyphs(overlay.unread);
+        let height = (size * 0.5).round();
+        let scale = ((height * 0.6) / GLYPH_HEIGHT as f32).round().max(1.0) as usize;
+        let text_width = (text.len() * (GLYPH_WIDTH + 1) - 1) * scale;
+        let width = (text_width as f32 + height * 0.5).max(height);
+        // A circle for one digit, a pill for more, in the top-right corner.
+        let radius = height / 2.0;
+        let (left, right, middle) = (size - width + radius, size - radius, radius);
+        let pill = |x: f32, y: f32, grow: f32| capsule(x, y, left, right, middle, radius + grow);
+        canvas.clear(|x, y| pill(x, y, gap));
+        canvas.fill(BADGE, |x, y| pill(x, y, 0.0));
+
+        let text_left = ((left + right) / 2.0 - text_width as f32 / 2.0).round() as usize;
+        let text_top = (middle - (GLYPH_HEIGHT * scale) as f32 / 2.0).round() as usize;
+        for (index, glyph) in text.iter().enumerate() {
+            let glyph_left = text_left + index * (GLYPH_WIDTH + 1) * scale;
+            canvas.fill(BADGE_TEXT, |x, y| {
+                let (col, row) = (x as usize, y as usize);
+                if col < glyph_left || row < text_top {
+                    return 0.0;
+                }
+                let (col, row) = ((col - glyph_left) / scale, (row - text_top) / scale);
+                let lit = row < GLYPH_HEIGHT
+                    && col < GLYPH_WIDTH
+                    && glyph[row] & (1 << (GLYPH_WIDTH - 1 - col)) != 0;
+                if lit {
+                    1.0
+                } else {
+                    0.0
+                }
+            });
+        }
+    }
+    canvas
+}
+
+fn status_color(status: UserStatus) -> [f32; 3] {
+    match status {
+        UserStatus::Online => ONLINE,
+        UserStatus::Idle => IDLE,
+        UserStatus::Dnd => DND,
+        UserStatus::Offline => INVISIBLE,
+    }
+}
+
+fn badge_glyphs(count: u32) -> Vec<[u8; GLYPH_HEIGHT]> {
+    if count > MAX_BADGE_COUNT {
+        vec![DIGITS[MAX_BADGE_COUNT as usize], PLUS]
+    } else {
+        vec![DIGITS[count as usize]]
+    }
+}
+
+/// Coverage of the pixel centered at (`x`, `y`) by a circle.
+fn circle(x: f32, y: f32, cx: f32, cy: f32, radius: f32) -> f32 {
+    let distance = ((x - cx).powi(2) + (y - cy).powi(2)).sqrt();
+    (radius - distance + 0.5).clamp(0.0, 1.0)
+}
+
+/// Coverage by a horizontal capsule around the segment from `left` to
+/// `right` at height `cy`.
+fn capsule(x: f32, y: f32, left: f32, right: f32, cy: f32, radius: f32) -> f32 {
+    circle(x.clamp(left, right), cy, x, y, radius)
+}
+
+const fn rgb(hex: u32) -> [f32; 3] {
+    [
+        ((hex >> 16) & 0xff) as f32 / 255.0,
+        ((hex >> 8) & 0xff) as f32 / 255.0,
+        (hex & 0xff) as f32 / 255.0,
+    ]
+}
+
+/// A square image with premultiplied RGBA in 0..=1.
+#[derive(Clone)]
+struct Canvas {
+    size: u32,
+    pixels: Vec<[f32; 4]>,
+}
+
+impl Canvas {
+    /// Decode a square RGBA8 PNG.
+    fn decode(png: &[u8]) -> Self {
+        let mut reader = png::Decoder::new(png)
+            .read_info()
+            .expect("bundled icon is a valid PNG");
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader
+            .next_frame(&mut buf)
+            .expect("bundled icon is a valid PNG");
+        assert_eq!(info.color_type, png::ColorType::Rgba);
+        assert_eq!(info.width, info.height);
+        let pixels = buf[..info.buffer_size()]
+            .chunks_exact(4)
+            .map(|p| {
+                let alpha = p[3] as f32 / 255.0;
+                [
+                    p[0] as f32 / 255.0 * alpha,
+                    p[1] as f32 / 255.0 * alpha,
+                    p[2] as f32 / 255.0 * alpha,
+                    alpha,
+                ]
+            })
+            .collect();
+        Self {
+            size: info.width,
+            pixels,
+        }
+    }
+
+    /// Scale to `size` by averaging the source area under each pixel.
+    fn resample(&self, size: u32) -> Self {
+        if size == self.size {
+            return self.clone();
+        }
+        let scale = self.size as f32 / size as f32;
+        // Source pixels overlapping [start, end), with the overlap of each.
+        let spans: Vec<Vec<(usize, f32)>> = (0..size)
+            .map(|i| {
+                let (start, end) = (i as f32 * scale, (i + 1) as f32 * scale);
+                (start.floor() as usize..(end.ceil() as usize).min(self.size as usize))
+                    .map(|s| {
+                        let overlap = end.min(s as f32 + 1.0) - start.max(s as f32);
+                        (s, overlap / scale)
+                    })
+                    .collect()
+            })
+            .collect();
+
+        let mut pixels = Vec::with_capacity((size * size) as usize);
+        for rows in &spans {
+            for cols in &spans {
+                let mut sum = [0.0; 4];
+                for &(row, wy) in rows {
+                    for &(col, wx) in cols {
+                        let pixel = self.pixels[row * self.size as usize + col];
+                        for (total, channel) in sum.iter_mut().zip(pixel) {
+                            *total += channel * wx * wy;
+                        }
+                    }
+                }
+                pixels.push(sum);
+            }
+        }
+        Self { size, pixels }
+    }
+
+    /// Erase by the coverage `shape` returns for each pixel center.
+    fn clear(&mut self, shape: impl Fn(f32, f32) -> f32) {
+        self.paint(|pixel, x, y| {
+            let keep = 1.0 - shape(x, y);
+            pixel.iter_mut().for_each(|c| *c *= keep);
+        });
+    }
+
+    /// Paint an opaque `color` by the coverage `shape` returns.
+    fn fill(&mut self, color: [f32; 3], shape: impl Fn(f32, f32) -> f32) {
+        self.paint(|pixel, x, y| {
+            let cover = shape(x, y);
+            let source = [color[0], color[1], color[2], 1.0];
+            for (c, s) in pixel.iter_mut().zip(source) {
+                *c = s * cover + *c * (1.0 - cover);
+            }
+        });
+    }
+
+    fn paint(&mut self, mut f: impl FnMut(&mut [f32; 4], f32, f32)) {
+        let size = self.size as usize;
+        for (index, pixel) in self.pixels.iter_mut().enumerate() {
+            let (x, y) = ((index % size) as f32 + 0.5, (index / size) as f32 + 0.5);
+            f(pixel, x, y);
+        }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let rgba: Vec<u8> = self
+            .pixels
+            .iter()
+            .flat_map(|&[r, g, b, a]| {
+                let straight = |c: f32| if a > 0.0 { c / a } else { 0.0 };
+                [straight(r), straight(g), straight(b), a].map(|c| (c * 255.0).round() as u8)
+            })
+            .collect();
+
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, self.size, self.size);
+        encoder.set_color(png::ColorType::Rgba);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder
+            .write_header()
+            .expect("writing to memory can't fail");
+        writer
+            .write_image_data(&rgba)
+            .expect("writing to memory can't fail");
+        writer.finish().expect("writing to memory can't fail");
+        png
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE_F: f32 = SIZE as f32;
+
+    /// Straight RGBA8 pixels of a PNG, checking it's `SIZE` square.
+    fn decode(png: &[u8]) -> Vec<[u8; 4]> {
+        let mut reader = png::Decoder::new(png).read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).unwrap();
+        assert_eq!((info.width, info.height), (SIZE, SIZE));
+        assert_eq!(info.color_type, png::ColorType::Rgba);
+        buf[..info.buffer_size()]
+            .chunks_exact(4)
+            .map(|p| [p[0], p[1], p[2], p[3]])
+            .collect()
+    }
+
+    fn pixel(pixels: &[[u8; 4]], x: f32, y: f32) -> [u8; 4] {
+        pixels[y as usize * SIZE as usize + x as usize]
+    }
+
+    fn opaque(color: [f32; 3]) -> [u8; 4] {
+        let [r, g, b] = color.map(|c| (c * 255.0).round() as u8);
+        [r, g, b, 255]
+    }
+
+    fn overlay(status: UserStatus, unread: u32) -> Overlay {
+        Overlay { status, unread }
+    }
+
+    #[test]
+    fn renders_at_tray_size() {
+        let mut icon = IconRenderer::new();
+        for unread in [0, 1, 42] {
+            decode(icon.png(overlay(UserStatus::Online, unread)));
+        }
+    }
+
+    #[test]
+    fn status_dot_sits_bottom_right() {
+        let mut icon = IconRenderer::new();
+        let radius = SIZE_F * 0.2;
+        let center = SIZE_F - radius - 0.5;
+        let gap = (SIZE_F / 16.0).max(1.0);
+        for (status, color) in [
+            (UserStatus::Online, ONLINE),
+            (UserStatus::Idle, IDLE),
+            (UserStatus::Dnd, DND),
+            (UserStatus::Offline, INVISIBLE),
+        ] {
+            let pixels = decode(icon.png(overlay(status, 0)));
+            assert_eq!(pixel(&pixels, center, center), opaque(color), "{status:?}");
+            // The ring cut around it, left of and above the dot.
+            let ring = center - radius - gap / 2.0;
+            assert_eq!(pixel(&pixels, ring, center)[3], 0, "{status:?}");
+            assert_eq!(pixel(&pixels, center, ring)[3], 0, "{status:?}");
+        }
+    }
+
+    #[test]
+    fn badge_sits_top_right() {
+        let mut icon = IconRenderer::new();
+        let plain = decode(icon.png(overlay(UserStatus::Online, 0)));
+        let badged = decode(icon.png(overlay(UserStatus::Online, 3)));
+        // Half the icon high; its left edge, clear of the digit.
+        let height = (SIZE_F * 0.5).round();
+        let (x, y) = (SIZE_F - height + 2.0, height / 2.0);
+        assert_eq!(pixel(&badged, x, y), opaque(BADGE));
+        assert_ne!(pixel(&plain, x, y), opaque(BADGE));
+
+        let in_badge = |pixels: &[[u8; 4]], color: [u8; 4]| {
+            (0..height as usize).any(|y| {
+                (SIZE as usize - height as usize..SIZE as usize)
+                    .any(|x| pixels[y * SIZE as usize + x] == color)
+            })
+        };
+        assert!(in_badge(&badged, opaque(BADGE_TEXT)));
+        assert!(!in_badge(&plain, opaque(BADGE)));
+        // Below the badge and the ring cut around it, nothing changes.
+        let gap = (SIZE_F / 16.0).max(1.0);
+        let below = (height + gap + 1.0).ceil() as usize * SIZE as usize;
+        assert_eq!(badged[below..], plain[below..]);
+    }
+
+    #[test]
+    fn large_counts_widen_the_badge() {
+        let mut icon = IconRenderer::new();
+        let one = decode(icon.png(overlay(UserStatus::Online, 1)));
+        let many = decode(icon.png(overlay(UserStatus::Online, 10)));
+        // Left of a one digit badge but inside the "9+" pill.
+        let (x, y) = (SIZE_F / 2.0 - SIZE_F / 8.0, (SIZE_F * 0.25).round());
+        assert_eq!(pixel(&many, x, y), opaque(BADGE));
+        assert_ne!(pixel(&one, x, y), opaque(BADGE));
+    }
+
+    #[test]
+    fn caches_identical_overlays() {
+        let mut icon = IconRenderer::new();
+        let first = icon.png(overlay(UserStatus::Idle, 2)).as_ptr();
+        assert_eq!(icon.png(overlay(UserStatus::Idle, 2)).as_ptr(), first);
+        assert_eq!(icon.cache.len(), 1);
+
+        // Everything above nine is drawn as "9+".
+        let ten = icon.png(overlay(UserStatus::Idle, 10)).to_vec();
+        assert_eq!(icon.png(overlay(UserStatus::Idle, 250)), ten);
+        assert_eq!(icon.cache.len(), 2);
+
+        assert_ne!(icon.png(overlay(UserStatus::Dnd, 2)), ten);
+        assert_eq!(icon.cache.len(), 3);
+    }
+}
@@ -0,0 +1,412 @@
+//! System tray support for Nodes.
+//!
+//! Provides a tray icon with menu for quick access and background operation.
+//! The menu reflects app state pushed by the frontend with `tray_update`:
+//! the presence status, whether notifications are muted and the most recent
+//! DM conversations. The unread count in the tooltip comes from the
+//! notification inbox instead (see [`crate::db::inbox`]). Updates are
+//! applied to the existing menu items in place, so the menu doesn't flicker
+//! or close while it's open. The icon itself shows the status and unread
+//! count as overlays (see [`icon`]).
+//!
+//! Menu actions that change frontend state are emitted as events:
+//! `tray:set-status`, `tray:mute`, `tray:unmute` and `tray:open-dm`. A DM
+//! that's popped out is focused instead of being opened in the main window.
+
+mod icon;
+
+use std::sync::{Mutex, MutexGuard};
+
+use serde::{Deserialize, Serialize};
+use tauri::{
+    image::Image,
+    menu::{CheckMenuItem, Menu, MenuItem, PredefinedMenuItem, Submenu},
+    tray::{MouseButton, TrayIconBuilder, TrayIconEvent},
+    AppHandle, Emitter, Manager, Runtime, Wry,
+};
+
+use crate::popout::{self, Popout};
+use icon::{IconRenderer, Overlay};
+
+const TRAY_ID: &str = "nodes-main-tray";
+const MAX_RECENT_DMS: usize = 5;
+/// Where the recent DMs start in the menu, after Show, Status and Mute.
+const DM_POSITION: usize = 5;
+/// Menu id, label and duration in minutes.
+const MUTE_OPTIONS: [(&str, &str, u64); 2] = [
+    ("mute:1h", "For 1 Hour", 60),
+    ("mute:8h", "For 8 Hours", 8 * 60),
+];
+
+/// `UserStatus` from `@nodes/core`. `Offline` is shown as "Invisible".
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UserStatus {
+    #[default]
+    Online,
+    Idle,
+    Dnd,
+    Offline,
+}
+
+impl UserStatus {
+    const ALL: [UserStatus; 4] = [Self::Online, Self::Idle, Self::Dnd, Self::Offline];
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Online => "Online",
+            Self::Idle => "Idle",
+            Self::Dnd => "Do Not Disturb",
+            Self::Offline => "Invisible",
+        }
+    }
+
+    fn menu_id(self) -> &'static str {
+        match self {
+            Self::Online => "status:online",
+            Self::Idle => "status:idle",
+            Self::Dnd => "status:dnd",
+            Self::Offline => "status:offline",
+        }
+    }
+}
+
+/// App state shown in the tray.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TrayState {
+    pub status: UserStatus,
+    /// Notifications are muted for now.
+    pub muted: bool,
+    /// Unread notifications in the inbox; set with [`set_unread`] rather
+    /// than by the frontend.
+    #[serde(skip)]
+    pub unread: u32,
+    /// Most recent first; only the first five are shown.
+    pub recent_dms: Vec<RecentDm>,
+}
+
+/// A DM conversation to jump to, sent back with `tray:open-dm`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecentDm {
+    pub conversation_id: String,
+    pub recipient_key: String,
+    pub name: String,
+    #[serde(default)]
+    pub unread: u32,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct Mute {
+    minutes: u64,
+}
+
+/// The tray menu and the state it shows. One instance is managed by Tauri.
+pub struct Tray<R: Runtime = Wry> {
+    menu: Mutex<TrayMenu<R>>,
+}
+
+struct TrayMenu<R: Runtime> {
+    shown: TrayState,
+    menu: Menu<R>,
+    statuses: Vec<(UserStatus, CheckMenuItem<R>)>,
+    mute: Submenu<R>,
+    unmute: MenuItem<R>,
+    /// One item per DM slot; only the first `shown.recent_dms.len()` are in
+    /// the menu.
+    dms: Vec<MenuItem<R>>,
+    /// Follows the DMs while there are any.
+    dm_separator: PredefinedMenuItem<R>,
+    icon: IconRenderer,
+}
+
+impl<R: Runtime> Tray<R> {
+    /// Bring the menu and tooltip in line with `next`, touching only what
+    /// changed.
+    pub fn update(&self, app: &AppHandle<R>, mut next: TrayState) -> tauri::Result<()> {
+        next.recent_dms.truncate(MAX_RECENT_DMS);
+        let mut menu = self.lock();
+        next.unread = menu.shown.unread;
+        if next.status != menu.shown.status {
+            menu.check_status(next.status)?;
+            menu.show_icon(app, next.status, next.unread)?;
+        }
+        if next.muted != menu.shown.muted {
+            menu.mute.set_text(mute_label(next.muted))?;
+            menu.unmute.set_enabled(next.muted)?;
+        }
+        if next.recent_dms != menu.shown.recent_dms {
+            menu.show_dms(&next.recent_dms)?;
+        }
+        menu.shown = next;
+        Ok(())
+    }
+
+    fn show_unread(&self, app: &AppHandle<R>, unread: u32) -> tauri::Result<()> {
+        let mut menu = self.lock();
+        if unread == menu.shown.unread {
+            return Ok(());
+        }
+        let status = menu.shown.status;
+        menu.show_icon(app, status, unread)?;
+        if let Some(tray) = app.tray_by_id(TRAY_ID) {
+            tray.set_tooltip(Some(tooltip(unread)))?;
+        }
+        menu.shown.unread = unread;
+        Ok(())
+    }
+
+    /// Check the item the user picked right away; the frontend confirms it
+    /// with the next update.
+    fn select_status(&self, app: &AppHandle<R>, status: UserStatus) {
+        let mut menu = self.lock();
+        // Clicking toggles a check item natively, so re-apply all of them.
+        let unread = menu.shown.unread;
+        let shown = menu
+            .check_status(status)
+            .and_then(|()| menu.show_icon(app, status, unread));
+        if let Err(e) = shown {
+            eprintln!("[tray] Failed to update status: {e}");
+        }
+        menu.shown.status = status;
+    }
+
+    fn recent_dm(&self, index: usize) -> Option<RecentDm> {
+        self.lock().shown.recent_dms.get(index).cloned()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, TrayMenu<R>> {
+        self.menu.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<R: Runtime> TrayMenu<R> {
+    fn check_status(&self, status: UserStatus) -> tauri::Result<()> {
+        for (item_status, item) in &self.statuses {
+            item.set_checked(*item_status == status)?;
+        }
+        Ok(())
+    }
+
+    fn show_icon(
+        &mut self,
+        app: &AppHandle<R>,
+        status: UserStatus,
+        unread: u32,
+    ) -> tauri::Result<()> {
+        let png = self.icon.png(Overlay { status, unread });
+        if let Some(tray) = app.tray_by_id(TRAY_ID) {
+            tray.set_icon(Some(Image::from_bytes(png)?))?;
+        }
+        Ok(())
+    }
+
+    /// Relabel, insert or remove DM items to match `dms`.
+    fn show_dms(&self, dms: &[RecentDm]) -> tauri::Result<()> {
+        let shown = &self.shown.recent_dms;
+        for (index, (slot, dm)) in self.dms.iter().zip(dms).enumerate() {
+            let label = dm_label(dm);
+            if shown.get(index).map(dm_label).as_ref() != Some(&label) {
+                slot.set_text(label)?;
+            }
+            if index >= shown.len() {
+                self.menu.insert(slot, DM_POSITION + index)?;
+            }
+        }
+        for slot in self.dms.iter().take(shown.len()).skip(dms.len()) {
+            self.menu.remove(slot)?;
+        }
+        if shown.is_empty() && !dms.is_empty() {
+            self.menu
+                .insert(&self.dm_separator, DM_POSITION + dms.len())?;
+        } else if !shown.is_empty() && dms.is_empty() {
+            self.menu.remove(&self.dm_separator)?;
+        }
+        Ok(())
+    }
+}
+
+/// Show `unread` notifications in the icon and tooltip. Safe to call from
+/// any thread; the change is applied on the main thread.
+pub fn set_unread<R: Runtime>(app: &AppHandle<R>, unread: u32) {
+    let handle = app.clone();
+    let result = app.run_on_main_thread(move || {
+        if let Err(e) = handle.state::<Tray<R>>().show_unread(&handle, unread) {
+            eprintln!("[tray] Failed to show unread count: {e}");
+        }
+    });
+    if let Err(e) = result {
+        eprintln!("[tray] Failed to show unread count: {e}");
+    }
+}
+
+/// Creates and configures the system tray for the application.
+pub fn create_tray<R: Runtime>(app: &AppHandle<R>) -> tauri::Result<()> {
+    let shown = TrayState::default();
+
+    // Build the tray menu
+    let show_item = MenuItem::with_id(app, "show", "Show Nodes", true, None::<&str>)?;
+    let statuses = UserStatus::ALL
+        .into_iter()
+        .map(|status| {
+            let checked = status == shown.status;
+            CheckMenuItem::with_id(
+                app,
+                status.menu_id(),
+                status.label(),
+                true,
+                checked,
+                None::<&str>,
+            )
+            .map(|item| (status, item))
+        })
+        .collect::<tauri::Result<Vec<_>>>()?;
+    let status_items: Vec<&dyn tauri::menu::IsMenuItem<R>> = statuses
+        .iter()
+        .map(|(_, item)| item as &dyn tauri::menu::IsMenuItem<R>)
+        .collect();
+    let status_menu = Submenu::with_id_and_items(app, "status", "Status", true, &status_items)?;
+
+    let mute_items = MUTE_OPTIONS
+        .into_iter()
+        .map(|(id, label, _)| MenuItem::with_id(app, id, label, true, None::<&str>))
+        .collect::<tauri::Result<Vec<_>>>()?;
+    let unmute = MenuItem::with_id(app, "unmute", "Unmute", shown.muted, None::<&str>)?;
+    let mute = Submenu::with_id_and_items(
+        app,
+        "mute",
+        mute_label(shown.muted),
+        true,
+        &[
+            &mute_items[0],
+            &mute_items[1],
+            &PredefinedMenuItem::separator(app)?,
+            &unmute,
+        ],
+    )?;
+
+    let quit_item = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
+    let menu = Menu::with_items(
+        app,
+        &[
+            &show_item,
+            &PredefinedMenuItem::separator(app)?,
+            &status_menu,
+            &mute,
+            &PredefinedMenuItem::separator(app)?,
+            &quit_item,
+        ],
+    )?;
+
+    let dms = (0..MAX_RECENT_DMS)
+        .map(|index| MenuItem::with_id(app, format!("dm:{index}"), "", true, None::<&str>))
+        .collect::<tauri::Result<Vec<_>>>()?;
+
+    let mut icon = IconRenderer::new();
+    let png = icon.png(Overlay {
+        status: shown.status,
+        unread: shown.unread,
+    });
+
+    // Build the tray icon with a unique ID to prevent duplicates
+    let _tray = TrayIconBuilder::with_id(TRAY_ID)
+        .icon(Image::from_bytes(png)?)
+        .tooltip(tooltip(shown.unread))
+        .menu(&menu)
+        .show_menu_on_left_click(false)
+        .on_menu_event(|app, event| on_menu_event(app, event.id.as_ref()))
+        .on_tray_icon_event(|tray, event| {
+            if let TrayIconEvent::Click {
+                button: MouseButton::Left,
+                ..
+            } = event
+            {
+                show_main_window(tray.app_handle());
+            }
+        })
+        .build(app)?;
+
+    app.manage(Tray {
+        menu: Mutex::new(TrayMenu {
+            shown,
+            menu,
+            statuses,
+            mute,
+            unmute,
+            dms,
+            dm_separator: PredefinedMenuItem::separator(app)?,
+            icon,
+        }),
+    });
+
+    Ok(())
+}
+
+fn on_menu_event<R: Runtime>(app: &AppHandle<R>, id: &str) {
+    match id {
+        "show" => show_main_window(app),
+        "quit" => crate::shutdown::request_quit(app),
+        "unmute" => {
+            let _ = app.emit("tray:unmute", ());
+        }
+        _ => {
+            if let Some(status) = UserStatus::ALL.into_iter().find(|s| s.menu_id() == id) {
+                app.state::<Tray<R>>().select_status(app, status);
+                let _ = app.emit("tray:set-status", status);
+            } else if let Some((_, _, minutes)) = MUTE_OPTIONS.iter().find(|(m, _, _)| *m == id) {
+                let _ = app.emit("tray:mute", Mute { minutes: *minutes });
+            } else if let Some(index) = id.strip_prefix("dm:").and_then(|i| i.parse().ok()) {
+                if let Some(dm) = app.state::<Tray<R>>().recent_dm(index) {
+                    open_dm(app, dm);
+                }
+            }
+        }
+    }
+}
+
+/// Focus the DM's pop-out if it has one, otherwise open it in the main
+/// window.
+fn open_dm<R: Runtime>(app: &AppHandle<R>, dm: RecentDm) {
+    let popout = Popout::Dm {
+        conversation_id: dm.conversation_id.clone(),
+        recipient_key: dm.recipient_key.clone(),
+    };
+    if popout::focus(app, &popout).is_none() {
+        show_main_window(app);
+        let _ = app.emit_to(popout::MAIN_LABEL, "tray:open-dm", dm);
+    }
+}
+
+fn show_main_window<R: Runtime>(app: &AppHandle<R>) {
+    if let Some(window) = app.get_webview_window("main") {
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+fn mute_label(muted: bool) -> &'static str {
+    if muted {
+        "Notifications Muted"
+    } else {
+        "Mute Notifications"
+    }
+}
+
+fn tooltip(unread: u32) -> String {
+    match unread {
+        0 => "Nodes".to_string(),
+        1 => "Nodes - 1 unread notification".to_string(),
+        n => format!("Nodes - {n} unread notifications"),
+    }
+}
+
+fn dm_label(dm: &RecentDm) -> String {
+    // A lone `&` marks a mnemonic on Windows.
+    let name = dm.name.replace('&', "&&");
+    if dm.unread > 0 {
+        format!("{name} ({})", dm.unread)
+    } else {
+        name
+    }
+}
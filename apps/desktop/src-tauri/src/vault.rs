@@ -0,0 +1,399 @@
+//! Optional "remember me" credential vault.
+//!
+//! When a user opts in, the keystore's Argon2-derived wrapping key is kept in
+//! the platform secret store (Keychain, Credential Manager, or the Secret
+//! Service D-Bus API on Linux) so the next launch can unlock without a
+//! passphrase. The keypair itself never leaves the keystore file.
+//!
+//! If no secret-service agent is reachable, the key is written to an
+//! encrypted file instead. That fallback is sealed with a random per-install
+//! key (mixed with the machine id on Linux) and only protects against the
+//! data directory being copied elsewhere — it is not a substitute for an
+//! unlocked keyring.
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use rand::RngCore;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use zeroize::Zeroizing;
+
+/// Service name under which credentials are filed in the OS keychain.
+const SERVICE: &str = "com.nodes.desktop";
+
+const FILE_KEY_NAME: &str = "vault.key";
+const NONCE_LENGTH: usize = 12;
+
+#[derive(Debug, thiserror::Error)]
+pub enum VaultError {
+    #[error("Secret store unavailable: {0}")]
+    Unavailable(String),
+    #[error("Stored credential is corrupt")]
+    Corrupt,
+    #[error("Vault I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Where a credential ended up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum VaultBackend {
+    Keychain,
+    EncryptedFile,
+}
+
+/// A place secrets can be kept, keyed by account (the identity's public key).
+pub trait SecretStore: Send + Sync {
+    fn get(&self, account: &str) -> Result<Option<Vec<u8>>, VaultError>;
+    fn set(&self, account: &str, secret: &[u8]) -> Result<(), VaultError>;
+    fn delete(&self, account: &str) -> Result<(), VaultError>;
+}
+
+/// Platform secret store via the `keyring` crate.
+pub struct KeychainStore;
+
+impl KeychainStore {
+    fn entry(account: &str) -> Result<keyring::Entry, VaultError> {
+        keyring::Entry::new(SERVICE, account).map_err(|e| VaultError::Unavailable(e.to_string()))
+    }
+}
+
+impl SecretStore for KeychainStore {
+    fn get(&self, account: &str) -> Result<Option<Vec<u8>>, VaultError> {
+        match Self::entry(account)?.get_password() {
+            Ok(encoded) => BASE64
+                .decode(encoded)
+                .map(Some)
+                .map_err(|_| VaultError::Corrupt),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(VaultError::Unavailable(e.to_string())),
+        }
+    }
+
+    fn set(&self, account: &str, secret: &[u8]) -> Result<(), VaultError> {
+        Self::entry(account)?
+            .set_password(&BASE64.encode(secret))
+            .map_err(|e| VaultError::Unavailable(e.to_string()))
+    }
+
+    fn delete(&self, account: &str) -> Result<(), VaultError> {
+        match Self::entry(account)?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(VaultError::Unavailable(e.to_string())),
+        }
+    }
+}
+
+/// Encrypted-file fallback used when no keychain agent is available.
+pub struct FileStore {
+    dir: PathBuf,
+}
+
+impl FileStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    fn path_for(&self, account: &str) -> PathBuf {
+        let digest = Sha256::digest(account.as_bytes());
+        let stem: String = digest[..16].iter().map(|b| format!("{b:02x}")).collect();
+        self.dir.join(format!("{stem}.secret"))
+    }
+
+    /// Load or create the per-install sealing key.
+    fn sealing_key(&self) -> Result<Zeroizing<[u8; 32]>, VaultError> {
+        let path = self.dir.join(FILE_KEY_NAME);
+        let raw = match fs::read(&path) {
+            Ok(raw) if raw.len() == 32 => Zeroizing::new(raw),
+            Ok(_) => return Err(VaultError::Corrupt),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                let mut raw = Zeroizing::new(vec![0u8; 32]);
+                rand::thread_rng().fill_bytes(&mut raw);
+                write_private(&path, &raw)?;
+                raw
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut hasher = Sha256::new();
+        hasher.update(raw.as_slice());
+        if let Ok(machine_id) = fs::read("/etc/machine-id") {
+            hasher.update(machine_id.trim_ascii());
+        }
+        let mut key = Zeroizing::new([0u8; 32]);
+        key.copy_from_slice(&hasher.finalize());
+        Ok(key)
+    }
+}
+
+impl SecretStore for FileStore {
+    fn get(&self, account: &str) -> Result<Option<Vec<u8>>, VaultError> {
+        let sealed = match fs::read(self.path_for(account)) {
+            Ok(sealed) => sealed,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+        if sealed.len() <= NONCE_LENGTH {
+            return Err(VaultError::Corrupt);
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LENGTH);
+        let key = self.sealing_key()?;
+        Aes256Gcm::new(key.as_slice().into())
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: account.as_bytes(),
+                },
+            )
+            .map(Some)
+            .map_err(|_| VaultError::Corrupt)
+    }
+
+    fn set(&self, account: &str, secret: &[u8]) -> Result<(), VaultError> {
+        fs::create_dir_all(&self.dir)?;
+        let key = self.sealing_key()?;
+        let mut nonce = [0u8; NONCE_LENGTH];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let ciphertext = Aes256Gcm::new(key.as_slice().into())
+            .encrypt(
+                Nonce::from_slice(&nonce),
+                Payload {
+                    msg: secret,
+                    aad: account.as_bytes(),
+                },
+            )
+            .map_err(|_| VaultError::Corrupt)?;
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        write_private(&self.path_for(account), &sealed)?;
+        Ok(())
+    }
+
+    fn delete(&self, account: &str) -> Result<(), VaultError> {
+        match fs::remove_file(self.path_for(account)) {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+/// Keychain first, encrypted file when the keychain is unreachable.
+pub struct Vault {
+    primary: Box<dyn SecretStore>,
+    fallback: Box<dyn SecretStore>,
+}
+
+impl Vault {
+    pub fn new(primary: Box<dyn SecretStore>, fallback: Box<dyn SecretStore>) -> Self {
+        Self { primary, fallback }
+    }
+
+    /// The production vault: OS keychain with a file fallback in `dir`.
+    pub fn platform(dir: PathBuf) -> Self {
+        Self::new(Box::new(KeychainStore), Box::new(FileStore::new(dir)))
+    }
+
+    pub fn store(&self, account: &str, secret: &[u8]) -> Result<VaultBackend, VaultError> {
+        match self.primary.set(account, secret) {
+            Ok(()) => {
+                // Don't leave a stale copy behind from an earlier fallback.
+                let _ = self.fallback.delete(account);
+                Ok(VaultBackend::Keychain)
+            }
+            Err(VaultError::Unavailable(reason)) => {
+                eprintln!("[vault] keychain unavailable, using encrypted file: {reason}");
+                self.fallback.set(account, secret)?;
+                Ok(VaultBackend::EncryptedFile)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn fetch(&self, account: &str) -> Result<Option<Zeroizing<Vec<u8>>>, VaultError> {
+        match self.primary.get(account) {
+            Ok(Some(secret)) => return Ok(Some(Zeroizing::new(secret))),
+            Ok(None) | Err(VaultError::Unavailable(_)) => {}
+            Err(e) => return Err(e),
+        }
+        Ok(self.fallback.get(account)?.map(Zeroizing::new))
+    }
+
+    /// Remove the credential from every backend.
+    pub fn forget(&self, account: &str) -> Result<(), VaultError> {
+        let primary = self.primary.delete(account);
+        self.fallback.delete(account)?;
+        match primary {
+            Ok(()) | Err(VaultError::Unavailable(_)) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+fn write_private(path: &std::path::Path, bytes: &[u8]) -> std::io::Result<()> {
+    let mut options = OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path)?;
+    file.write_all(bytes)?;
+    file.sync_all()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    /// In-memory stand-in for the secret service, which can be switched off.
+    #[derive(Clone, Default)]
+    struct MemoryStore {
+        secrets: Arc<Mutex<HashMap<String, Vec<u8>>>>,
+        down: Arc<AtomicBool>,
+    }
+
+    impl MemoryStore {
+        fn unavailable() -> Self {
+            let store = Self::default();
+            store.down.store(true, Ordering::SeqCst);
+            store
+        }
+
+        fn check(&self) -> Result<(), VaultError> {
+            if self.down.load(Ordering::SeqCst) {
+                return Err(VaultError::Unavailable("no agent".into()));
+            }
+            Ok(())
+        }
+
+        fn holds(&self, account: &str) -> Option<Vec<u8>> {
+            self.secrets.lock().unwrap().get(account).cloned()
+        }
+    }
+
+    impl SecretStore for MemoryStore {
+        fn get(&self, account: &str) -> Result<Option<Vec<u8>>, VaultError> {
+            self.check()?;
+            Ok(self.holds(account))
+        }
+
+        fn set(&self, account: &str, secret: &[u8]) -> Result<(), VaultError> {
+            self.check()?;
+            self.secrets
+                .lock()
+                .unwrap()
+                .insert(account.into(), secret.to_vec());
+            Ok(())
+        }
+
+        fn delete(&self, account: &str) -> Result<(), VaultError> {
+            self.check()?;
+            self.secrets.lock().unwrap().remove(account);
+            Ok(())
+        }
+    }
+
+    fn vault(primary: &MemoryStore, fallback: &MemoryStore) -> Vault {
+        Vault::new(Box::new(primary.clone()), Box::new(fallback.clone()))
+    }
+
+    #[test]
+    fn falls_back_when_keychain_unavailable() {
+        let (primary, fallback) = (MemoryStore::unavailable(), MemoryStore::default());
+        let vault = vault(&primary, &fallback);
+
+        assert_eq!(
+            vault.store("alice", b"key").unwrap(),
+            VaultBackend::EncryptedFile
+        );
+        assert_eq!(fallback.holds("alice").as_deref(), Some(&b"key"[..]));
+        assert_eq!(vault.fetch("alice").unwrap().unwrap().as_slice(), b"key");
+        assert!(vault.fetch("bob").unwrap().is_none());
+    }
+
+    #[test]
+    fn keychain_replaces_stale_fallback() {
+        let (primary, fallback) = (MemoryStore::unavailable(), MemoryStore::default());
+        let vault = vault(&primary, &fallback);
+        vault.store("alice", b"old").unwrap();
+
+        primary.down.store(false, Ordering::SeqCst);
+        assert_eq!(
+            vault.store("alice", b"new").unwrap(),
+            VaultBackend::Keychain
+        );
+        assert_eq!(primary.holds("alice").as_deref(), Some(&b"new"[..]));
+        assert_eq!(fallback.holds("alice"), None);
+        assert_eq!(vault.fetch("alice").unwrap().unwrap().as_slice(), b"new");
+    }
+
+    #[test]
+    fn forget_clears_every_backend() {
+        let (primary, fallback) = (MemoryStore::default(), MemoryStore::default());
+        primary.set("alice", b"key").unwrap();
+        fallback.set("alice", b"key").unwrap();
+        vault(&primary, &fallback).forget("alice").unwrap();
+        assert_eq!(primary.holds("alice"), None);
+        assert_eq!(fallback.holds("alice"), None);
+
+        // An unreachable keychain has nothing to forget.
+        let primary = MemoryStore::unavailable();
+        fallback.set("alice", b"key").unwrap();
+        vault(&primary, &fallback).forget("alice").unwrap();
+        assert_eq!(fallback.holds("alice"), None);
+    }
+
+    #[test]
+    fn file_store_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = FileStore::new(dir.path().join("vault"));
+
+        assert_eq!(store.get("alice").unwrap(), None);
+        store.set("alice", b"wrapping key").unwrap();
+        assert_eq!(
+            store.get("alice").unwrap().as_deref(),
+            Some(&b"wrapping key"[..])
+        );
+        assert_eq!(store.get("bob").unwrap(), None);
+
+        // The sealing key persists, so a fresh instance can still read it.
+        let reopened = FileStore::new(dir.path().join("vault"));
+        assert_eq!(
+            reopened.get("alice").unwrap().as_deref(),
+            Some(&b"wrapping key"[..])
+        );
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(store.path_for("alice"))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let path = store.path_for("alice");
+        let mut sealed = fs::read(&path).unwrap();
+        *sealed.last_mut().unwrap() ^= 1;
+        fs::write(&path, sealed).unwrap();
+        assert!(matches!(store.get("alice"), Err(VaultError::Corrupt)));
+
+        store.delete("alice").unwrap();
+        store.delete("alice").unwrap();
+        assert_eq!(store.get("alice").unwrap(), None);
+    }
+}
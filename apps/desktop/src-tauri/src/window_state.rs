@@ -0,0 +1,477 @@
+//! Window position and size, remembered across launches.
+//!
+//! For each window label, `window-state.json` in the app config directory
+//! keeps the last normal (not maximized) bounds, whether the window was
+//! maximized, and which monitor it was on. Moving or resizing updates the
+//! in-memory copy right away. The file is written once the window has been
+//! still for [`SAVE_DELAY`], and anything pending is written on exit.
+//!
+//! Monitors can change between launches, so a saved window goes back to the
+//! same monitor if it's still connected, otherwise to the monitor it overlaps
+//! most, otherwise to the primary one. It is then shrunk and moved to fit
+//! inside that monitor's work area, so it never reopens off-screen.
+//! Positions and sizes are physical pixels; sizes are rescaled when the
+//! monitor's scale factor differs from the saved one.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Sender};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tauri::{
+    Manager, Monitor, PhysicalPosition, PhysicalSize, Runtime, WebviewWindow, WindowEvent,
+};
+
+use crate::durable;
+
+/// How long a window has to stay still before its geometry is written.
+const SAVE_DELAY: Duration = Duration::from_millis(500);
+
+/// A rectangle in physical pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bounds {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Bounds {
+    fn overlap(&self, other: &Bounds) -> i64 {
+        let span = |start: i32, len: u32, other_start: i32, other_len: u32| {
+            let end = (start as i64 + len as i64).min(other_start as i64 + other_len as i64);
+            (end - (start as i64).max(other_start as i64)).max(0)
+        };
+        span(self.x, self.width, other.x, other.width)
+            * span(self.y, self.height, other.y, other.height)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WindowGeometry {
+    /// Outer position and inner size while not maximized.
+    pub bounds: Bounds,
+    pub maximized: bool,
+    /// Name of the monitor the window was on, if the OS reports one.
+    pub monitor: Option<String>,
+    /// Scale factor `bounds` were measured at.
+    pub scale: f64,
+}
+
+/// A connected monitor.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Screen {
+    pub name: Option<String>,
+    /// The monitor minus taskbars, docks and menu bars.
+    pub work_area: Bounds,
+    pub scale: f64,
+}
+
+impl From<&Monitor> for Screen {
+    fn from(monitor: &Monitor) -> Self {
+        let area = monitor.work_area();
+        Self {
+            name: monitor.name().cloned(),
+            work_area: Bounds {
+                x: area.position.x,
+                y: area.position.y,
+                width: area.size.width,
+                height: area.size.height,
+            },
+            scale: monitor.scale_factor(),
+        }
+    }
+}
+
+/// Where to put a window saved as `saved`, given the connected `screens`
+/// with the primary one first and the size the window's frame adds to its
+/// inner size. `None` if there are no screens.
+pub fn place(
+    saved: &WindowGeometry,
+    screens: &[Screen],
+    frame: (u32, u32),
+) -> Option<WindowGeometry> {
+    let screen = screens
+        .iter()
+        .find(|screen| saved.monitor.is_some() && screen.name == saved.monitor)
+        .or_else(|| {
+            screens
+                .iter()
+                .map(|screen| (screen, screen.work_area.overlap(&saved.bounds)))
+                .filter(|&(_, overlap)| overlap > 0)
+                .max_by_key(|&(_, overlap)| overlap)
+                .map(|(screen, _)| screen)
+        })
+        .or(screens.first())?;
+
+    let ratio = if saved.scale > 0.0 {
+        screen.scale / saved.scale
+    } else {
+        1.0
+    };
+    let area = screen.work_area;
+    let fit = |len: u32, frame: u32, available: u32| {
+        ((len as f64 * ratio).round() as u32).min(available.saturating_sub(frame))
+    };
+    let width = fit(saved.bounds.width, frame.0, area.width);
+    let height = fit(saved.bounds.height, frame.1, area.height);
+    let clamp = |pos: i32, len: u32, start: i32, available: u32| {
+        pos.clamp(start, start + available.saturating_sub(len) as i32)
+    };
+    Some(WindowGeometry {
+        bounds: Bounds {
+            x: clamp(saved.bounds.x, width + frame.0, area.x, area.width),
+            y: clamp(saved.bounds.y, height + frame.1, area.y, area.height),
+            width,
+            height,
+        },
+        maximized: saved.maximized,
+        monitor: screen.name.clone(),
+        scale: screen.scale,
+    })
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum WindowStateError {
+    #[error("Failed to save window state: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to save window state: {0}")]
+    Json(#[from] serde_json::Error),
+}
+
+/// Saved geometry per window label. One instance is managed by Tauri.
+pub struct WindowStates {
+    store: Arc<Store>,
+    changed: Sender<()>,
+}
+
+struct Store {
+    path: PathBuf,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    windows: BTreeMap<String, WindowGeometry>,
+    /// Changed since the file was last written.
+    dirty: bool,
+}
+
+impl WindowStates {
+    /// Load from `path`, starting empty if the file is missing or
+    /// unreadable, and start the thread that writes changes back.
+    pub fn load(path: PathBuf) -> Self {
+        let windows = match fs::read(&path) {
+            Ok(bytes) => serde_json::from_slice(&bytes).unwrap_or_else(|e| {
+                eprintln!("[window-state] Ignoring unreadable window state: {e}");
+                BTreeMap::new()
+            }),
+            Err(e) if e.kind() == ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => {
+                eprintln!("[window-state] Failed to read window state: {e}");
+                BTreeMap::new()
+            }
+        };
+        let store = Arc::new(Store {
+            path,
+            state: Mutex::new(State {
+                windows,
+                dirty: false,
+            }),
+        });
+
+        let (changed, changes) = mpsc::channel();
+        let writer = store.clone();
+        std::thread::spawn(move || {
+            while changes.recv().is_ok() {
+                // Wait for the window to settle.
+                while changes.recv_timeout(SAVE_DELAY).is_ok() {}
+                if let Err(e) = writer.save() {
+                    eprintln!("[window-state] {e}");
+                }
+            }
+        });
+        Self { store, changed }
+    }
+
+    pub fn get(&self, label: &str) -> Option<WindowGeometry> {
+        self.store.lock().windows.get(label).cloned()
+    }
+
+    /// Write pending changes now rather than after the delay.
+    pub fn flush(&self) -> Result<(), WindowStateError> {
+        self.store.save()
+    }
+
+    fn record<R: Runtime>(&self, window: &WebviewWindow<R>) -> tauri::Result<()> {
+        let previous = self.get(window.label());
+        let Some(geometry) = capture(window, previous)? else {
+            return Ok(());
+        };
+        let mut state = self.store.lock();
+        if state.windows.get(window.label()) != Some(&geometry) {
+            state.windows.insert(window.label().to_string(), geometry);
+            state.dirty = true;
+            let _ = self.changed.send(());
+        }
+        Ok(())
+    }
+}
+
+impl Store {
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Write the file if anything changed. The lock is held throughout so
+    /// the writer thread and [`WindowStates::flush`] don't interleave.
+    fn save(&self) -> Result<(), WindowStateError> {
+        let mut state = self.lock();
+        if !state.dirty {
+            return Ok(());
+        }
+        durable::write_atomic(&self.path, &serde_json::to_vec_pretty(&state.windows)?)?;
+        state.dirty = false;
+        Ok(())
+    }
+}
+
+/// The window's geometry now, or `None` while it's minimized. A maximized
+/// window keeps the bounds it had before, so unmaximizing after a relaunch
+/// returns it to them.
+fn capture<R: Runtime>(
+    window: &WebviewWindow<R>,
+    previous: Option<WindowGeometry>,
+) -> tauri::Result<Option<WindowGeometry>> {
+    if window.is_minimized()? {
+        return Ok(None);
+    }
+    let monitor = window.current_monitor()?;
+    let name = monitor.as_ref().and_then(|m| m.name().cloned());
+    let maximized = window.is_maximized()?;
+    if maximized {
+        if let Some(previous) = previous {
+            return Ok(Some(WindowGeometry {
+                maximized: true,
+                monitor: name,
+                ..previous
+            }));
+        }
+    }
+    let position = window.outer_position()?;
+    let size = window.inner_size()?;
+    Ok(Some(WindowGeometry {
+        bounds: Bounds {
+            x: position.x,
+            y: position.y,
+            width: size.width,
+            height: size.height,
+        },
+        maximized,
+        monitor: name,
+        scale: match monitor {
+            Some(monitor) => monitor.scale_factor(),
+            None => window.scale_factor()?,
+        },
+    }))
+}
+
+/// Put `window` back where it was last time, if it has been open before.
+/// Call before showing it.
+pub fn restore<R: Runtime>(window: &WebviewWindow<R>) {
+    let Some(saved) = window.state::<WindowStates>().get(window.label()) else {
+        return;
+    };
+    if let Err(e) = apply(window, &saved) {
+        eprintln!(
+            "[window-state] Failed to restore window {}: {e}",
+            window.label()
+        );
+    }
+}
+
+fn apply<R: Runtime>(window: &WebviewWindow<R>, saved: &WindowGeometry) -> tauri::Result<()> {
+    let primary = window.primary_monitor()?.map(|m| Screen::from(&m));
+    let mut screens: Vec<Screen> = window
+        .available_monitors()?
+        .iter()
+        .map(Screen::from)
+        .collect();
+    screens.sort_by_key(|screen| Some(screen) != primary.as_ref());
+    let outer = window.outer_size()?;
+    let inner = window.inner_size()?;
+    let frame = (
+        outer.width.saturating_sub(inner.width),
+        outer.height.saturating_sub(inner.height),
+    );
+    let Some(placed) = place(saved, &screens, frame) else {
+        return Ok(());
+    };
+
+    // Move first: crossing to a monitor with another scale factor resizes
+    // the window on Windows.
+    let bounds = placed.bounds;
+    window.set_position(PhysicalPosition::new(bounds.x, bounds.y))?;
+    window.set_size(PhysicalSize::new(bounds.width, bounds.height))?;
+    if placed.maximized {
+        window.maximize()?;
+    }
+    Ok(())
+}
+
+/// Record `window`'s geometry whenever it moves or is resized.
+pub fn track<R: Runtime>(window: &WebviewWindow<R>) {
+    let app = window.app_handle().clone();
+    let label = window.label().to_string();
+    window.on_window_event(move |event| {
+        if !matches!(event, WindowEvent::Moved(_) | WindowEvent::Resized(_)) {
+            return;
+        }
+        let Some(window) = app.get_webview_window(&label) else {
+            return;
+        };
+        if let Err(e) = app.state::<WindowStates>().record(&window) {
+            eprintln!("[window-state] Failed to read window {label}: {e}");
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bounds(x: i32, y: i32, width: u32, height: u32) -> Bounds {
+        Bounds {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    fn screen(name: &str, work_area: Bounds, scale: f64) -> Screen {
+        Screen {
+            name: Some(name.into()),
+            work_area,
+            scale,
+        }
+    }
+
+    fn saved(bounds: Bounds, monitor: Option<&str>, scale: f64) -> WindowGeometry {
+        WindowGeometry {
+            bounds,
+            maximized: false,
+            monitor: monitor.map(Into::into),
+            scale,
+        }
+    }
+
+    /// A primary 1920x1040 screen and a second one to its right.
+    fn two_screens() -> Vec<Screen> {
+        vec![
+            screen("primary", bounds(0, 0, 1920, 1040), 1.0),
+            screen("right", bounds(1920, 0, 2560, 1400), 1.0),
+        ]
+    }
+
+    fn screen_for(saved: &WindowGeometry, screens: &[Screen]) -> Option<String> {
+        place(saved, screens, (0, 0)).unwrap().monitor
+    }
+
+    #[test]
+    fn picks_the_saved_then_most_overlapped_then_primary_screen() {
+        let screens = two_screens();
+        // The named monitor wins even if the bounds are on another one.
+        let on_right = saved(bounds(100, 100, 800, 600), Some("right"), 1.0);
+        assert_eq!(screen_for(&on_right, &screens).as_deref(), Some("right"));
+        let placed = place(&on_right, &screens, (0, 0)).unwrap();
+        assert_eq!(placed.bounds, bounds(1920, 100, 800, 600));
+
+        // A disconnected monitor falls back to the one overlapped most.
+        let straddling = saved(bounds(1700, 100, 800, 600), Some("gone"), 1.0);
+        assert_eq!(screen_for(&straddling, &screens).as_deref(), Some("right"));
+        let straddling = saved(bounds(1200, 100, 800, 600), None, 1.0);
+        assert_eq!(
+            screen_for(&straddling, &screens).as_deref(),
+            Some("primary")
+        );
+
+        // Off every screen: the primary one, which is listed first.
+        let lost = saved(bounds(-5000, 3000, 800, 600), Some("gone"), 1.0);
+        assert_eq!(screen_for(&lost, &screens).as_deref(), Some("primary"));
+        let placed = place(&lost, &screens, (0, 0)).unwrap();
+        assert_eq!(placed.bounds, bounds(0, 440, 800, 600));
+
+        assert_eq!(place(&lost, &[], (0, 0)), None);
+    }
+
+    #[test]
+    fn clamps_into_a_work_area_left_of_and_above_the_primary() {
+        let screens = [screen("left", bounds(-1920, -200, 1920, 1080), 1.0)];
+        let frame = (16, 39);
+        let place_at = |x, y| {
+            let window = saved(bounds(x, y, 800, 600), Some("left"), 1.0);
+            place(&window, &screens, frame).unwrap().bounds
+        };
+        assert_eq!(place_at(-3000, -500), bounds(-1920, -200, 800, 600));
+        assert_eq!(place_at(-1000, 0), bounds(-1000, 0, 800, 600));
+        // The frame counts towards the right and bottom edges.
+        assert_eq!(place_at(-100, 600), bounds(-816, 241, 800, 600));
+    }
+
+    #[test]
+    fn shrinks_a_window_larger_than_the_work_area() {
+        let screens = two_screens();
+        let huge = WindowGeometry {
+            maximized: true,
+            ..saved(bounds(-50, -50, 3000, 2000), Some("primary"), 1.0)
+        };
+        let placed = place(&huge, &screens, (16, 39)).unwrap();
+        assert_eq!(placed.bounds, bounds(0, 0, 1904, 1001));
+        assert!(placed.maximized);
+    }
+
+    #[test]
+    fn rescales_between_scale_factors() {
+        let screens = [
+            screen("standard", bounds(0, 0, 1920, 1040), 1.0),
+            screen("hidpi", bounds(1920, 0, 3840, 2110), 2.0),
+        ];
+        let standard = saved(bounds(100, 100, 800, 600), Some("standard"), 1.0);
+        let moved = WindowGeometry {
+            monitor: Some("hidpi".into()),
+            ..standard.clone()
+        };
+        let placed = place(&moved, &screens, (0, 0)).unwrap();
+        assert_eq!(placed.bounds, bounds(1920, 100, 1600, 1200));
+        assert_eq!(placed.scale, 2.0);
+
+        let hidpi = saved(bounds(100, 100, 1600, 1200), Some("standard"), 2.0);
+        let back = place(&hidpi, &screens, (0, 0)).unwrap();
+        assert_eq!(back.bounds, bounds(100, 100, 800, 600));
+        assert_eq!(back.scale, 1.0);
+
+        // Fractional factors round to whole pixels.
+        let odd = saved(bounds(0, 0, 801, 601), Some("standard"), 1.5);
+        let placed = place(&odd, &screens, (0, 0)).unwrap();
+        assert_eq!((placed.bounds.width, placed.bounds.height), (534, 401));
+
+        // A missing scale is taken as unchanged.
+        let unknown = saved(bounds(0, 0, 800, 600), Some("hidpi"), 0.0);
+        let placed = place(&unknown, &screens, (0, 0)).unwrap();
+        assert_eq!((placed.bounds.width, placed.bounds.height), (800, 600));
+    }
+
+    #[test]
+    fn survives_a_frame_larger_than_the_work_area() {
+        let screens = [screen("tiny", bounds(-10, 20, 300, 200), 1.0)];
+        let window = saved(bounds(500, 500, 800, 600), Some("tiny"), 1.0);
+        let placed = place(&window, &screens, (400, 250)).unwrap();
+        assert_eq!(placed.bounds, bounds(-10, 20, 0, 0));
+    }
+}
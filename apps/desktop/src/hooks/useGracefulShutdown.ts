@@ -4,21 +4,28 @@
  */
 import { useEffect } from "react";
 import { listen } from "@tauri-apps/api/event";
+import { invoke } from "@tauri-apps/api/core";
 import { useTransport } from "../providers/TransportProvider";
 
+const SUBSCRIBER = "presence";
+
 /**
  * Listen for app:before-quit event from Tauri and perform cleanup.
  * This includes:
  * - Setting user's presence to offline
  * - Any other necessary cleanup
+ *
+ * The app waits for our acknowledgement (up to a deadline) before exiting.
  */
 export function useGracefulShutdown() {
   const transport = useTransport();
 
   useEffect(() => {
+    invoke("shutdown_register", { subscriber: SUBSCRIBER }).catch(console.error);
+
     const unlisten = listen("app:before-quit", async () => {
       console.log("[Shutdown] Received quit signal, cleaning up...");
-      
+
       try {
         // Set presence to offline
         if (transport) {
@@ -27,12 +34,15 @@ export function useGracefulShutdown() {
         }
       } catch (error) {
         console.error("[Shutdown] Cleanup error:", error);
+      } finally {
+        await invoke("shutdown_ack", { subscriber: SUBSCRIBER }).catch(console.error);
       }
     });
 
     // Cleanup listener on unmount
     return () => {
       unlisten.then((fn) => fn());
+      invoke("shutdown_unregister", { subscriber: SUBSCRIBER }).catch(console.error);
     };
   }, [transport]);
 }
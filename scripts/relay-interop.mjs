@@ -0,0 +1,143 @@
+/**
+ * Checks the desktop app's built-in relay (src-tauri/src/relay) with real Gun
+ * clients.
+ *
+ * Run with: node scripts/relay-interop.mjs [port]
+ * With the relay turned on in the app's settings (port 8765 by default). Two
+ * separate Gun peers connect to ws://127.0.0.1:<port>/gun: one writes a node,
+ * the other reads it back through the relay and follows an update to it. A bare
+ * WebSocket peer then writes conflicting states, and a third Gun peer checks
+ * that the relay kept the ones HAM picks.
+ *
+ * `pnpm --filter @nodes/desktop test:relay` starts a relay on a free port and
+ * runs this against it.
+ */
+import { createRequire } from "module";
+
+const require = createRequire(import.meta.url);
+const gunPath = require.resolve("../packages/transport-gun/node_modules/gun");
+const Gun = require(gunPath);
+// Gun's own WebSocket client.
+const WebSocket = createRequire(gunPath)("ws");
+
+const port = Number(process.argv[2] ?? 8765);
+const url = `ws://127.0.0.1:${port}/gun`;
+const TIMEOUT_MS = 5000;
+
+/** A peer that only knows what the relay tells it. */
+function peer() {
+  return Gun({
+    peers: [url],
+    localStorage: false,
+    radisk: false,
+    file: false,
+    axe: false,
+    multicast: false,
+  });
+}
+
+/** A raw connection, to write with states of our choosing. */
+function wire() {
+  const ws = new WebSocket(url);
+  return new Promise((resolve, reject) => {
+    ws.once("open", () => resolve(ws));
+    ws.once("error", reject);
+  });
+}
+
+/** Put one field with the given HAM state and wait for the relay's ack. */
+function say(ws, soul, field, value, state) {
+  const id = Math.random().toString(36).slice(2);
+  return new Promise((resolve, reject) => {
+    const onMessage = (data) => {
+      for (const message of [].concat(JSON.parse(data))) {
+        if (message["@"] !== id) continue;
+        ws.off("message", onMessage);
+        return message.err ? reject(new Error(message.err)) : resolve();
+      }
+    };
+    ws.on("message", onMessage);
+    const node = { _: { "#": soul, ">": { [field]: state } }, [field]: value };
+    ws.send(JSON.stringify({ "#": id, put: { [soul]: node } }));
+  });
+}
+
+function within(what, promise) {
+  const timeout = new Promise((_, reject) =>
+    setTimeout(() => reject(new Error(`no ${what} after ${TIMEOUT_MS}ms`)), TIMEOUT_MS),
+  );
+  return Promise.race([promise, timeout]);
+}
+
+let failed = 0;
+async function check(name, run) {
+  try {
+    await run();
+    console.log("ok", name);
+  } catch (e) {
+    console.log("FAILED", name, e.message);
+    failed += 1;
+  }
+}
+
+const soul = `relay-interop/${Date.now()}-${Math.random().toString(36).slice(2)}`;
+const ALICE = { name: "Alice", count: 1, friend: { name: "Bob" } };
+const writer = peer();
+const reader = peer();
+
+await check("put is acknowledged", () => {
+  const acked = new Promise((resolve, reject) =>
+    writer.get(soul).put(ALICE, (ack) => (ack.err ? reject(new Error(ack.err)) : resolve())),
+  );
+  return within("ack", acked);
+});
+
+await check("another peer reads it back", async () => {
+  const node = await within("read", new Promise((resolve) => reader.get(soul).once(resolve)));
+  if (node?.name !== "Alice" || node?.count !== 1) {
+    throw new Error(`read ${JSON.stringify(node)}`);
+  }
+});
+
+await check("links are followed", async () => {
+  const name = await within(
+    "linked read",
+    new Promise((resolve) => reader.get(soul).get("friend").get("name").once(resolve)),
+  );
+  if (name !== "Bob") {
+    throw new Error(`read ${JSON.stringify(name)}`);
+  }
+});
+
+await check("updates reach subscribers", () => {
+  const seen = new Promise((resolve) =>
+    reader
+      .get(soul)
+      .get("count")
+      .on((count) => count === 2 && resolve()),
+  );
+  writer.get(soul).put({ count: 2 });
+  return within("update", seen);
+});
+
+await check("conflicting writes are resolved by HAM", async () => {
+  const conflicts = `${soul}/conflicts`;
+  const state = Date.now() - 60_000;
+  const ws = await within("connection", wire());
+  // The newer state wins whatever the order they arrive in; on equal states
+  // the larger value does.
+  await within("ack", say(ws, conflicts, "count", 10, state));
+  await within("ack", say(ws, conflicts, "count", 5, state - 1));
+  await within("ack", say(ws, conflicts, "first", "a", state));
+  await within("ack", say(ws, conflicts, "first", "b", state));
+  await within("ack", say(ws, conflicts, "second", "b", state));
+  await within("ack", say(ws, conflicts, "second", "a", state));
+  ws.close();
+
+  const node = await within("read", new Promise((resolve) => peer().get(conflicts).once(resolve)));
+  if (node?.count !== 10 || node?.first !== "b" || node?.second !== "b") {
+    throw new Error(`read ${JSON.stringify(node)}`);
+  }
+});
+
+process.exit(failed ? 1 : 0);
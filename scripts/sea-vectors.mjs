@@ -0,0 +1,56 @@
+/**
+ * Interop vectors for the desktop app's native SEA (src-tauri/src/crypto/sea.rs).
+ *
+ * Run with: node scripts/sea-vectors.mjs
+ * Prints fresh vectors from gun/sea as JSON, for the tests in sea.rs.
+ *
+ * Run with: node scripts/sea-vectors.mjs check <file.json>
+ * Checks output of the native code with gun/sea. The file holds
+ * { pub, signed: [..], key, encrypted: [..] } and each entry must verify
+ * or decrypt.
+ */
+import { createRequire } from "module";
+import { readFileSync } from "fs";
+
+const require = createRequire(import.meta.url);
+const SEA = require("../packages/crypto/node_modules/gun/sea");
+
+if (process.argv[2] === "check") {
+  const input = JSON.parse(readFileSync(process.argv[3], "utf8"));
+  let failed = 0;
+  for (const signed of input.signed) {
+    const message = await SEA.verify(signed, input.pub);
+    console.log("verify", message === undefined ? "FAILED" : JSON.stringify(message));
+    failed += message === undefined;
+  }
+  for (const encrypted of input.encrypted) {
+    const plain = await SEA.decrypt(encrypted, input.key);
+    console.log("decrypt", plain === undefined ? "FAILED" : JSON.stringify(plain));
+    failed += plain === undefined;
+  }
+  process.exit(failed ? 1 : 0);
+}
+
+const alice = await SEA.pair();
+const bob = await SEA.pair();
+const secret = await SEA.secret(bob.epub, alice);
+const vectors = {
+  alice,
+  bob,
+  work: {
+    data: "correct horse",
+    salt: alice.pub,
+    hash: await SEA.work("correct horse", alice.pub),
+  },
+  signed: {
+    object: await SEA.sign({ text: "hello", n: 1.5, 10: "ten", 2: "two" }, alice),
+    string: await SEA.sign("plain text", alice),
+    json: await SEA.sign(JSON.stringify({ id: "msg-1" }), alice),
+  },
+  secret,
+  encrypted: {
+    object: await SEA.encrypt({ text: "hi", at: 1700000000000 }, secret),
+    string: await SEA.encrypt("plain text", "passphrase"),
+  },
+};
+console.log(JSON.stringify(vectors, null, 2));